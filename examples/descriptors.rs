@@ -29,6 +29,10 @@ fn inspect_device(dev: DeviceInfo) {
 
     println!("Speed: {:?}", dev.speed());
 
+    for diagnostic in dev.device_descriptor().validate(dev.speed()) {
+        println!("Device descriptor problem {diagnostic}");
+    }
+
     match dev.active_configuration() {
         Ok(config) => println!("Active configuration is {}", config.configuration_value()),
         Err(e) => println!("Unknown active configuration: {e}"),
@@ -36,6 +40,10 @@ fn inspect_device(dev: DeviceInfo) {
 
     for config in dev.configurations() {
         println!("{config:#?}");
+
+        for diagnostic in config.validate(dev.speed()) {
+            println!("Configuration descriptor problem {diagnostic}");
+        }
     }
     println!();
     println!();
@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+use nusb::{lsusb::VerboseReport, MaybeFuture};
+
+fn main() {
+    env_logger::init();
+    for info in nusb::list_devices().wait().unwrap() {
+        match info.open().wait() {
+            Ok(device) => {
+                println!();
+                print!(
+                    "{}",
+                    VerboseReport::new(&info, &device, Duration::from_millis(100))
+                );
+            }
+            Err(e) => eprintln!(
+                "Failed to open device {:04x}:{:04x}: {e}",
+                info.vendor_id(),
+                info.product_id()
+            ),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Open devices through a privileged broker process.
+//!
+//! Instead of granting every user access to `/dev/bus/usb` with udev rules,
+//! a single privileged process can run a [`Broker`] on a Unix domain socket.
+//! Clients call [`DeviceInfo::open_via_broker`] with the socket path; the
+//! broker checks the requested device against its policy, opens it, and
+//! passes the file descriptor back over the socket (`SCM_RIGHTS`).
+//!
+//! ```no_run
+//! use std::os::unix::net::UnixListener;
+//! use nusb::{broker::Broker, MaybeFuture};
+//!
+//! // In the privileged broker process
+//! let listener = UnixListener::bind("/run/usb-broker.sock").unwrap();
+//! let broker = Broker::new(|dev| dev.vendor_id() == 0x1234 && dev.product_id() == 0x5678);
+//! broker.serve(&listener).unwrap();
+//! ```
+//!
+//! ```no_run
+//! use nusb::MaybeFuture;
+//!
+//! // In an unprivileged client process
+//! let device_info = nusb::list_devices().wait().unwrap()
+//!     .find(|dev| dev.vendor_id() == 0x1234 && dev.product_id() == 0x5678)
+//!     .expect("device not connected");
+//! let device = device_info.open_via_broker("/run/usb-broker.sock").wait().unwrap();
+//! ```
+//!
+//! The broker does not authenticate clients itself; restrict access to the
+//! socket with filesystem permissions.
+//!
+//! *Supported on Linux only.*
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    os::{
+        fd::{AsFd, OwnedFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    time::Duration,
+};
+
+use log::{debug, warn};
+
+use crate::{
+    maybe_future::blocking::Blocking,
+    platform::{self, fd_passing},
+    Device, DeviceInfo, Error, ErrorKind, MaybeFuture,
+};
+
+/// Maximum length of a request line.
+const MAX_REQUEST_LEN: u64 = 64;
+
+/// Time allowed for a client to send its request in [`Broker::serve`].
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Server side of the device broker.
+///
+/// See the [module documentation](self) for an example.
+pub struct Broker {
+    policy: Box<dyn Fn(&DeviceInfo) -> bool + Send + Sync>,
+    interface_mask: Option<InterfaceMask>,
+}
+
+type InterfaceMask = Box<dyn Fn(&DeviceInfo) -> u32 + Send + Sync>;
+
+impl Broker {
+    /// Create a broker that grants access to devices for which `policy`
+    /// returns `true`.
+    ///
+    /// The policy receives the broker's own [`DeviceInfo`] for the requested
+    /// device, so it can check fields such as the vendor ID, product ID and
+    /// serial number.
+    pub fn new(policy: impl Fn(&DeviceInfo) -> bool + Send + Sync + 'static) -> Self {
+        Broker {
+            policy: Box::new(policy),
+            interface_mask: None,
+        }
+    }
+
+    /// Restrict each device handed out to the interfaces in the mask returned
+    /// by `interface_mask`, before its file descriptor is sent.
+    ///
+    /// See [`Device::drop_privileges`] for the meaning of the mask. If the
+    /// kernel does not support restricting the handle, the request fails
+    /// rather than granting access to the whole device.
+    pub fn with_interface_mask(
+        mut self,
+        interface_mask: impl Fn(&DeviceInfo) -> u32 + Send + Sync + 'static,
+    ) -> Self {
+        self.interface_mask = Some(Box::new(interface_mask));
+        self
+    }
+
+    /// Accept connections on `listener` and serve one request from each,
+    /// until accepting a connection fails.
+    ///
+    /// Requests are served one at a time, and a client that does not send
+    /// its request within 5 seconds is disconnected. Refused requests are
+    /// logged and do not stop the broker.
+    pub fn serve(&self, listener: &UnixListener) -> std::io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+            if let Err(e) = self.handle(&stream) {
+                warn!("Broker request failed: {e}");
+            }
+        }
+    }
+
+    /// Serve a single request from a connected client.
+    ///
+    /// On success, the opened device's file descriptor has been sent to the
+    /// client. On error, the kind of the error has been sent to the client
+    /// if possible.
+    ///
+    /// This blocks until the client sends its request, unless a read timeout
+    /// is set on `stream`.
+    pub fn handle(&self, stream: &UnixStream) -> Result<(), Error> {
+        match self.open_requested(stream) {
+            Ok(device) => device.send_fd(stream),
+            Err(e) => {
+                let _ = fd_passing::send_error(stream.as_fd(), e.kind());
+                Err(e)
+            }
+        }
+    }
+
+    fn open_requested(&self, stream: &UnixStream) -> Result<Device, Error> {
+        let mut line = String::new();
+        BufReader::new(stream.take(MAX_REQUEST_LEN))
+            .read_line(&mut line)
+            .map_err(|e| Error::new_io(ErrorKind::Other, "failed to read broker request", e))?;
+
+        let (busnum, device_address) =
+            parse_request(&line).ok_or(Error::new(ErrorKind::Other, "invalid broker request"))?;
+        debug!("Broker request for device {busnum:03}/{device_address:03}");
+
+        let info = crate::list_devices()
+            .wait()?
+            .find(|d| d.busnum() == busnum && d.device_address() == device_address)
+            .ok_or(Error::new(ErrorKind::NotFound, "device not found"))?;
+
+        if !(self.policy)(&info) {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "device access denied by broker policy",
+            ));
+        }
+
+        let device = info.open().wait()?;
+        if let Some(interface_mask) = &self.interface_mask {
+            device.drop_privileges(interface_mask(&info))?;
+        }
+        Ok(device)
+    }
+}
+
+fn parse_request(line: &str) -> Option<(u8, u8)> {
+    let (bus, addr) = line.trim_end().split_once(' ')?;
+    Some((bus.parse().ok()?, addr.parse().ok()?))
+}
+
+/// Send a request for a device on a connected stream and receive its fd.
+fn request(mut stream: &UnixStream, busnum: u8, device_address: u8) -> Result<OwnedFd, Error> {
+    writeln!(stream, "{busnum} {device_address}")
+        .map_err(|e| Error::new_io(ErrorKind::Other, "failed to send broker request", e))?;
+    fd_passing::recv_fd(stream.as_fd())
+}
+
+pub(crate) fn open(
+    socket_path: PathBuf,
+    info: &DeviceInfo,
+) -> impl MaybeFuture<Output = Result<Device, Error>> {
+    let busnum = info.busnum();
+    let device_address = info.device_address();
+    let sysfs_path = info.path.clone();
+
+    Blocking::new(move || {
+        let stream = UnixStream::connect(&socket_path)
+            .map_err(|e| Error::new_io(ErrorKind::Other, "failed to connect to broker", e))?;
+        let fd = request(&stream, busnum, device_address)?;
+        platform::Device::create_inner(fd, Some(sysfs_path)).map(Device::wrap)
+    })
+}
+
+#[test]
+fn test_broker_protocol() {
+    let (server, mut client) = UnixStream::pair().unwrap();
+    let broker = std::thread::spawn(move || Broker::new(|_| true).handle(&server));
+    writeln!(client, "not a device").unwrap();
+    let err = fd_passing::recv_fd(client.as_fd()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Other);
+    assert_eq!(broker.join().unwrap().unwrap_err().kind(), ErrorKind::Other);
+
+    let (server, client) = UnixStream::pair().unwrap();
+    fd_passing::send_error(server.as_fd(), ErrorKind::PermissionDenied).unwrap();
+    let err = request(&client, 1, 2).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+    // A silent client times out instead of blocking the broker
+    let (server, _client) = UnixStream::pair().unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_millis(10)))
+        .unwrap();
+    let err = Broker::new(|_| true).handle(&server).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Other);
+
+    assert_eq!(parse_request("3 12\n"), Some((3, 12)));
+    assert_eq!(parse_request("3\n"), None);
+}
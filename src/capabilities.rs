@@ -0,0 +1,129 @@
+/// Transfer capabilities and limits of the OS driver for an opened device.
+///
+/// Returned by [`Device::capabilities`][crate::Device::capabilities].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Whether [`Endpoint::allocate`][crate::Endpoint::allocate] can return
+    /// buffers for zero-copy transfers.
+    pub zero_copy: bool,
+
+    /// Largest length of a single bulk or interrupt transfer, if the OS
+    /// imposes a limit.
+    ///
+    /// This does not account for [`memory_limit`](Self::memory_limit), which
+    /// is shared with other transfers.
+    pub max_transfer_size: Option<usize>,
+
+    /// Total memory available for all in-flight transfers, if limited.
+    ///
+    /// On Linux, this is the `usbfs_memory_mb` parameter of the `usbcore`
+    /// module, which is shared by all devices opened through usbfs.
+    pub memory_limit: Option<usize>,
+
+    /// Capability flags reported by Linux usbfs, or `None` on other
+    /// platforms or if the kernel does not support the query.
+    pub usbfs: Option<UsbfsCapabilities>,
+}
+
+impl Capabilities {
+    /// Limit a requested transfer size to [`max_transfer_size`](Self::max_transfer_size),
+    /// rounding to a nonzero multiple of `max_packet_size`.
+    pub fn transfer_size(&self, requested: usize, max_packet_size: usize) -> usize {
+        let requested = requested.div_ceil(max_packet_size).max(1) * max_packet_size;
+        match self.max_transfer_size {
+            Some(max) if requested > max => (max / max_packet_size).max(1) * max_packet_size,
+            _ => requested,
+        }
+    }
+}
+
+/// Capability flags reported by the Linux `USBDEVFS_GET_CAPABILITIES` ioctl.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsbfsCapabilities(pub(crate) u32);
+
+impl UsbfsCapabilities {
+    /// Get the raw capability bits.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Transfers can be terminated with a zero-length packet
+    /// (`USBDEVFS_CAP_ZERO_PACKET`).
+    pub fn zero_packet(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    /// Bulk transfers can be split into continued URBs
+    /// (`USBDEVFS_CAP_BULK_CONTINUATION`).
+    pub fn bulk_continuation(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    /// Transfers are not limited to 16 KiB (`USBDEVFS_CAP_NO_PACKET_SIZE_LIM`).
+    pub fn no_packet_size_limit(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+
+    /// The host controller supports scatter-gather, so large bulk transfers
+    /// don't need contiguous kernel memory
+    /// (`USBDEVFS_CAP_BULK_SCATTER_GATHER`).
+    pub fn bulk_scatter_gather(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+
+    /// Pending transfers can be reaped after the device disconnects
+    /// (`USBDEVFS_CAP_REAP_AFTER_DISCONNECT`).
+    pub fn reap_after_disconnect(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    /// Buffers can be allocated with `mmap` for zero-copy transfers
+    /// (`USBDEVFS_CAP_MMAP`).
+    pub fn mmap(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    /// The file descriptor can be restricted to a set of interfaces
+    /// (`USBDEVFS_CAP_DROP_PRIVILEGES`).
+    pub fn drop_privileges(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    /// Extended connection information is available
+    /// (`USBDEVFS_CAP_CONNINFO_EX`).
+    pub fn conninfo_ex(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    /// The device can be suspended and resumed through usbfs
+    /// (`USBDEVFS_CAP_SUSPEND`).
+    pub fn suspend(&self) -> bool {
+        self.0 & 0x100 != 0
+    }
+}
+
+#[test]
+fn test_transfer_size() {
+    let caps = Capabilities {
+        max_transfer_size: Some(16384),
+        ..Default::default()
+    };
+    assert_eq!(caps.transfer_size(1000, 512), 1024);
+    assert_eq!(caps.transfer_size(0, 512), 512);
+    assert_eq!(caps.transfer_size(65536, 512), 16384);
+    assert_eq!(caps.transfer_size(65536, 1000), 16000);
+    assert_eq!(Capabilities::default().transfer_size(65536, 512), 65536);
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+#[test]
+fn test_unknown_usbfs_capabilities() {
+    // The fake device's fd doesn't support USBDEVFS_GET_CAPABILITIES
+    let fake = crate::platform::fake::FakeDevice::new();
+    let caps = fake.device().capabilities();
+    assert_eq!(caps.usbfs, None);
+    assert_eq!(caps.max_transfer_size, None);
+    assert!(!caps.zero_copy);
+    assert_eq!(caps.transfer_size(65536, 512), 65536);
+}
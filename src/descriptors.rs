@@ -2,11 +2,17 @@
 //!
 //! Descriptors are blocks of data that describe the functionality of a USB device.
 
-use std::{collections::BTreeMap, fmt::Debug, iter, num::NonZeroU8, ops::Deref};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Debug, Display},
+    iter,
+    num::NonZeroU8,
+    ops::Deref,
+};
 
 use log::warn;
 
-use crate::transfer::Direction;
+use crate::{transfer::Direction, Speed};
 
 pub(crate) const DESCRIPTOR_TYPE_DEVICE: u8 = 0x01;
 pub(crate) const DESCRIPTOR_LEN_DEVICE: u8 = 18;
@@ -22,6 +28,10 @@ pub(crate) const DESCRIPTOR_LEN_ENDPOINT: u8 = 7;
 
 pub(crate) const DESCRIPTOR_TYPE_STRING: u8 = 0x03;
 
+pub(crate) const DESCRIPTOR_TYPE_INTERFACE_ASSOCIATION: u8 = 0x0B;
+
+pub(crate) const DESCRIPTOR_TYPE_SUPERSPEED_COMPANION: u8 = 0x30;
+
 /// USB defined language IDs for string descriptors.
 ///
 /// In practice, different language IDs are not used,
@@ -79,6 +89,10 @@ impl<'a> Deref for Descriptor<'a> {
 pub struct DescriptorIter<'a>(&'a [u8]);
 
 impl<'a> DescriptorIter<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        DescriptorIter(buf)
+    }
+
     /// Get the concatenated bytes of the remaining descriptors.
     pub fn as_bytes(&self) -> &'a [u8] {
         self.0
@@ -612,6 +626,10 @@ impl<'a> Debug for InterfaceDescriptor<'a> {
 pub struct EndpointDescriptor<'a>(&'a [u8]);
 
 impl<'a> EndpointDescriptor<'a> {
+    pub(crate) fn new_unchecked(d: &'a [u8]) -> Self {
+        EndpointDescriptor(d)
+    }
+
     /// The bytes of the endpoint descriptor and all trailing descriptors.
     pub fn as_bytes(&self) -> &'a [u8] {
         self.0
@@ -689,6 +707,7 @@ impl<'a> Debug for EndpointDescriptor<'a> {
 
 /// Endpoint type.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(dead_code)]
 pub enum TransferType {
     /// Control endpoint.
@@ -704,6 +723,516 @@ pub enum TransferType {
     Interrupt = 3,
 }
 
+/// A problem found when checking descriptors against the USB specification.
+///
+/// Returned by [`DeviceDescriptor::validate`],
+/// [`ConfigurationDescriptor::validate`], and [`validate_configuration`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Byte offset of the offending descriptor from the start of the
+    /// descriptor data.
+    pub offset: usize,
+
+    /// The problem found.
+    pub kind: DiagnosticKind,
+}
+
+/// Category and details of a [`Diagnostic`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// The configuration descriptor header is too short or is not a
+    /// configuration descriptor. No further checks were performed.
+    InvalidHeader,
+
+    /// The `wTotalLength` field does not match the length of the data.
+    TotalLengthMismatch {
+        /// Value of the `wTotalLength` field.
+        total_length: u16,
+
+        /// Number of bytes available.
+        actual: usize,
+    },
+
+    /// A descriptor's `bLength` is less than 2 or extends past the end of the
+    /// data. Descriptors after this one could not be checked.
+    InvalidLength {
+        /// Value of the `bLength` field.
+        length: u8,
+
+        /// Number of bytes remaining in the data.
+        remaining: usize,
+    },
+
+    /// A descriptor is shorter than the minimum length for its type and was
+    /// ignored.
+    TooShort {
+        /// Value of the `bDescriptorType` field.
+        descriptor_type: u8,
+
+        /// Value of the `bLength` field.
+        length: u8,
+
+        /// Minimum length for this descriptor type.
+        min_length: u8,
+    },
+
+    /// `bNumInterfaces` does not match the number of distinct interface
+    /// numbers found.
+    NumInterfacesMismatch {
+        /// Value of the `bNumInterfaces` field.
+        num_interfaces: u8,
+
+        /// Number of distinct interfaces found.
+        found: usize,
+    },
+
+    /// `bNumEndpoints` does not match the number of endpoint descriptors
+    /// found in the alternate setting.
+    NumEndpointsMismatch {
+        /// `bInterfaceNumber` of the alternate setting.
+        interface_number: u8,
+
+        /// `bAlternateSetting` of the alternate setting.
+        alternate_setting: u8,
+
+        /// Value of the `bNumEndpoints` field.
+        num_endpoints: u8,
+
+        /// Number of endpoint descriptors found.
+        found: usize,
+    },
+
+    /// An endpoint address appears more than once in the same alternate
+    /// setting.
+    DuplicateEndpointAddress {
+        /// `bInterfaceNumber` of the alternate setting.
+        interface_number: u8,
+
+        /// `bAlternateSetting` of the alternate setting.
+        alternate_setting: u8,
+
+        /// The repeated `bEndpointAddress`.
+        address: u8,
+    },
+
+    /// An endpoint's `wMaxPacketSize` is not allowed for its transfer type at
+    /// this speed.
+    InvalidMaxPacketSize {
+        /// `bEndpointAddress` of the endpoint.
+        address: u8,
+
+        /// Raw value of the `wMaxPacketSize` field.
+        max_packet_size_raw: u16,
+
+        /// Speed the descriptor was checked against.
+        speed: Speed,
+    },
+
+    /// An endpoint's `bInterval` is out of range for its transfer type at
+    /// this speed.
+    InvalidInterval {
+        /// `bEndpointAddress` of the endpoint.
+        address: u8,
+
+        /// Value of the `bInterval` field.
+        interval: u8,
+
+        /// Speed the descriptor was checked against.
+        speed: Speed,
+    },
+
+    /// A SuperSpeed endpoint descriptor is not immediately followed by a
+    /// SuperSpeed Endpoint Companion descriptor.
+    MissingSuperSpeedCompanion {
+        /// `bEndpointAddress` of the endpoint.
+        address: u8,
+    },
+
+    /// The device descriptor's `bMaxPacketSize0` is not allowed at this speed.
+    InvalidMaxPacketSize0 {
+        /// Value of the `bMaxPacketSize0` field.
+        max_packet_size_0: u8,
+
+        /// Speed the descriptor was checked against.
+        speed: Speed,
+    },
+
+    /// The device descriptor's `bNumConfigurations` is zero.
+    NoConfigurations,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at offset {}: ", self.offset)?;
+        match self.kind {
+            DiagnosticKind::InvalidHeader => write!(f, "invalid configuration descriptor header"),
+            DiagnosticKind::TotalLengthMismatch {
+                total_length,
+                actual,
+            } => write!(
+                f,
+                "wTotalLength is {total_length} but {actual} bytes are present"
+            ),
+            DiagnosticKind::InvalidLength { length, remaining } => write!(
+                f,
+                "bLength {length} is invalid with {remaining} bytes remaining"
+            ),
+            DiagnosticKind::TooShort {
+                descriptor_type,
+                length,
+                min_length,
+            } => write!(
+                f,
+                "descriptor of type 0x{descriptor_type:02X} has bLength {length}, minimum is {min_length}"
+            ),
+            DiagnosticKind::NumInterfacesMismatch {
+                num_interfaces,
+                found,
+            } => write!(
+                f,
+                "bNumInterfaces is {num_interfaces} but {found} interfaces were found"
+            ),
+            DiagnosticKind::NumEndpointsMismatch {
+                interface_number,
+                alternate_setting,
+                num_endpoints,
+                found,
+            } => write!(
+                f,
+                "interface {interface_number} alt setting {alternate_setting} has bNumEndpoints {num_endpoints} but {found} endpoints were found"
+            ),
+            DiagnosticKind::DuplicateEndpointAddress {
+                interface_number,
+                alternate_setting,
+                address,
+            } => write!(
+                f,
+                "endpoint 0x{address:02X} appears more than once in interface {interface_number} alt setting {alternate_setting}"
+            ),
+            DiagnosticKind::InvalidMaxPacketSize {
+                address,
+                max_packet_size_raw,
+                speed,
+            } => write!(
+                f,
+                "endpoint 0x{address:02X} wMaxPacketSize 0x{max_packet_size_raw:04X} is invalid at {speed:?} speed"
+            ),
+            DiagnosticKind::InvalidInterval {
+                address,
+                interval,
+                speed,
+            } => write!(
+                f,
+                "endpoint 0x{address:02X} bInterval {interval} is invalid at {speed:?} speed"
+            ),
+            DiagnosticKind::MissingSuperSpeedCompanion { address } => write!(
+                f,
+                "endpoint 0x{address:02X} is missing a SuperSpeed Endpoint Companion descriptor"
+            ),
+            DiagnosticKind::InvalidMaxPacketSize0 {
+                max_packet_size_0,
+                speed,
+            } => write!(
+                f,
+                "bMaxPacketSize0 {max_packet_size_0} is invalid at {speed:?} speed"
+            ),
+            DiagnosticKind::NoConfigurations => write!(f, "bNumConfigurations is 0"),
+        }
+    }
+}
+
+impl DeviceDescriptor {
+    /// Check the device descriptor for conformance with the USB specification.
+    ///
+    /// Pass the device's connection speed from
+    /// [`Device::speed`][crate::Device::speed] to enable speed-dependent
+    /// checks, or `None` to skip them.
+    pub fn validate(&self, speed: Option<Speed>) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let max_packet_size_0 = self.max_packet_size_0();
+
+        if let Some(speed) = speed {
+            let valid = match speed {
+                Speed::Low => max_packet_size_0 == 8,
+                Speed::Full => matches!(max_packet_size_0, 8 | 16 | 32 | 64),
+                Speed::High => max_packet_size_0 == 64,
+                // Encoded as an exponent: 2^9 = 512
+                Speed::Super | Speed::SuperPlus => max_packet_size_0 == 9,
+            };
+            if !valid {
+                diagnostics.push(Diagnostic {
+                    offset: 0,
+                    kind: DiagnosticKind::InvalidMaxPacketSize0 {
+                        max_packet_size_0,
+                        speed,
+                    },
+                });
+            }
+        }
+
+        if self.num_configurations() == 0 {
+            diagnostics.push(Diagnostic {
+                offset: 0,
+                kind: DiagnosticKind::NoConfigurations,
+            });
+        }
+
+        diagnostics
+    }
+}
+
+impl<'a> ConfigurationDescriptor<'a> {
+    /// Check the configuration descriptor and all trailing descriptors for
+    /// conformance with the USB specification.
+    ///
+    /// Unlike the parsing methods, which skip malformed descriptors, this
+    /// reports each problem found.
+    ///
+    /// Pass the device's connection speed from
+    /// [`Device::speed`][crate::Device::speed] to enable speed-dependent
+    /// checks of endpoint descriptors, or `None` to skip them.
+    pub fn validate(&self, speed: Option<Speed>) -> Vec<Diagnostic> {
+        validate_configuration(self.0, speed)
+    }
+}
+
+/// Check a buffer containing a configuration descriptor and all trailing
+/// descriptors for conformance with the USB specification.
+///
+/// This accepts raw bytes, such as a dump from another tool, that
+/// [`ConfigurationDescriptor::new`] might reject. Any data following
+/// `wTotalLength` is reported as a mismatch.
+///
+/// See [`ConfigurationDescriptor::validate`].
+pub fn validate_configuration(buf: &[u8], speed: Option<Speed>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |offset, kind| diagnostics.push(Diagnostic { offset, kind });
+
+    if buf.len() < DESCRIPTOR_LEN_CONFIGURATION as usize
+        || buf[0] < DESCRIPTOR_LEN_CONFIGURATION
+        || buf[1] != DESCRIPTOR_TYPE_CONFIGURATION
+    {
+        report(0, DiagnosticKind::InvalidHeader);
+        return diagnostics;
+    }
+
+    let total_length = u16::from_le_bytes([buf[2], buf[3]]);
+    if total_length as usize != buf.len() {
+        report(
+            0,
+            DiagnosticKind::TotalLengthMismatch {
+                total_length,
+                actual: buf.len(),
+            },
+        );
+    }
+    let end = buf.len().min(total_length as usize);
+
+    struct AltSetting {
+        offset: usize,
+        interface_number: u8,
+        alternate_setting: u8,
+        num_endpoints: u8,
+        endpoints: Vec<u8>,
+    }
+
+    let finish_alt_setting = |alt: AltSetting, report: &mut dyn FnMut(usize, DiagnosticKind)| {
+        if alt.num_endpoints as usize != alt.endpoints.len() {
+            report(
+                alt.offset,
+                DiagnosticKind::NumEndpointsMismatch {
+                    interface_number: alt.interface_number,
+                    alternate_setting: alt.alternate_setting,
+                    num_endpoints: alt.num_endpoints,
+                    found: alt.endpoints.len(),
+                },
+            );
+        }
+    };
+
+    let mut interface_numbers = BTreeSet::new();
+    let mut alt_setting: Option<AltSetting> = None;
+    let mut needs_companion: Option<(usize, u8)> = None;
+    let mut offset = buf[0] as usize;
+
+    while offset < end {
+        let remaining = end - offset;
+        let length = buf[offset];
+        if remaining < 2 || length < 2 || length as usize > remaining {
+            report(offset, DiagnosticKind::InvalidLength { length, remaining });
+            break;
+        }
+        let desc = &buf[offset..offset + length as usize];
+        let descriptor_type = desc[1];
+
+        if let Some((ep_offset, address)) = needs_companion.take() {
+            if descriptor_type != DESCRIPTOR_TYPE_SUPERSPEED_COMPANION {
+                report(
+                    ep_offset,
+                    DiagnosticKind::MissingSuperSpeedCompanion { address },
+                );
+            }
+        }
+
+        let min_length = match descriptor_type {
+            DESCRIPTOR_TYPE_INTERFACE => DESCRIPTOR_LEN_INTERFACE,
+            DESCRIPTOR_TYPE_ENDPOINT => DESCRIPTOR_LEN_ENDPOINT,
+            _ => 2,
+        };
+
+        if length < min_length {
+            report(
+                offset,
+                DiagnosticKind::TooShort {
+                    descriptor_type,
+                    length,
+                    min_length,
+                },
+            );
+        } else if descriptor_type == DESCRIPTOR_TYPE_INTERFACE {
+            let intf = InterfaceDescriptor(desc);
+            if let Some(prev) = alt_setting.take() {
+                finish_alt_setting(prev, &mut report);
+            }
+            interface_numbers.insert(intf.interface_number());
+            alt_setting = Some(AltSetting {
+                offset,
+                interface_number: intf.interface_number(),
+                alternate_setting: intf.alternate_setting(),
+                num_endpoints: intf.num_endpoints(),
+                endpoints: Vec::new(),
+            });
+        } else if descriptor_type == DESCRIPTOR_TYPE_ENDPOINT {
+            let ep = EndpointDescriptor(desc);
+            let address = ep.address();
+
+            if let Some(alt) = alt_setting.as_mut() {
+                if alt.endpoints.contains(&address) {
+                    report(
+                        offset,
+                        DiagnosticKind::DuplicateEndpointAddress {
+                            interface_number: alt.interface_number,
+                            alternate_setting: alt.alternate_setting,
+                            address,
+                        },
+                    );
+                }
+                alt.endpoints.push(address);
+            }
+
+            if let Some(speed) = speed {
+                if !valid_max_packet_size(&ep, speed) {
+                    report(
+                        offset,
+                        DiagnosticKind::InvalidMaxPacketSize {
+                            address,
+                            max_packet_size_raw: ep.max_packet_size_raw(),
+                            speed,
+                        },
+                    );
+                }
+
+                if !valid_interval(&ep, speed) {
+                    report(
+                        offset,
+                        DiagnosticKind::InvalidInterval {
+                            address,
+                            interval: ep.interval(),
+                            speed,
+                        },
+                    );
+                }
+
+                if matches!(speed, Speed::Super | Speed::SuperPlus) {
+                    needs_companion = Some((offset, address));
+                }
+            }
+        }
+
+        offset += length as usize;
+    }
+
+    if let Some((ep_offset, address)) = needs_companion {
+        report(
+            ep_offset,
+            DiagnosticKind::MissingSuperSpeedCompanion { address },
+        );
+    }
+
+    if let Some(alt) = alt_setting {
+        finish_alt_setting(alt, &mut report);
+    }
+
+    let num_interfaces = buf[4];
+    if num_interfaces as usize != interface_numbers.len() {
+        report(
+            0,
+            DiagnosticKind::NumInterfacesMismatch {
+                num_interfaces,
+                found: interface_numbers.len(),
+            },
+        );
+    }
+
+    diagnostics.sort_by_key(|d| d.offset);
+    diagnostics
+}
+
+fn valid_max_packet_size(ep: &EndpointDescriptor, speed: Speed) -> bool {
+    let size = ep.max_packet_size();
+    let additional_transactions = (ep.max_packet_size_raw() >> 11) & 0b11;
+    let transfer_type = ep.transfer_type();
+
+    if ep.max_packet_size_raw() >> 13 != 0 {
+        return false;
+    }
+
+    if additional_transactions != 0 {
+        // Only high-speed periodic endpoints may have additional transactions
+        // per microframe, and only if the packet size requires them.
+        let periodic = matches!(
+            transfer_type,
+            TransferType::Interrupt | TransferType::Isochronous
+        );
+        let min_size = match additional_transactions {
+            1 => 513,
+            2 => 683,
+            _ => return false,
+        };
+        if speed != Speed::High || !periodic || size < min_size {
+            return false;
+        }
+    }
+
+    match (transfer_type, speed) {
+        (TransferType::Control, Speed::Low) => size == 8,
+        (TransferType::Control, Speed::Full) => matches!(size, 8 | 16 | 32 | 64),
+        (TransferType::Control, Speed::High) => size == 64,
+        (TransferType::Control, Speed::Super | Speed::SuperPlus) => size == 512,
+        (TransferType::Bulk, Speed::Low) => false,
+        (TransferType::Bulk, Speed::Full) => matches!(size, 8 | 16 | 32 | 64),
+        (TransferType::Bulk, Speed::High) => size == 512,
+        (TransferType::Bulk, Speed::Super | Speed::SuperPlus) => size == 1024,
+        (TransferType::Interrupt, Speed::Low) => size <= 8,
+        (TransferType::Interrupt, Speed::Full) => size <= 64,
+        (TransferType::Interrupt, _) => size <= 1024,
+        (TransferType::Isochronous, Speed::Low) => false,
+        (TransferType::Isochronous, Speed::Full) => size <= 1023,
+        (TransferType::Isochronous, _) => size <= 1024,
+    }
+}
+
+fn valid_interval(ep: &EndpointDescriptor, speed: Speed) -> bool {
+    let interval = ep.interval();
+    match (ep.transfer_type(), speed) {
+        (TransferType::Interrupt, Speed::Low) => interval >= 10,
+        (TransferType::Interrupt, Speed::Full) => interval >= 1,
+        (TransferType::Interrupt | TransferType::Isochronous, _) => (1..=16).contains(&interval),
+        (TransferType::Control | TransferType::Bulk, _) => true,
+    }
+}
+
 /// Split a chain of concatenated configuration descriptors by `wTotalLength`
 #[allow(unused)]
 pub(crate) fn parse_concatenated_config_descriptors(
@@ -822,6 +1351,66 @@ fn test_malformed() {
     assert!(c.interfaces().next().is_none());
 }
 
+#[test]
+#[rustfmt::skip]
+fn test_validate() {
+    let valid = [
+        0x09, 0x02, 0x19, 0x00, 0x01, 0x01, 0x00, 0xe0, 0x00,
+        0x09, 0x04, 0x00, 0x00, 0x01, 0x09, 0x00, 0x00, 0x00,
+        0x07, 0x05, 0x81, 0x03, 0x04, 0x00, 0x0c
+    ];
+    assert_eq!(validate_configuration(&valid, Some(Speed::High)), vec![]);
+    assert_eq!(ConfigurationDescriptor(&valid).validate(None), vec![]);
+
+    // Trailing data, bNumInterfaces = 2, interval 12 too large for high speed
+    let c = [
+        0x09, 0x02, 0x19, 0x00, 0x02, 0x01, 0x00, 0xe0, 0x00,
+        0x09, 0x04, 0x00, 0x00, 0x01, 0x09, 0x00, 0x00, 0x00,
+        0x07, 0x05, 0x81, 0x03, 0x04, 0x00, 0x20,
+        0x00
+    ];
+    assert_eq!(validate_configuration(&c, Some(Speed::High)), vec![
+        Diagnostic { offset: 0, kind: DiagnosticKind::TotalLengthMismatch { total_length: 25, actual: 26 } },
+        Diagnostic { offset: 0, kind: DiagnosticKind::NumInterfacesMismatch { num_interfaces: 2, found: 1 } },
+        Diagnostic { offset: 18, kind: DiagnosticKind::InvalidInterval { address: 0x81, interval: 0x20, speed: Speed::High } },
+    ]);
+
+    // Duplicate endpoint, bulk packet size, missing companion
+    let c = [
+        0x09, 0x02, 0x20, 0x00, 0x01, 0x01, 0x00, 0xe0, 0x00,
+        0x09, 0x04, 0x00, 0x00, 0x02, 0xff, 0x00, 0x00, 0x00,
+        0x07, 0x05, 0x01, 0x02, 0x00, 0x04, 0x00,
+        0x07, 0x05, 0x01, 0x02, 0x00, 0x02, 0x00,
+    ];
+    assert_eq!(validate_configuration(&c, Some(Speed::Super)), vec![
+        Diagnostic { offset: 18, kind: DiagnosticKind::MissingSuperSpeedCompanion { address: 0x01 } },
+        Diagnostic { offset: 25, kind: DiagnosticKind::DuplicateEndpointAddress { interface_number: 0, alternate_setting: 0, address: 0x01 } },
+        Diagnostic { offset: 25, kind: DiagnosticKind::InvalidMaxPacketSize { address: 0x01, max_packet_size_raw: 512, speed: Speed::Super } },
+        Diagnostic { offset: 25, kind: DiagnosticKind::MissingSuperSpeedCompanion { address: 0x01 } },
+    ]);
+
+    // Truncated endpoint descriptor
+    let c = [
+        0x09, 0x02, 0x15, 0x00, 0x01, 0x01, 0x00, 0xe0, 0x00,
+        0x09, 0x04, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x00,
+        0x07, 0x05, 0x81,
+    ];
+    assert_eq!(validate_configuration(&c, None), vec![
+        Diagnostic { offset: 9, kind: DiagnosticKind::NumEndpointsMismatch { interface_number: 0, alternate_setting: 0, num_endpoints: 1, found: 0 } },
+        Diagnostic { offset: 18, kind: DiagnosticKind::InvalidLength { length: 7, remaining: 3 } },
+    ]);
+
+    assert_eq!(validate_configuration(&[9, 4, 0, 0, 0, 0, 0, 0, 0], None), vec![
+        Diagnostic { offset: 0, kind: DiagnosticKind::InvalidHeader },
+    ]);
+
+    let dev = DeviceDescriptor::from_fields(0x0200, 0, 0, 0, 8, 0x1234, 0x5678, 0x0100, 0, 0, 0, 1);
+    assert_eq!(dev.validate(Some(Speed::Full)), vec![]);
+    assert_eq!(dev.validate(Some(Speed::High)), vec![
+        Diagnostic { offset: 0, kind: DiagnosticKind::InvalidMaxPacketSize0 { max_packet_size_0: 8, speed: Speed::High } },
+    ]);
+}
+
 #[test]
 #[rustfmt::skip]
 fn test_linux_root_hub() {
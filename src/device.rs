@@ -3,24 +3,52 @@ use crate::{
         decode_string_descriptor, validate_string_descriptor, ConfigurationDescriptor,
         DeviceDescriptor, InterfaceDescriptor, DESCRIPTOR_TYPE_STRING,
     },
-    io::{EndpointRead, EndpointWrite},
+    io::{EndpointRead, EndpointSink, EndpointStream, EndpointWrite},
     platform,
     transfer::{
-        Buffer, BulkOrInterrupt, Completion, ControlIn, ControlOut, Direction, EndpointDirection,
-        EndpointType, In, Out, TransferError,
+        standard_request, Buffer, BufferPool, BulkOrInterrupt, Completion, ControlIn, ControlOut,
+        Direction, EndpointDirection, EndpointStats, EndpointStatus, EndpointType, In,
+        InterfaceFeature, InterfaceStatus, Out, StatusPoll, TransferError, TransferId,
     },
-    ActiveConfigurationError, DeviceInfo, Error, ErrorKind, GetDescriptorError, MaybeFuture, Speed,
+    ActiveConfigurationError, Capabilities, DeviceInfo, Error, ErrorKind, GetDescriptorError,
+    MaybeFuture, Speed,
 };
 use log::{error, warn};
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "android",
+    target_arch = "wasm32"
+))]
+use crate::transfer::{DeviceFeature, DeviceStatus, PtmStatus, SystemExitLatency};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::transfer::SETUP_PACKET_SIZE;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::descriptors::{language_id::US_ENGLISH, DESCRIPTOR_TYPE_INTERFACE_ASSOCIATION};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::maybe_future::blocking::{Blocking, BlockingTask};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::maybe_future::{Mode, Sequence};
 use std::{
+    collections::VecDeque,
     fmt::Debug,
     future::{poll_fn, Future},
+    io::IoSlice,
     marker::PhantomData,
     num::NonZeroU8,
     sync::Arc,
-    task::{Context, Poll},
+    task::{ready, Context, Poll},
     time::Duration,
 };
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    pin::Pin,
+    sync::Mutex,
+};
 
 /// An opened USB device.
 ///
@@ -44,11 +72,18 @@ use std::{
 #[derive(Clone)]
 pub struct Device {
     backend: Arc<crate::platform::Device>,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    strings: Arc<Mutex<StringCache>>,
 }
 
 impl Device {
     pub(crate) fn wrap(backend: Arc<platform::Device>) -> Device {
-        Device { backend }
+        Device {
+            backend,
+            #[cfg(not(target_arch = "wasm32"))]
+            strings: Default::default(),
+        }
     }
 
     pub(crate) fn open(d: &DeviceInfo) -> impl MaybeFuture<Output = Result<Device, Error>> {
@@ -69,6 +104,128 @@ impl Device {
         platform::Device::from_fd(fd).map(|d| d.map(Device::wrap))
     }
 
+    /// Receive a device handle sent by another process with
+    /// [`send_fd`](Self::send_fd) over a Unix domain socket.
+    ///
+    /// This waits for a message on `socket` carrying a usbfs file descriptor
+    /// (`SCM_RIGHTS`) and wraps it as with [`from_fd`](Self::from_fd). If the
+    /// sender reports an error in place of the fd, as a broker does, an error
+    /// of the same kind is returned.
+    ///
+    /// *Supported on Linux and Android only.*
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn from_socket(
+        socket: impl std::os::fd::AsFd,
+    ) -> impl MaybeFuture<Output = Result<Device, Error>> {
+        platform::Device::from_socket(socket.as_fd()).map(|d| d.map(Device::wrap))
+    }
+
+    /// Send this device's usbfs file descriptor to another process over a
+    /// Unix domain socket (`SCM_RIGHTS`).
+    ///
+    /// The receiving process opens it with [`from_socket`](Self::from_socket).
+    /// This allows a privileged broker to open a device, restrict it with
+    /// [`drop_privileges`](Self::drop_privileges), and hand it to a sandboxed
+    /// process that cannot open `/dev/bus/usb` itself:
+    ///
+    /// ```no_run
+    /// use std::os::unix::net::UnixStream;
+    /// use nusb::{Device, MaybeFuture};
+    ///
+    /// let (broker, worker) = UnixStream::pair().unwrap();
+    ///
+    /// // In the broker
+    /// let device_info = nusb::list_devices().wait().unwrap().next().unwrap();
+    /// let device = device_info.open().wait().unwrap();
+    /// device.drop_privileges(1 << 0).unwrap();
+    /// device.send_fd(&broker).unwrap();
+    /// drop(device);
+    ///
+    /// // In the worker
+    /// let device = Device::from_socket(&worker).wait().unwrap();
+    /// let interface = device.claim_interface(0).wait().unwrap();
+    /// ```
+    ///
+    /// Both processes share the same open file, so restrictions applied by
+    /// `drop_privileges` before or after sending affect both.
+    ///
+    /// *Supported on Linux and Android only.*
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn send_fd(&self, socket: impl std::os::fd::AsFd) -> Result<(), Error> {
+        self.backend.send_fd(socket.as_fd())
+    }
+
+    /// Restrict this device handle to the interfaces in `interface_mask`.
+    ///
+    /// Bit `n` of `interface_mask` allows interface number `n` to be claimed.
+    /// After this call, claiming any other interface fails, and requests that
+    /// affect the whole device, such as detaching kernel drivers and
+    /// [`set_configuration`](Self::set_configuration), are no longer
+    /// permitted. Calling this again can only narrow the mask; privileges
+    /// cannot be regained on this file descriptor.
+    ///
+    /// This uses `USBDEVFS_DROP_PRIVILEGES`, available since Linux 4.18; see
+    /// [`UsbfsCapabilities::drop_privileges`][crate::UsbfsCapabilities::drop_privileges].
+    ///
+    /// *Supported on Linux and Android only.*
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn drop_privileges(&self, interface_mask: u32) -> Result<(), Error> {
+        self.backend.drop_privileges(interface_mask)
+    }
+
+    /// Allow the kernel to autosuspend the device while this handle is open.
+    ///
+    /// usbfs forbids suspend while a device is open, so an idle device stays
+    /// powered until this is called. The device is only suspended if runtime
+    /// power management is enabled for it, i.e. its sysfs `power/control`
+    /// attribute is `auto` (see [`DeviceInfo::power_control`][crate::DeviceInfo::power_control]),
+    /// and after the `power/autosuspend_delay_ms` delay has elapsed.
+    ///
+    /// Pending and newly submitted transfers do not resume a suspended
+    /// device; call [`forbid_suspend`](Self::forbid_suspend) to resume it and
+    /// keep it awake before performing IO.
+    ///
+    /// This uses `USBDEVFS_ALLOW_SUSPEND`, available since Linux 3.19; see
+    /// [`UsbfsCapabilities::suspend`][crate::UsbfsCapabilities::suspend].
+    ///
+    /// *Supported on Linux and Android only.*
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn allow_suspend(&self) -> Result<(), Error> {
+        self.backend.allow_suspend()
+    }
+
+    /// Resume the device if it is suspended, and prevent it from being
+    /// suspended while this handle is open.
+    ///
+    /// This is the default state after opening a device, and undoes
+    /// [`allow_suspend`](Self::allow_suspend).
+    ///
+    /// This uses `USBDEVFS_FORBID_SUSPEND`.
+    ///
+    /// *Supported on Linux and Android only.*
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn forbid_suspend(&self) -> Result<(), Error> {
+        self.backend.forbid_suspend()
+    }
+
+    /// Wait for the device to be resumed after it was suspended with
+    /// [`allow_suspend`](Self::allow_suspend).
+    ///
+    /// This completes when the device is resumed, for instance by remote
+    /// wakeup or another process performing IO, and then forbids suspend as
+    /// if [`forbid_suspend`](Self::forbid_suspend) had been called. It
+    /// completes immediately if the device has already resumed since the last
+    /// call to `allow_suspend`.
+    ///
+    /// This uses `USBDEVFS_WAIT_FOR_RESUME`, which blocks a thread until the
+    /// device resumes.
+    ///
+    /// *Supported on Linux and Android only.*
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn wait_for_resume(&self) -> impl MaybeFuture<Output = Result<(), Error>> {
+        self.backend.clone().wait_for_resume()
+    }
+
     /// Wrap a [`web_sys::UsbDevice`] object obtained from JS.
     ///
     /// *Supported on wasm only.*
@@ -141,6 +298,28 @@ impl Device {
         self.backend.speed()
     }
 
+    /// Get the transfer capabilities and limits of the OS driver for this
+    /// device.
+    ///
+    /// [`EndpointRead`] and [`EndpointWrite`] use this to limit their
+    /// transfer size.
+    ///
+    /// ### Platform-specific details
+    /// * On Linux, usbfs is queried with `USBDEVFS_GET_CAPABILITIES` and the
+    ///   `usbfs_memory_mb` module parameter is read when the device is opened.
+    /// * On other platforms, no limits are currently reported.
+    pub fn capabilities(&self) -> Capabilities {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            self.backend.capabilities()
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            Capabilities::default()
+        }
+    }
+
     /// Get information about the active configuration.
     ///
     /// This returns cached data and does not perform IO. However, it can fail if the
@@ -277,6 +456,141 @@ impl Device {
         })
     }
 
+    /// Get the list of supported languages for string descriptors, using a
+    /// cached copy if available.
+    ///
+    /// The list is requested from the device on the first successful call
+    /// and shared by all clones of this `Device`.
+    ///
+    /// ### Platform-specific details
+    ///
+    /// See notes on [`get_descriptor`][`Self::get_descriptor`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn cached_string_languages(
+        &self,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<Vec<u16>, GetDescriptorError>> {
+        let device = self.clone();
+        Sequence::new(move |mode| async move {
+            let source = DeviceStrings::new(&device, mode, timeout);
+            StringCache::languages(&device.strings, &source).await
+        })
+    }
+
+    /// Get a string descriptor, using a cached copy if available.
+    ///
+    /// If the device does not list `language_id` as supported, or fails to
+    /// return the string in that language, the string is requested in
+    /// [`US_ENGLISH`][`crate::descriptors::language_id::US_ENGLISH`] instead.
+    ///
+    /// The first request in a language also fetches every string referenced
+    /// by the device, configuration, interface association and interface
+    /// descriptors, so that later lookups of any of these strings do not
+    /// perform IO. The cache is shared by all clones of this `Device`.
+    /// Failed requests are not cached.
+    ///
+    /// ### Platform-specific details
+    ///
+    /// See notes on [`get_descriptor`][`Self::get_descriptor`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn cached_string(
+        &self,
+        desc_index: NonZeroU8,
+        language_id: u16,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<String, GetDescriptorError>> {
+        let device = self.clone();
+        Sequence::new(move |mode| async move {
+            let source = DeviceStrings::new(&device, mode, timeout);
+            StringCache::string(&device.strings, &source, desc_index, language_id).await
+        })
+    }
+
+    /// Get the string describing an interface in the active configuration,
+    /// using a cached copy if available.
+    ///
+    /// This uses the `iInterface` field of the interface's first alternate
+    /// setting, and returns `Ok(None)` if the interface doesn't exist or has
+    /// no string. See [`cached_string`][`Self::cached_string`] for caching
+    /// and language fallback.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn cached_interface_string(
+        &self,
+        interface_number: u8,
+        language_id: u16,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<Option<String>, GetDescriptorError>> {
+        let device = self.clone();
+        Sequence::new(move |mode| async move {
+            let Some(index) = device.active_configuration().ok().and_then(|c| {
+                c.interfaces()
+                    .find(|i| i.interface_number() == interface_number)
+                    .and_then(|i| i.first_alt_setting().string_index())
+            }) else {
+                return Ok(None);
+            };
+            let source = DeviceStrings::new(&device, mode, timeout);
+            StringCache::string(&device.strings, &source, index, language_id)
+                .await
+                .map(Some)
+        })
+    }
+
+    /// String indexes referenced by the device and configuration descriptors.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn referenced_string_indexes(&self) -> BTreeSet<NonZeroU8> {
+        let device_descriptor = self.device_descriptor();
+        let mut indexes: BTreeSet<NonZeroU8> = [
+            device_descriptor.manufacturer_string_index(),
+            device_descriptor.product_string_index(),
+            device_descriptor.serial_number_string_index(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        for config in self.configurations() {
+            indexes.extend(config.string_index());
+            indexes.extend(
+                config
+                    .interface_alt_settings()
+                    .filter_map(|i| i.string_index()),
+            );
+            indexes.extend(
+                config
+                    .descriptors()
+                    .filter(|d| {
+                        d.descriptor_type() == DESCRIPTOR_TYPE_INTERFACE_ASSOCIATION && d.len() >= 8
+                    })
+                    .filter_map(|d| NonZeroU8::new(d[7])),
+            );
+        }
+
+        indexes
+    }
+
+    /// Create a [`BufferPool`] of `count` buffers of `buffer_size` bytes for
+    /// transfers on this device's endpoints.
+    ///
+    /// The buffers are allocated up front for zero-copy transfers where
+    /// possible, as with [`Endpoint::allocate`]. On Linux, zero-copy memory is
+    /// limited by the `usbfs_memory_mb` module parameter, and buffers that
+    /// cannot be mapped fall back to the system allocator; see
+    /// [`BufferPoolStats::zero_copy`][crate::transfer::BufferPoolStats::zero_copy].
+    pub fn buffer_pool(&self, buffer_size: usize, count: usize) -> BufferPool {
+        let zero_copy = self.capabilities().zero_copy;
+        BufferPool::new(buffer_size, count, |len| {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            if zero_copy {
+                if let Ok(b) = self.backend.allocate(len) {
+                    return b;
+                }
+            }
+
+            Buffer::new(len)
+        })
+    }
+
     /// Reset the device, forcing it to re-enumerate.
     ///
     /// This `Device` will no longer be usable, and you should drop it and call
@@ -371,6 +685,191 @@ impl Device {
     ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
         self.backend.clone().control_out(data, timeout)
     }
+
+    /// Create a [`ControlQueue`] for submitting control transfers on the
+    /// default control endpoint with caller-provided [`Buffer`]s.
+    ///
+    /// Only supported on Linux and Android.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn control_queue(&self) -> ControlQueue {
+        ControlQueue::new(self.backend.clone().control_queue())
+    }
+
+    /// Send a command with a control OUT transfer, then poll a status with
+    /// control IN transfers until `ready` returns `true` for the status data.
+    ///
+    /// The status is requested every [`StatusPoll`] interval, and the
+    /// status data accepted by `ready` is returned. If the command and
+    /// status sequence does not complete within the timeout of `poll`,
+    /// [`TransferError::TimedOut`] is returned. Stalls are retried as
+    /// configured by [`StatusPoll::stall_retries`], and any other error is
+    /// returned immediately.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use nusb::transfer::{ControlIn, ControlOut, ControlType, Recipient, StatusPoll};
+    /// # use nusb::MaybeFuture;
+    /// # fn main() -> Result<(), std::io::Error> {
+    /// # let di = nusb::list_devices().wait().unwrap().next().unwrap();
+    /// # let device = di.open().wait().unwrap();
+    ///
+    /// let status = device.control_command(
+    ///     ControlOut {
+    ///         control_type: ControlType::Vendor,
+    ///         recipient: Recipient::Device,
+    ///         request: 0x40,
+    ///         value: 0x0,
+    ///         index: 0x0,
+    ///         data: &[0x01],
+    ///     },
+    ///     ControlIn {
+    ///         control_type: ControlType::Vendor,
+    ///         recipient: Recipient::Device,
+    ///         request: 0x41,
+    ///         value: 0x0,
+    ///         index: 0x0,
+    ///         length: 1,
+    ///     },
+    ///     |status| status[0] == 0,
+    ///     StatusPoll::new(Duration::from_millis(10), Duration::from_secs(1)).stall_retries(3),
+    /// ).wait()?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// ### Platform-specific details
+    ///
+    /// * Not supported on Windows. You must [claim an interface][`Device::claim_interface`]
+    ///   and use the interface handle to submit transfers.
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "android"))]
+    pub fn control_command(
+        &self,
+        command: ControlOut,
+        status: ControlIn,
+        ready: impl FnMut(&[u8]) -> bool + Send + 'static,
+        poll: StatusPoll,
+    ) -> impl MaybeFuture<Output = Result<Vec<u8>, TransferError>> {
+        control_command_sequence(self.clone(), command, status, ready, poll)
+    }
+
+    /// Send a `GET_STATUS` request for the device status.
+    ///
+    /// ### Platform-specific details
+    ///
+    /// * Not supported on Windows. Use [`Interface::control_in`] with
+    ///   `Recipient::Device` instead.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "android",
+        target_arch = "wasm32"
+    ))]
+    pub fn get_status(
+        &self,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<DeviceStatus, TransferError>> {
+        self.control_in(standard_request::get_device_status(), timeout)
+            .map(standard_request::device_status)
+    }
+
+    /// Send a `GET_STATUS` request for the device's Precision Time
+    /// Measurement status.
+    ///
+    /// ### Platform-specific details
+    ///
+    /// * Not supported on Windows.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "android",
+        target_arch = "wasm32"
+    ))]
+    pub fn get_ptm_status(
+        &self,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<PtmStatus, TransferError>> {
+        self.control_in(standard_request::get_ptm_status(), timeout)
+            .map(standard_request::ptm_status)
+    }
+
+    /// Send a `SET_FEATURE` request to enable a device feature.
+    ///
+    /// ### Platform-specific details
+    ///
+    /// * Not supported on Windows.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "android",
+        target_arch = "wasm32"
+    ))]
+    pub fn set_feature(
+        &self,
+        feature: DeviceFeature,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
+        self.control_out(standard_request::device_feature(feature, true), timeout)
+    }
+
+    /// Send a `CLEAR_FEATURE` request to disable a device feature.
+    ///
+    /// ### Platform-specific details
+    ///
+    /// * Not supported on Windows.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "android",
+        target_arch = "wasm32"
+    ))]
+    pub fn clear_feature(
+        &self,
+        feature: DeviceFeature,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
+        self.control_out(standard_request::device_feature(feature, false), timeout)
+    }
+
+    /// Send a `SET_SEL` request to inform a SuperSpeed device of the system
+    /// exit latencies.
+    ///
+    /// ### Platform-specific details
+    ///
+    /// * Not supported on Windows.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "android",
+        target_arch = "wasm32"
+    ))]
+    pub fn set_sel(
+        &self,
+        latency: SystemExitLatency,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
+        let data = latency.to_bytes();
+        self.control_out(standard_request::set_sel(&data), timeout)
+    }
+
+    /// Send a `SET_ISOCH_DELAY` request to inform a SuperSpeed device of the
+    /// delay from the host transmitting a packet to the device receiving it,
+    /// in nanoseconds.
+    ///
+    /// ### Platform-specific details
+    ///
+    /// * Not supported on Windows.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "android",
+        target_arch = "wasm32"
+    ))]
+    pub fn set_isoch_delay(
+        &self,
+        delay_ns: u16,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
+        self.control_out(standard_request::set_isoch_delay(delay_ns), timeout)
+    }
 }
 
 impl Debug for Device {
@@ -379,6 +878,152 @@ impl Debug for Device {
     }
 }
 
+/// String descriptors cached by [`Device::cached_string`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct StringCache {
+    languages: Option<Vec<u16>>,
+
+    /// Languages in which the referenced strings have been fetched.
+    loaded_languages: BTreeSet<u16>,
+
+    strings: BTreeMap<(u16, NonZeroU8), String>,
+}
+
+/// Where [`StringCache`] fetches strings that are not cached.
+#[cfg(not(target_arch = "wasm32"))]
+trait StringSource {
+    async fn languages(&self) -> Result<Vec<u16>, GetDescriptorError>;
+
+    async fn string(
+        &self,
+        desc_index: NonZeroU8,
+        language_id: u16,
+    ) -> Result<String, GetDescriptorError>;
+
+    /// Strings to fetch along with the first string in a language.
+    fn referenced_string_indexes(&self) -> BTreeSet<NonZeroU8>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct DeviceStrings<'a> {
+    device: &'a Device,
+    mode: Mode,
+    timeout: Duration,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a> DeviceStrings<'a> {
+    fn new(device: &'a Device, mode: Mode, timeout: Duration) -> Self {
+        DeviceStrings {
+            device,
+            mode,
+            timeout,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StringSource for DeviceStrings<'_> {
+    async fn languages(&self) -> Result<Vec<u16>, GetDescriptorError> {
+        let languages = self
+            .device
+            .get_string_descriptor_supported_languages(self.timeout);
+        Ok(self.mode.run(languages).await?.collect())
+    }
+
+    async fn string(
+        &self,
+        desc_index: NonZeroU8,
+        language_id: u16,
+    ) -> Result<String, GetDescriptorError> {
+        let string = self
+            .device
+            .get_string_descriptor(desc_index, language_id, self.timeout);
+        self.mode.run(string).await
+    }
+
+    fn referenced_string_indexes(&self) -> BTreeSet<NonZeroU8> {
+        self.device.referenced_string_indexes()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StringCache {
+    async fn languages(
+        cache: &Mutex<Self>,
+        source: &impl StringSource,
+    ) -> Result<Vec<u16>, GetDescriptorError> {
+        if let Some(languages) = &cache.lock().unwrap().languages {
+            return Ok(languages.clone());
+        }
+
+        let languages = source.languages().await?;
+        cache.lock().unwrap().languages = Some(languages.clone());
+        Ok(languages)
+    }
+
+    async fn string(
+        cache: &Mutex<Self>,
+        source: &impl StringSource,
+        desc_index: NonZeroU8,
+        language_id: u16,
+    ) -> Result<String, GetDescriptorError> {
+        // Devices that fail to list languages usually still answer in US English
+        let languages = Self::languages(cache, source).await.unwrap_or_default();
+        let language_id = if languages.contains(&language_id) {
+            language_id
+        } else {
+            US_ENGLISH
+        };
+
+        match Self::string_in_language(cache, source, desc_index, language_id).await {
+            Err(_) if language_id != US_ENGLISH => {
+                Self::string_in_language(cache, source, desc_index, US_ENGLISH).await
+            }
+            r => r,
+        }
+    }
+
+    async fn string_in_language(
+        cache: &Mutex<Self>,
+        source: &impl StringSource,
+        desc_index: NonZeroU8,
+        language_id: u16,
+    ) -> Result<String, GetDescriptorError> {
+        let preload = {
+            let mut cache = cache.lock().unwrap();
+            if let Some(s) = cache.strings.get(&(language_id, desc_index)) {
+                return Ok(s.clone());
+            }
+            cache.loaded_languages.insert(language_id)
+        };
+
+        if preload {
+            for index in source.referenced_string_indexes() {
+                if index == desc_index {
+                    continue;
+                }
+                if let Ok(s) = source.string(index, language_id).await {
+                    cache
+                        .lock()
+                        .unwrap()
+                        .strings
+                        .insert((language_id, index), s);
+                }
+            }
+        }
+
+        let s = source.string(desc_index, language_id).await?;
+        cache
+            .lock()
+            .unwrap()
+            .strings
+            .insert((language_id, desc_index), s.clone());
+        Ok(s)
+    }
+}
+
 /// An opened interface of a USB device.
 ///
 /// Obtain an `Interface` with the [`Device::claim_interface`] method.
@@ -484,10 +1129,122 @@ impl Interface {
     ///   limitation.
     pub fn control_out(
         &self,
-        data: ControlOut,
+        data: ControlOut,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
+        self.backend.clone().control_out(data, timeout)
+    }
+
+    /// Create a [`ControlQueue`] for submitting control transfers on the
+    /// default control endpoint with caller-provided [`Buffer`]s.
+    ///
+    /// Only supported on Linux and Android.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn control_queue(&self) -> ControlQueue {
+        ControlQueue::new(self.backend.control_queue())
+    }
+
+    /// Send a command with a control OUT transfer, then poll a status with
+    /// control IN transfers until `ready` returns `true` for the status data.
+    ///
+    /// The status is requested every [`StatusPoll`] interval, and the
+    /// status data accepted by `ready` is returned. If the command and
+    /// status sequence does not complete within the timeout of `poll`,
+    /// [`TransferError::TimedOut`] is returned. Stalls are retried as
+    /// configured by [`StatusPoll::stall_retries`], and any other error is
+    /// returned immediately.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use nusb::transfer::{ControlIn, ControlOut, ControlType, Recipient, StatusPoll};
+    /// # use nusb::MaybeFuture;
+    /// # fn main() -> Result<(), std::io::Error> {
+    /// # let di = nusb::list_devices().wait().unwrap().next().unwrap();
+    /// # let device = di.open().wait().unwrap();
+    /// # let interface = device.claim_interface(0).wait().unwrap();
+    ///
+    /// let status = interface.control_command(
+    ///     ControlOut {
+    ///         control_type: ControlType::Vendor,
+    ///         recipient: Recipient::Interface,
+    ///         request: 0x40,
+    ///         value: 0x0,
+    ///         index: 0x0,
+    ///         data: &[0x01],
+    ///     },
+    ///     ControlIn {
+    ///         control_type: ControlType::Vendor,
+    ///         recipient: Recipient::Interface,
+    ///         request: 0x41,
+    ///         value: 0x0,
+    ///         index: 0x0,
+    ///         length: 1,
+    ///     },
+    ///     |status| status[0] == 0,
+    ///     StatusPoll::new(Duration::from_millis(10), Duration::from_secs(1)).stall_retries(3),
+    /// ).wait()?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn control_command(
+        &self,
+        command: ControlOut,
+        status: ControlIn,
+        ready: impl FnMut(&[u8]) -> bool + Send + 'static,
+        poll: StatusPoll,
+    ) -> impl MaybeFuture<Output = Result<Vec<u8>, TransferError>> {
+        control_command_sequence(self.clone(), command, status, ready, poll)
+    }
+
+    /// Send a `GET_STATUS` request for the interface status.
+    pub fn get_status(
+        &self,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<InterfaceStatus, TransferError>> {
+        self.control_in(
+            standard_request::get_interface_status(self.interface_number()),
+            timeout,
+        )
+        .map(standard_request::interface_status)
+    }
+
+    /// Send a `SET_FEATURE` request to enable an interface feature.
+    pub fn set_feature(
+        &self,
+        feature: InterfaceFeature,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
+        self.control_out(
+            standard_request::interface_feature(self.interface_number(), feature, true),
+            timeout,
+        )
+    }
+
+    /// Send a `CLEAR_FEATURE` request to disable an interface feature.
+    pub fn clear_feature(
+        &self,
+        feature: InterfaceFeature,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
+        self.control_out(
+            standard_request::interface_feature(self.interface_number(), feature, false),
+            timeout,
+        )
+    }
+
+    /// Send a `SYNCH_FRAME` request to get the frame number in which an
+    /// isochronous endpoint's synchronization pattern starts.
+    ///
+    /// ### Platform-specific details
+    /// * On Windows, this is subject to the same WinUSB restrictions as
+    ///   [`control_in`][Self::control_in] with `Recipient::Endpoint`.
+    pub fn synch_frame(
+        &self,
+        endpoint: u8,
         timeout: Duration,
-    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
-        self.backend.clone().control_out(data, timeout)
+    ) -> impl MaybeFuture<Output = Result<u16, TransferError>> {
+        self.control_in(standard_request::synch_frame(endpoint), timeout)
+            .map(standard_request::frame_number)
     }
 
     /// Get the interface number.
@@ -550,12 +1307,128 @@ impl Interface {
         let backend = self.backend.endpoint(ep_desc)?;
         Ok(Endpoint {
             backend,
+            submissions: Submissions::default(),
+            stats: EndpointStats::default(),
             ep_type: PhantomData,
             ep_dir: PhantomData,
         })
     }
 }
 
+/// Control transfers on the default control endpoint, shared by [`Device`]
+/// and [`Interface`] to implement `control_command`.
+#[cfg(not(target_arch = "wasm32"))]
+trait ControlPipe: Send + Sync + 'static {
+    fn control_out(
+        &self,
+        data: ControlOut,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>>;
+
+    fn control_in(
+        &self,
+        data: ControlIn,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<Vec<u8>, TransferError>>;
+
+    /// Create a future that completes after `timeout`, using the device's
+    /// timer where available.
+    fn delay(&self, timeout: Duration) -> crate::timer::Delay;
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "android"))]
+impl ControlPipe for Device {
+    fn control_out(
+        &self,
+        data: ControlOut,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
+        Device::control_out(self, data, timeout)
+    }
+
+    fn control_in(
+        &self,
+        data: ControlIn,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<Vec<u8>, TransferError>> {
+        Device::control_in(self, data, timeout)
+    }
+
+    fn delay(&self, timeout: Duration) -> crate::timer::Delay {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return self.backend.delay(timeout);
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        return crate::timer::Delay::new(timeout);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ControlPipe for Interface {
+    fn control_out(
+        &self,
+        data: ControlOut,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
+        Interface::control_out(self, data, timeout)
+    }
+
+    fn control_in(
+        &self,
+        data: ControlIn,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<Vec<u8>, TransferError>> {
+        Interface::control_in(self, data, timeout)
+    }
+
+    fn delay(&self, timeout: Duration) -> crate::timer::Delay {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return self.backend.device.delay(timeout);
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        return crate::timer::Delay::new(timeout);
+    }
+}
+
+/// Send a command and poll its status as configured by `poll`.
+#[cfg(not(target_arch = "wasm32"))]
+fn control_command_sequence(
+    pipe: impl ControlPipe,
+    command: ControlOut,
+    status: ControlIn,
+    ready: impl FnMut(&[u8]) -> bool + Send + 'static,
+    poll: StatusPoll,
+) -> impl MaybeFuture<Output = Result<Vec<u8>, TransferError>> {
+    let data = command.data.to_vec();
+    let (control_type, recipient, request, value, index) = (
+        command.control_type,
+        command.recipient,
+        command.request,
+        command.value,
+        command.index,
+    );
+    Sequence::new(move |mode| async move {
+        poll.run(
+            mode,
+            |timeout| {
+                let command = ControlOut {
+                    control_type,
+                    recipient,
+                    request,
+                    value,
+                    index,
+                    data: &data,
+                };
+                pipe.control_out(command, timeout)
+            },
+            |timeout| pipe.control_in(status, timeout),
+            ready,
+            |timeout| pipe.delay(timeout),
+        )
+        .await
+    })
+}
+
 impl Debug for Interface {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Interface")
@@ -664,10 +1537,53 @@ impl Debug for Interface {
 /// ```
 pub struct Endpoint<EpType, Dir> {
     backend: platform::Endpoint,
+    submissions: Submissions,
+    stats: EndpointStats,
+
     ep_type: PhantomData<EpType>,
     ep_dir: PhantomData<Dir>,
 }
 
+/// IDs and tags of the pending transfers of an [`Endpoint`] or
+/// [`ControlQueue`], in submission order. The backends do not carry these, so
+/// they are re-attached on completion.
+#[derive(Default)]
+struct Submissions {
+    pending: VecDeque<PendingInfo>,
+    next_id: u64,
+}
+
+struct PendingInfo {
+    id: TransferId,
+    tag: u64,
+}
+
+impl Submissions {
+    /// Record a transfer about to be submitted, and assign its ID.
+    fn push(&mut self, tag: u64) -> TransferId {
+        let id = TransferId(self.next_id);
+        self.next_id += 1;
+        self.pending.push_back(PendingInfo { id, tag });
+        id
+    }
+
+    /// Get the position of a pending transfer in submission order.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn position(&self, id: TransferId) -> Option<usize> {
+        self.pending.iter().position(|p| p.id == id)
+    }
+
+    /// Fill in the ID and tag of the oldest pending transfer.
+    fn reattach(&mut self, mut c: Completion) -> Completion {
+        // A completion without a record keeps the default ID and tag
+        if let Some(info) = self.pending.pop_front() {
+            c.id = info.id;
+            c.tag = info.tag;
+        }
+        c
+    }
+}
+
 /// Methods for all endpoints.
 impl<EpType: EndpointType, Dir: EndpointDirection> Endpoint<EpType, Dir> {
     /// Get the endpoint address.
@@ -689,6 +1605,31 @@ impl<EpType: EndpointType, Dir: EndpointDirection> Endpoint<EpType, Dir> {
         self.backend.pending()
     }
 
+    /// Get counters for the transfers completed on this endpoint.
+    pub fn stats(&self) -> EndpointStats {
+        self.stats
+    }
+
+    /// Reset the counters returned by [`stats`](Self::stats).
+    pub fn reset_stats(&mut self) {
+        self.stats = EndpointStats::default();
+    }
+
+    /// Get the capabilities of the device this endpoint belongs to.
+    ///
+    /// See [`Device::capabilities`].
+    pub fn device_capabilities(&self) -> Capabilities {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            self.backend.interface().device.capabilities()
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            Capabilities::default()
+        }
+    }
+
     /// Request cancellation of all pending transfers.
     ///
     /// The transfers are cancelled asynchronously. Once cancelled, they will be
@@ -712,6 +1653,38 @@ impl<EpType: BulkOrInterrupt> Endpoint<EpType, Out> {
     pub fn writer(self, buffer_size: usize) -> EndpointWrite<EpType> {
         EndpointWrite::new(self, buffer_size)
     }
+
+    /// Create an [`EndpointSink`] wrapping the given endpoint to submit
+    /// buffers through the [`futures_sink::Sink`] trait.
+    ///
+    /// See [`EndpointSink::new`][`crate::io::EndpointSink::new`] for details.
+    pub fn sink(self) -> EndpointSink<EpType> {
+        EndpointSink::new(self)
+    }
+
+    /// Begin a single transfer sending the concatenation of `bufs`.
+    ///
+    /// This allows a transfer to be built from separate pieces, such as a
+    /// header and a payload, without assembling them first. The transfer
+    /// completes once from [`next_complete()`][`Self::next_complete`] like one
+    /// submitted with [`submit()`][`Self::submit`].
+    ///
+    /// No OS backend accepts a scatter-gather list from userspace (Linux
+    /// usbfs only uses scatter-gather internally, when the host controller
+    /// supports it, to send large contiguous buffers), so the slices are
+    /// copied once into a buffer from [`allocate()`][`Self::allocate`], which
+    /// is zero-copy where supported.
+    ///
+    /// The [`tag`](Completion::tag) of the completion is set to `tag`, as
+    /// with [`submit_tagged`](Self::submit_tagged). Pass 0 if it is not used.
+    pub fn submit_vectored(&mut self, bufs: &[IoSlice<'_>], tag: u64) -> TransferId {
+        let len = bufs.iter().map(|b| b.len()).sum();
+        let mut buf = self.allocate(len);
+        for b in bufs {
+            buf.extend_from_slice(b);
+        }
+        self.submit_tagged(buf, tag)
+    }
 }
 
 impl<EpType: BulkOrInterrupt> Endpoint<EpType, In> {
@@ -723,6 +1696,14 @@ impl<EpType: BulkOrInterrupt> Endpoint<EpType, In> {
     pub fn reader(self, buffer_size: usize) -> EndpointRead<EpType> {
         EndpointRead::new(self, buffer_size)
     }
+
+    /// Create an [`EndpointStream`] wrapping the given endpoint to receive
+    /// completed transfers through the [`futures_core::Stream`] trait.
+    ///
+    /// See [`EndpointStream::new`][`crate::io::EndpointStream::new`] for details.
+    pub fn stream(self, transfer_size: usize) -> EndpointStream<EpType> {
+        EndpointStream::new(self, transfer_size)
+    }
 }
 
 /// Methods for Bulk and Interrupt endpoints.
@@ -767,7 +1748,84 @@ impl<EpType: BulkOrInterrupt, Dir: EndpointDirection> Endpoint<EpType, Dir> {
     /// `TransferError::InvalidArgument`. Up to `requested_len /
     /// max_packet_size` packets will be received, ending early when any packet
     /// is shorter than `max_packet_size`.
-    pub fn submit(&mut self, buf: Buffer) {
+    ///
+    /// Returns an ID for the transfer, which is also found in its
+    /// [`Completion`].
+    pub fn submit(&mut self, buf: Buffer) -> TransferId {
+        self.submit_tagged(buf, 0)
+    }
+
+    /// Begin a transfer on the endpoint, with a tag returned in its
+    /// [`Completion`].
+    ///
+    /// This is like [`submit`](Self::submit), but the [`tag`](Completion::tag)
+    /// of the completion is set to `tag`, so that protocol layers that keep
+    /// many transfers pending can associate each completion with their own
+    /// request without keeping a separate queue.
+    pub fn submit_tagged(&mut self, buf: Buffer, tag: u64) -> TransferId {
+        let (id, buf) = self.prepare_submit(buf, tag);
+        if let Some(buf) = buf {
+            self.backend.submit(buf)
+        }
+        id
+    }
+
+    /// Begin a transfer on the endpoint, cancelling it if it does not
+    /// complete within `timeout`.
+    ///
+    /// This is like [`submit`](Self::submit), but the OS cancels only this
+    /// transfer when the timeout elapses, and other pending transfers are not
+    /// affected. A transfer that timed out is returned from
+    /// [`next_complete()`][`Self::next_complete`] with a status of
+    /// [`TransferError::TimedOut`] and any data transferred before the
+    /// timeout.
+    ///
+    /// Since transfers complete in order, a transfer that times out while an
+    /// earlier transfer is still pending is not returned until the earlier
+    /// transfer completes.
+    ///
+    /// The [`tag`](Completion::tag) of the completion is set to `tag`, as
+    /// with [`submit_tagged`](Self::submit_tagged). Pass 0 if it is not used.
+    ///
+    /// Only supported on Linux and Android.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn submit_with_timeout(&mut self, buf: Buffer, timeout: Duration, tag: u64) -> TransferId {
+        let (id, buf) = self.prepare_submit(buf, tag);
+        if let Some(buf) = buf {
+            self.backend.submit_timeout(buf, timeout)
+        }
+        id
+    }
+
+    /// Request cancellation of a single pending transfer.
+    ///
+    /// Unlike [`cancel_all`](Self::cancel_all), other pending transfers are
+    /// not affected. The transfer is still returned from
+    /// [`next_complete()`][`Self::next_complete`] in submission order, with a
+    /// status of [`TransferError::Cancelled`] unless it completed before it
+    /// could be cancelled.
+    ///
+    /// Returns `false` if no transfer with this ID is pending.
+    ///
+    /// Only supported on Linux and Android.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn cancel(&mut self, id: TransferId) -> bool {
+        match self.submissions.position(id) {
+            Some(index) => {
+                self.backend.cancel(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Assign an ID, record the tag, and check the length of an IN transfer.
+    ///
+    /// Returns `None` for the buffer if the transfer was completed with an
+    /// error instead of being passed on for submission.
+    fn prepare_submit(&mut self, buf: Buffer, tag: u64) -> (TransferId, Option<Buffer>) {
+        let id = self.submissions.push(tag);
+
         if Dir::DIR == Direction::In {
             let req_len = buf.requested_len();
             if req_len == 0 || req_len % self.max_packet_size() != 0 {
@@ -777,11 +1835,12 @@ impl<EpType: BulkOrInterrupt, Dir: EndpointDirection> Endpoint<EpType, Dir> {
                     self.endpoint_address(),
                 );
 
-                return self.backend.submit_err(buf, TransferError::InvalidArgument);
+                self.backend.submit_err(buf, TransferError::InvalidArgument);
+                return (id, None);
             }
         }
 
-        self.backend.submit(buf)
+        (id, Some(buf))
     }
 
     /// Return a `Future` that waits for the next pending transfer to complete.
@@ -811,7 +1870,14 @@ impl<EpType: BulkOrInterrupt, Dir: EndpointDirection> Endpoint<EpType, Dir> {
     ///  * if there are no transfers pending (that is, if [`Self::pending()`]
     ///    would return 0).
     pub fn poll_next_complete(&mut self, cx: &mut Context<'_>) -> Poll<Completion> {
-        self.backend.poll_next_complete(cx)
+        let c = ready!(self.backend.poll_next_complete(cx));
+        Poll::Ready(self.reattach_info(c))
+    }
+
+    fn reattach_info(&mut self, c: Completion) -> Completion {
+        let c = self.submissions.reattach(c);
+        self.stats.record(&c);
+        c
     }
 
     /// Wait for a pending transfer completion.
@@ -827,7 +1893,49 @@ impl<EpType: BulkOrInterrupt, Dir: EndpointDirection> Endpoint<EpType, Dir> {
     ///    would return 0).
     #[cfg(not(target_arch = "wasm32"))]
     pub fn wait_next_complete(&mut self, timeout: Duration) -> Option<Completion> {
-        self.backend.wait_next_complete(timeout)
+        self.backend
+            .wait_next_complete(timeout)
+            .map(|c| self.reattach_info(c))
+    }
+
+    /// Wait for a pending transfer completion, with a timeout.
+    ///
+    /// Async version of [`wait_next_complete`](Self::wait_next_complete):
+    /// returns `None` if `timeout` elapses before a transfer completes. The
+    /// timeout is implemented by nusb and does not require an async runtime.
+    ///
+    /// Note that the transfer is not cancelled after the timeout, and can still
+    /// be returned from a subsequent call. To abandon it, call
+    /// [`cancel_all`](Self::cancel_all) and wait for the cancelled transfers
+    /// with [`next_complete`](Self::next_complete).
+    ///
+    /// ## Panics
+    ///  * if there are no transfers pending (that is, if [`Self::pending()`]
+    ///    would return 0).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn next_complete_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> impl Future<Output = Option<Completion>> + Send + Sync + '_ {
+        let mut delay = self.delay(timeout);
+        poll_fn(move |cx| {
+            if let Poll::Ready(c) = self.poll_next_complete(cx) {
+                return Poll::Ready(Some(c));
+            }
+            ready!(Pin::new(&mut delay).poll(cx));
+            Poll::Ready(None)
+        })
+    }
+
+    /// Create a future that completes after `timeout`, using the timer
+    /// backing this endpoint's device where available.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn delay(&self, timeout: Duration) -> crate::timer::Delay {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return self.backend.delay(timeout);
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        return crate::timer::Delay::new(timeout);
     }
 
     /// Submit a single transfer and wait for it to complete.
@@ -840,7 +1948,7 @@ impl<EpType: BulkOrInterrupt, Dir: EndpointDirection> Endpoint<EpType, Dir> {
     /// leaves a transfer pending.
     ///
     /// In the case of a timeout, the returned `Completion` will have a status
-    /// of `TransferError::Cancelled`.
+    /// of `TransferError::TimedOut`.
     ///
     /// ## Panics
     ///  * if any transfer is already pending.
@@ -854,7 +1962,10 @@ impl<EpType: BulkOrInterrupt, Dir: EndpointDirection> Endpoint<EpType, Dir> {
 
         self.cancel_all();
         loop {
-            if let Some(completion) = self.wait_next_complete(Duration::from_secs(1)) {
+            if let Some(mut completion) = self.wait_next_complete(Duration::from_secs(1)) {
+                if completion.status == Err(TransferError::Cancelled) {
+                    completion.status = Err(TransferError::TimedOut);
+                }
                 return completion;
             }
             log::warn!("cancelled transfer due to timeout, but it has not yet returned");
@@ -875,6 +1986,44 @@ impl<EpType: BulkOrInterrupt, Dir: EndpointDirection> Endpoint<EpType, Dir> {
     pub fn clear_halt(&mut self) -> impl MaybeFuture<Output = Result<(), Error>> {
         self.backend.clear_halt()
     }
+
+    /// Start [`clear_halt`](Self::clear_halt) on a background thread, for use
+    /// from `poll` methods that can't hold the borrowing future.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn clear_halt_task(&mut self) -> BlockingTask<Result<(), Error>> {
+        let clear = self.backend.clear_halt();
+        std::future::IntoFuture::into_future(Blocking::new(move || clear.wait()))
+    }
+
+    /// Send a `GET_STATUS` request for the endpoint's status.
+    pub fn get_status(
+        &self,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<EndpointStatus, TransferError>> {
+        self.interface()
+            .control_in(
+                standard_request::get_endpoint_status(self.endpoint_address()),
+                timeout,
+            )
+            .map(standard_request::endpoint_status)
+    }
+
+    /// Send a `SET_FEATURE` `ENDPOINT_HALT` request to halt the endpoint.
+    ///
+    /// Use [`clear_halt`][Self::clear_halt] to resume use of the endpoint.
+    pub fn set_halt(
+        &mut self,
+        timeout: Duration,
+    ) -> impl MaybeFuture<Output = Result<(), TransferError>> {
+        self.interface().control_out(
+            standard_request::endpoint_halt(self.endpoint_address(), true),
+            timeout,
+        )
+    }
+
+    fn interface(&self) -> Interface {
+        Interface::wrap(self.backend.interface().clone())
+    }
 }
 
 impl<EpType: BulkOrInterrupt, Dir: EndpointDirection> Debug for Endpoint<EpType, Dir> {
@@ -890,6 +2039,202 @@ impl<EpType: BulkOrInterrupt, Dir: EndpointDirection> Debug for Endpoint<EpType,
     }
 }
 
+/// A queue of control transfers on the default control endpoint, using
+/// caller-provided [`Buffer`]s.
+///
+/// Obtain a `ControlQueue` with [`Device::control_queue`] or
+/// [`Interface::control_queue`].
+///
+/// Unlike [`Device::control_in`] and [`Device::control_out`], which allocate
+/// a new transfer for each request, this follows the submit / complete model
+/// of [`Endpoint`]: multiple transfers can be pending at once, and they are
+/// returned from [`next_complete`](Self::next_complete) in submission order
+/// with the buffer, so it can be reused for the next request.
+///
+/// The buffer of a [`Completion`] holds only the data stage of the transfer.
+/// Buffers from [`allocate`](Self::allocate) have room for the SETUP packet in
+/// addition to the requested data length, so they can be resubmitted without
+/// reallocating.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use nusb::{transfer::{ControlIn, ControlType, Recipient}, MaybeFuture};
+/// # let di = nusb::list_devices().wait().unwrap().next().unwrap();
+/// # let device = di.open().wait().unwrap();
+/// let request = ControlIn {
+///     control_type: ControlType::Vendor,
+///     recipient: Recipient::Device,
+///     request: 0x30,
+///     value: 0x0,
+///     index: 0x0,
+///     length: 64,
+/// };
+///
+/// let mut queue = device.control_queue();
+/// for _ in 0..4 {
+///     let buf = queue.allocate(64);
+///     queue.submit_in(request, buf, Duration::from_millis(100));
+/// }
+///
+/// loop {
+///     let completion = queue.wait_next_complete(Duration::from_secs(1)).unwrap();
+///     println!("{:?}", &completion.buffer[..]);
+///     queue.submit_in(request, completion.buffer, Duration::from_millis(100));
+/// }
+/// ```
+///
+/// Dropping the `ControlQueue` cancels any pending transfers.
+///
+/// Only supported on Linux and Android.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub struct ControlQueue {
+    backend: platform::ControlQueue,
+    submissions: Submissions,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl ControlQueue {
+    fn new(backend: platform::ControlQueue) -> Self {
+        ControlQueue {
+            backend,
+            submissions: Submissions::default(),
+        }
+    }
+
+    /// Get the number of transfers that have been submitted that have not yet
+    /// been returned from `next_complete`.
+    pub fn pending(&self) -> usize {
+        self.backend.pending()
+    }
+
+    /// Request cancellation of all pending transfers.
+    ///
+    /// The transfers are cancelled asynchronously. Once cancelled, they will
+    /// be returned from calls to `next_complete`.
+    pub fn cancel_all(&mut self) {
+        self.backend.cancel_all()
+    }
+
+    /// Allocate a buffer for control transfers with up to `len` bytes of
+    /// data.
+    ///
+    /// Like [`Endpoint::allocate`], this uses memory that can be used
+    /// zero-copy by the OS where supported, falling back to [`Buffer::new`].
+    pub fn allocate(&self, len: usize) -> Buffer {
+        self.backend.allocate(len)
+    }
+
+    /// Begin an **IN (device-to-host)** control transfer.
+    ///
+    /// The contents of `buf` are replaced by the data received. If its
+    /// capacity is too small for `request.length` bytes and the SETUP
+    /// packet, a new buffer is allocated instead.
+    ///
+    /// The transfer is cancelled if it does not complete within `timeout`,
+    /// and returned with a status of [`TransferError::TimedOut`].
+    ///
+    /// Returns an ID for the transfer, which is also found in its
+    /// [`Completion`].
+    pub fn submit_in(&mut self, request: ControlIn, buf: Buffer, timeout: Duration) -> TransferId {
+        let len = SETUP_PACKET_SIZE + request.length as usize;
+        let mut buf = self.prepare_buffer(buf, len);
+        buf.extend_from_slice(&request.setup_packet());
+        buf.set_requested_len(len);
+        self.submit(Direction::In, buf, timeout)
+    }
+
+    /// Begin an **OUT (host-to-device)** control transfer.
+    ///
+    /// `request.data` is copied into `buf`, replacing its contents. If its
+    /// capacity is too small for the data and the SETUP packet, a new buffer
+    /// is allocated instead.
+    ///
+    /// The transfer is cancelled if it does not complete within `timeout`,
+    /// and returned with a status of [`TransferError::TimedOut`].
+    ///
+    /// Returns an ID for the transfer, which is also found in its
+    /// [`Completion`].
+    ///
+    /// ## Panics
+    /// * if `request.data` is longer than `u16::MAX` bytes.
+    pub fn submit_out(
+        &mut self,
+        request: ControlOut,
+        buf: Buffer,
+        timeout: Duration,
+    ) -> TransferId {
+        let mut buf = self.prepare_buffer(buf, SETUP_PACKET_SIZE + request.data.len());
+        buf.extend_from_slice(&request.setup_packet());
+        buf.extend_from_slice(request.data);
+        self.submit(Direction::Out, buf, timeout)
+    }
+
+    fn prepare_buffer(&self, mut buf: Buffer, len: usize) -> Buffer {
+        if buf.capacity() < len {
+            buf = self.allocate(len - SETUP_PACKET_SIZE);
+        }
+        buf.clear();
+        buf
+    }
+
+    fn submit(&mut self, direction: Direction, buf: Buffer, timeout: Duration) -> TransferId {
+        let id = self.submissions.push(0);
+        self.backend.submit(direction, buf, timeout);
+        id
+    }
+
+    /// Return a `Future` that waits for the next pending transfer to complete.
+    ///
+    /// This future is cancel-safe: it can be cancelled and re-created without
+    /// side effects, enabling its use in `select!{}` or similar.
+    ///
+    /// ## Panics
+    /// * if there are no transfers pending (that is, if [`Self::pending()`]
+    ///   would return 0).
+    pub fn next_complete(&mut self) -> impl Future<Output = Completion> + Send + Sync + '_ {
+        poll_fn(|cx| self.poll_next_complete(cx))
+    }
+
+    /// Poll for a pending transfer completion.
+    ///
+    /// Returns a completed transfer if one is available, or arranges for the
+    /// context's waker to be notified when a transfer completes.
+    ///
+    /// ## Panics
+    ///  * if there are no transfers pending (that is, if [`Self::pending()`]
+    ///    would return 0).
+    pub fn poll_next_complete(&mut self, cx: &mut Context<'_>) -> Poll<Completion> {
+        let c = ready!(self.backend.poll_next_complete(cx));
+        Poll::Ready(self.submissions.reattach(c))
+    }
+
+    /// Wait for a pending transfer completion.
+    ///
+    /// Blocks for up to `timeout` waiting for a transfer to complete, or
+    /// returns `None` if the timeout is reached.
+    ///
+    /// Note that the transfer is not cancelled after the timeout, and can still
+    /// be returned from a subsequent call.
+    ///
+    /// ## Panics
+    ///  * if there are no transfers pending (that is, if [`Self::pending()`]
+    ///    would return 0).
+    pub fn wait_next_complete(&mut self, timeout: Duration) -> Option<Completion> {
+        self.backend
+            .wait_next_complete(timeout)
+            .map(|c| self.submissions.reattach(c))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl Debug for ControlQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ControlQueue")
+            .field("pending", &self.pending())
+            .finish_non_exhaustive()
+    }
+}
+
 #[test]
 fn assert_send_sync() {
     use crate::transfer::{Bulk, In, Interrupt, Out};
@@ -901,4 +2246,124 @@ fn assert_send_sync() {
     require_send_sync::<Endpoint<Bulk, Out>>();
     require_send_sync::<Endpoint<Interrupt, In>>();
     require_send_sync::<Endpoint<Interrupt, Out>>();
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    require_send_sync::<ControlQueue>();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn test_string_cache() {
+    use futures_lite::future::block_on;
+    use std::cell::{Cell, RefCell};
+
+    struct FakeStrings {
+        languages: Cell<Option<Vec<u16>>>,
+        strings: BTreeMap<(u16, u8), &'static str>,
+        requests: RefCell<Vec<(u16, u8)>>,
+    }
+
+    impl StringSource for FakeStrings {
+        async fn languages(&self) -> Result<Vec<u16>, GetDescriptorError> {
+            self.languages
+                .take()
+                .ok_or(GetDescriptorError::Transfer(TransferError::Stall))
+        }
+
+        async fn string(
+            &self,
+            desc_index: NonZeroU8,
+            language_id: u16,
+        ) -> Result<String, GetDescriptorError> {
+            self.requests
+                .borrow_mut()
+                .push((language_id, desc_index.get()));
+            self.strings
+                .get(&(language_id, desc_index.get()))
+                .map(|s| s.to_string())
+                .ok_or(GetDescriptorError::Transfer(TransferError::Stall))
+        }
+
+        fn referenced_string_indexes(&self) -> BTreeSet<NonZeroU8> {
+            [1, 2].into_iter().flat_map(NonZeroU8::new).collect()
+        }
+    }
+
+    const GERMAN: u16 = 0x0407;
+    let source = FakeStrings {
+        languages: Cell::new(None),
+        strings: [
+            ((US_ENGLISH, 1), "Manufacturer"),
+            ((US_ENGLISH, 2), "Product"),
+            ((US_ENGLISH, 3), "Interface"),
+            ((GERMAN, 1), "Hersteller"),
+        ]
+        .into(),
+        requests: RefCell::new(Vec::new()),
+    };
+    let cache = Mutex::new(StringCache::default());
+    let lookup = |index: u8, language_id| {
+        let index = NonZeroU8::new(index).unwrap();
+        block_on(StringCache::string(&cache, &source, index, language_id))
+    };
+    let requests = || std::mem::take(&mut *source.requests.borrow_mut());
+
+    // The language list failed, so it is not cached, and US English is used.
+    // The first miss preloads the referenced strings.
+    assert_eq!(lookup(3, GERMAN).unwrap(), "Interface");
+    assert_eq!(
+        requests(),
+        [(US_ENGLISH, 1), (US_ENGLISH, 2), (US_ENGLISH, 3)]
+    );
+
+    // Hits, including preloaded strings, don't perform requests
+    assert_eq!(lookup(2, US_ENGLISH).unwrap(), "Product");
+    assert_eq!(lookup(3, US_ENGLISH).unwrap(), "Interface");
+    assert_eq!(requests(), []);
+
+    // The language list is fetched again, and German is now used
+    source.languages.set(Some(vec![US_ENGLISH, GERMAN]));
+    assert_eq!(lookup(1, GERMAN).unwrap(), "Hersteller");
+    assert_eq!(requests(), [(GERMAN, 2), (GERMAN, 1)]);
+    assert_eq!(
+        block_on(StringCache::languages(&cache, &source)).unwrap(),
+        [US_ENGLISH, GERMAN]
+    );
+
+    // Missing in German falls back to the cached US English string, and the
+    // failure is not cached
+    assert_eq!(lookup(2, GERMAN).unwrap(), "Product");
+    assert_eq!(lookup(2, GERMAN).unwrap(), "Product");
+    assert_eq!(requests(), [(GERMAN, 2), (GERMAN, 2)]);
+
+    // Failures are returned and requested again each time
+    assert!(lookup(4, US_ENGLISH).is_err());
+    assert!(lookup(4, US_ENGLISH).is_err());
+    assert_eq!(requests(), [(US_ENGLISH, 4), (US_ENGLISH, 4)]);
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn test_suspend() {
+    let fake = crate::platform::fake::FakeDevice::new();
+    let device = fake.device();
+
+    device.allow_suspend().unwrap();
+    assert!(fake.suspend_allowed());
+    device.forbid_suspend().unwrap();
+    assert!(!fake.suspend_allowed());
+
+    device.allow_suspend().unwrap();
+    fake.suspend();
+    std::thread::scope(|s| {
+        let waiter = s.spawn(|| device.wait_for_resume().wait());
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(!waiter.is_finished());
+        fake.resume();
+        waiter.join().unwrap().unwrap();
+    });
+    assert!(!fake.suspend_allowed());
+
+    fake.disconnect();
+    let err = device.allow_suspend().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Disconnected);
 }
@@ -1,11 +1,18 @@
 #[cfg(target_os = "windows")]
 use std::ffi::{OsStr, OsString};
 
-#[cfg(any(target_os = "linux"))]
+#[cfg(target_os = "linux")]
 use crate::platform::SysfsPath;
 
 use crate::{Device, Error, MaybeFuture};
 
+#[cfg(target_os = "linux")]
+use crate::ErrorKind;
+
+/// How long [`DeviceInfo::reset_port`] keeps the port disabled.
+#[cfg(target_os = "linux")]
+const PORT_DISABLE_TIME: std::time::Duration = std::time::Duration::from_millis(500);
+
 /// Opaque device identifier
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub struct DeviceId(pub(crate) crate::platform::DeviceId);
@@ -17,9 +24,14 @@ pub struct DeviceId(pub(crate) crate::platform::DeviceId);
 /// ### Platform-specific notes
 ///
 /// * Some fields are platform-specific
-///     * Linux: `sysfs_path`, `busnum`
+///     * Linux: `sysfs_path`, `busnum`, `devpath`, `num_configurations`,
+///       `configuration_value`, `max_power`, `removable`, `authorized`,
+///       `avoid_reset_quirk`, `quirks`, `ltm_capable`, `lanes`
 ///     * Windows: `instance_id`, `parent_instance_id`, `port_number`, `driver`
 ///     * macOS: `registry_id`, `location_id`
+/// * Linux: the power management methods (`power_control`,
+///   `autosuspend_delay` and `runtime_status`) read sysfs each time they are
+///   called, rather than returning data cached when the device was listed.
 #[derive(Clone)]
 pub struct DeviceInfo {
     #[cfg(target_os = "linux")]
@@ -28,6 +40,36 @@ pub struct DeviceInfo {
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub(crate) busnum: u8,
 
+    #[cfg(target_os = "linux")]
+    pub(crate) devpath: String,
+
+    #[cfg(target_os = "linux")]
+    pub(crate) num_configurations: Option<u8>,
+
+    #[cfg(target_os = "linux")]
+    pub(crate) configuration_value: Option<u8>,
+
+    #[cfg(target_os = "linux")]
+    pub(crate) max_power: Option<u16>,
+
+    #[cfg(target_os = "linux")]
+    pub(crate) removable: Option<bool>,
+
+    #[cfg(target_os = "linux")]
+    pub(crate) authorized: bool,
+
+    #[cfg(target_os = "linux")]
+    pub(crate) avoid_reset_quirk: bool,
+
+    #[cfg(target_os = "linux")]
+    pub(crate) quirks: u32,
+
+    #[cfg(target_os = "linux")]
+    pub(crate) ltm_capable: bool,
+
+    #[cfg(target_os = "linux")]
+    pub(crate) lanes: Option<(u8, u8)>,
+
     #[cfg(target_os = "windows")]
     pub(crate) instance_id: OsString,
 
@@ -136,11 +178,98 @@ impl DeviceInfo {
     /// *(Linux-only)* Bus number.
     ///
     /// On Linux, the `bus_id` is an integer and this provides the value as `u8`.
-    #[cfg(any(target_os = "linux"))]
+    #[cfg(target_os = "linux")]
     pub fn busnum(&self) -> u8 {
         self.busnum
     }
 
+    /// *(Linux-only)* Port path of the device within its bus, from the sysfs
+    /// `devpath` attribute.
+    ///
+    /// This is the dot-separated form of [`port_chain`](Self::port_chain),
+    /// e.g. `"1.4"`, or `"0"` for a root hub. Unlike the
+    /// [`device_address`](Self::device_address) (sysfs `devnum`), it stays the
+    /// same when the device is reconnected to the same port.
+    #[cfg(target_os = "linux")]
+    pub fn devpath(&self) -> &str {
+        &self.devpath
+    }
+
+    /// *(Linux-only)* Number of configurations, from the
+    /// `bNumConfigurations` device descriptor field.
+    ///
+    /// `None` if the sysfs attribute could not be read.
+    #[cfg(target_os = "linux")]
+    pub fn num_configurations(&self) -> Option<u8> {
+        self.num_configurations
+    }
+
+    /// *(Linux-only)* Value of the active configuration, from the sysfs
+    /// `bConfigurationValue` attribute.
+    ///
+    /// `None` if the device is unconfigured.
+    #[cfg(target_os = "linux")]
+    pub fn configuration_value(&self) -> Option<u8> {
+        self.configuration_value
+    }
+
+    /// *(Linux-only)* Maximum power consumption of the active configuration
+    /// in milliamps, from the sysfs `bMaxPower` attribute.
+    ///
+    /// `None` if the device is unconfigured.
+    #[cfg(target_os = "linux")]
+    pub fn max_power(&self) -> Option<u16> {
+        self.max_power
+    }
+
+    /// *(Linux-only)* Whether the device is connected to a port that allows
+    /// it to be removed, from the sysfs `removable` attribute.
+    ///
+    /// `None` if the hub does not report this.
+    #[cfg(target_os = "linux")]
+    pub fn removable(&self) -> Option<bool> {
+        self.removable
+    }
+
+    /// *(Linux-only)* Whether the device is authorized for use, from the sysfs
+    /// `authorized` attribute.
+    ///
+    /// Unauthorized devices are left unconfigured by the kernel.
+    #[cfg(target_os = "linux")]
+    pub fn authorized(&self) -> bool {
+        self.authorized
+    }
+
+    /// *(Linux-only)* Whether the kernel avoids resetting the device, from the
+    /// sysfs `avoid_reset_quirk` attribute.
+    #[cfg(target_os = "linux")]
+    pub fn avoid_reset_quirk(&self) -> bool {
+        self.avoid_reset_quirk
+    }
+
+    /// *(Linux-only)* Bitmask of `USB_QUIRK_*` flags the kernel applies to the
+    /// device, from the sysfs `quirks` attribute.
+    #[cfg(target_os = "linux")]
+    pub fn quirks(&self) -> u32 {
+        self.quirks
+    }
+
+    /// *(Linux-only)* Whether the device supports Latency Tolerance Messaging,
+    /// from the sysfs `ltm_capable` attribute.
+    #[cfg(target_os = "linux")]
+    pub fn ltm_capable(&self) -> bool {
+        self.ltm_capable
+    }
+
+    /// *(Linux-only)* Number of transmit and receive lanes in use, from the
+    /// sysfs `tx_lanes` and `rx_lanes` attributes.
+    ///
+    /// `None` if the kernel does not report lanes (before Linux 4.18).
+    #[cfg(target_os = "linux")]
+    pub fn lanes(&self) -> Option<(u8, u8)> {
+        self.lanes
+    }
+
     /// *(Windows-only)* Instance ID path of this device
     #[cfg(target_os = "windows")]
     pub fn instance_id(&self) -> &OsStr {
@@ -311,6 +440,174 @@ impl DeviceInfo {
     pub fn open(&self) -> impl MaybeFuture<Output = Result<Device, Error>> {
         Device::open(self)
     }
+
+    /// *(Linux-only)* Get the runtime power management setting of the device,
+    /// from the sysfs `power/control` attribute.
+    #[cfg(target_os = "linux")]
+    pub fn power_control(&self) -> Result<PowerControl, Error> {
+        self.path
+            .read_attr_with("power/control", PowerControl::from_str)
+    }
+
+    /// *(Linux-only)* Get the idle time after which the device is
+    /// autosuspended, from the sysfs `power/autosuspend_delay_ms` attribute.
+    ///
+    /// Returns `None` if autosuspend is disabled by a negative delay.
+    #[cfg(target_os = "linux")]
+    pub fn autosuspend_delay(&self) -> Result<Option<std::time::Duration>, Error> {
+        self.path
+            .read_attr_with("power/autosuspend_delay_ms", |s| s.parse::<i64>().ok())
+            .map(|ms| u64::try_from(ms).ok().map(std::time::Duration::from_millis))
+    }
+
+    /// *(Linux-only)* Get the current runtime power management state of the
+    /// device, from the sysfs `power/runtime_status` attribute.
+    #[cfg(target_os = "linux")]
+    pub fn runtime_status(&self) -> Result<RuntimeStatus, Error> {
+        self.path
+            .read_attr_with("power/runtime_status", RuntimeStatus::from_str)
+    }
+
+    /// *(Linux-only)* Authorize or deauthorize the device by writing its sysfs
+    /// `authorized` attribute.
+    ///
+    /// Deauthorizing a device unconfigures it and unbinds all drivers, and an
+    /// unauthorized device is not configured until it is authorized. This is
+    /// the basis of USB device authorization policies such as USBGuard.
+    ///
+    /// Requires write access to sysfs, usually root. Returns an error of
+    /// kind [`ErrorKind::PermissionDenied`] otherwise.
+    ///
+    /// The cached [`authorized`](Self::authorized) value is not updated.
+    #[cfg(target_os = "linux")]
+    pub fn set_authorized(&self, authorized: bool) -> Result<(), Error> {
+        self.path
+            .write_attr("authorized", if authorized { "1" } else { "0" })
+    }
+
+    /// *(Linux-only)* Authorize or deauthorize an interface of the active
+    /// configuration by writing its sysfs `authorized` attribute.
+    ///
+    /// A deauthorized interface is unbound from its driver and can't be
+    /// bound again until it is authorized.
+    ///
+    /// Requires write access to sysfs, usually root.
+    #[cfg(target_os = "linux")]
+    pub fn set_interface_authorized(&self, interface: u8, authorized: bool) -> Result<(), Error> {
+        self.path
+            .interface(interface)?
+            .write_attr("authorized", if authorized { "1" } else { "0" })
+    }
+
+    /// *(Linux-only)* Set whether interfaces of devices on this bus are
+    /// authorized by default, by writing the sysfs
+    /// `interface_authorized_default` attribute.
+    ///
+    /// This attribute only exists on root hubs, so this must be called on the
+    /// [`root_hub`][crate::BusInfo::root_hub] of a bus. Otherwise, an error of
+    /// kind [`ErrorKind::NotFound`] is returned. Interfaces that are not
+    /// authorized by default must be authorized with
+    /// [`set_interface_authorized`](Self::set_interface_authorized) before a
+    /// driver binds to them.
+    ///
+    /// Requires write access to sysfs, usually root.
+    #[cfg(target_os = "linux")]
+    pub fn set_interface_authorized_default(&self, authorized: bool) -> Result<(), Error> {
+        self.path.write_attr(
+            "interface_authorized_default",
+            if authorized { "1" } else { "0" },
+        )
+    }
+
+    /// *(Linux-only)* Bind a kernel driver to an interface of the active
+    /// configuration, by writing the interface name to the driver's sysfs
+    /// `bind` attribute.
+    ///
+    /// `driver` is the name of the driver under `/sys/bus/usb/drivers`, as
+    /// returned by [`InterfaceInfo::driver`]. The driver must support the
+    /// device; see [`add_driver_id`](Self::add_driver_id). Returns an error
+    /// of kind [`ErrorKind::InvalidInput`] if no such driver is loaded.
+    ///
+    /// Requires write access to sysfs, usually root.
+    #[cfg(target_os = "linux")]
+    pub fn bind_interface(&self, interface: u8, driver: &str) -> Result<(), Error> {
+        let interface = self.path.interface(interface)?;
+        crate::platform::sysfs_usb_driver(driver)?.write_attr("bind", interface.name())
+    }
+
+    /// *(Linux-only)* Unbind the kernel driver from an interface of the active
+    /// configuration, by writing the interface name to the driver's sysfs
+    /// `unbind` attribute.
+    ///
+    /// Unlike [`Device::detach_kernel_driver`], this does not require
+    /// opening the device, and the driver is not re-attached when the device
+    /// is closed. Returns an error of kind [`ErrorKind::NotFound`] if no
+    /// driver is bound.
+    ///
+    /// Requires write access to sysfs, usually root.
+    #[cfg(target_os = "linux")]
+    pub fn unbind_interface(&self, interface: u8) -> Result<(), Error> {
+        let interface = self.path.interface(interface)?;
+        interface.write_attr("driver/unbind", interface.name())
+    }
+
+    /// *(Linux-only)* Add this device's vendor and product ID to the IDs
+    /// supported by a kernel driver, by writing the driver's sysfs `new_id`
+    /// attribute.
+    ///
+    /// The kernel then binds the driver to matching interfaces that have no
+    /// driver, including those of this device. The ID is forgotten when the
+    /// driver module is unloaded. Returns an error of kind
+    /// [`ErrorKind::InvalidInput`] if no such driver is loaded.
+    ///
+    /// Requires write access to sysfs, usually root.
+    #[cfg(target_os = "linux")]
+    pub fn add_driver_id(&self, driver: &str) -> Result<(), Error> {
+        crate::platform::sysfs_usb_driver(driver)?.write_attr(
+            "new_id",
+            &format!("{:04x} {:04x}", self.vendor_id, self.product_id),
+        )
+    }
+
+    /// *(Linux-only)* Reset the device by disabling and re-enabling the hub
+    /// port it is connected to, through the port's sysfs `disable`
+    /// attribute.
+    ///
+    /// Unlike [`Device::reset`], this removes power from the port on hubs
+    /// that support it and recovers devices that no longer respond to a USB
+    /// reset. The device disconnects and is enumerated again as a new device,
+    /// so this `DeviceInfo` and any open [`Device`] for it become stale.
+    ///
+    /// This blocks while the port is disabled, for half a second.
+    ///
+    /// Requires write access to sysfs, usually root, and a kernel with the
+    /// port `disable` attribute.
+    #[cfg(target_os = "linux")]
+    pub fn reset_port(&self) -> Result<(), Error> {
+        // Resolve the port first, because the device's directory goes away
+        // when the port is disabled.
+        let port = std::fs::canonicalize(self.path.0.join("port"))
+            .map_err(|_| Error::new(ErrorKind::NotFound, "device has no hub port"))?;
+        let port = SysfsPath(port);
+        port.write_attr("disable", "1")?;
+        // Give the device time to notice the disconnect and, where the hub
+        // switches port power, to lose power before the port comes back.
+        std::thread::sleep(PORT_DISABLE_TIME);
+        port.write_attr("disable", "0")
+    }
+
+    /// *(Linux-only)* Open the device through a [`Broker`][crate::broker::Broker]
+    /// listening on the Unix socket at `socket_path`.
+    ///
+    /// This is useful when the current process lacks permission to open the
+    /// device directly. See the [`broker`][crate::broker] module.
+    #[cfg(target_os = "linux")]
+    pub fn open_via_broker(
+        &self,
+        socket_path: impl AsRef<std::path::Path>,
+    ) -> impl MaybeFuture<Output = Result<Device, Error>> {
+        crate::broker::open(socket_path.as_ref().to_owned(), self)
+    }
 }
 
 // Not derived so that we can format some fields in hex
@@ -349,6 +646,16 @@ impl std::fmt::Debug for DeviceInfo {
         #[cfg(target_os = "linux")]
         {
             s.field("sysfs_path", &self.path);
+            s.field("devpath", &self.devpath);
+            s.field("num_configurations", &self.num_configurations);
+            s.field("configuration_value", &self.configuration_value);
+            s.field("max_power", &self.max_power);
+            s.field("removable", &self.removable);
+            s.field("authorized", &self.authorized);
+            s.field("avoid_reset_quirk", &self.avoid_reset_quirk);
+            s.field("quirks", &format_args!("0x{:X}", self.quirks));
+            s.field("ltm_capable", &self.ltm_capable);
+            s.field("lanes", &self.lanes);
         }
 
         #[cfg(target_os = "windows")]
@@ -377,6 +684,7 @@ impl std::fmt::Debug for DeviceInfo {
 
 /// USB connection speed
 #[derive(Copy, Clone, Eq, PartialOrd, Ord, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Speed {
     /// Low speed (1.5 Mbit)
@@ -410,13 +718,24 @@ impl Speed {
 }
 
 /// Summary information about a device's interface, available before opening a device.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InterfaceInfo {
     pub(crate) interface_number: u8,
     pub(crate) class: u8,
     pub(crate) subclass: u8,
     pub(crate) protocol: u8,
     pub(crate) interface_string: Option<String>,
+
+    // The Linux-only fields are not serialized, so that the format is the
+    // same on all platforms.
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) alternate_setting: u8,
+
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) driver: Option<String>,
 }
 
 impl InterfaceInfo {
@@ -444,23 +763,49 @@ impl InterfaceInfo {
     pub fn interface_string(&self) -> Option<&str> {
         self.interface_string.as_deref()
     }
+
+    /// *(Linux-only)* Currently selected alternate setting, from the sysfs
+    /// `bAlternateSetting` attribute.
+    #[cfg(target_os = "linux")]
+    pub fn alternate_setting(&self) -> u8 {
+        self.alternate_setting
+    }
+
+    /// *(Linux-only)* Name of the kernel driver bound to the interface, if
+    /// any.
+    ///
+    /// This is `"usbfs"` if the interface is claimed by a process through
+    /// usbfs. Otherwise, claiming the interface requires
+    /// [`detach_and_claim_interface`][crate::Device::detach_and_claim_interface].
+    #[cfg(target_os = "linux")]
+    pub fn driver(&self) -> Option<&str> {
+        self.driver.as_deref()
+    }
 }
 
 // Not derived so that we can format some fields in hex
 impl std::fmt::Debug for InterfaceInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("InterfaceInfo")
-            .field("interface_number", &self.interface_number)
+        let mut s = f.debug_struct("InterfaceInfo");
+        s.field("interface_number", &self.interface_number)
             .field("class", &format_args!("0x{:02X}", self.class))
             .field("subclass", &format_args!("0x{:02X}", self.subclass))
             .field("protocol", &format_args!("0x{:02X}", self.protocol))
-            .field("interface_string", &self.interface_string)
-            .finish()
+            .field("interface_string", &self.interface_string);
+
+        #[cfg(target_os = "linux")]
+        {
+            s.field("alternate_setting", &self.alternate_setting);
+            s.field("driver", &self.driver);
+        }
+
+        s.finish()
     }
 }
 
 /// USB host controller type
 #[derive(Copy, Clone, Eq, PartialOrd, Ord, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum UsbControllerType {
     /// xHCI controller (USB 3.0+)
@@ -498,6 +843,75 @@ impl UsbControllerType {
     }
 }
 
+/// Runtime power management setting of a device.
+///
+/// Returned by [`DeviceInfo::power_control`].
+#[cfg(target_os = "linux")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum PowerControl {
+    /// The device may be suspended when idle (`auto`).
+    Auto,
+
+    /// The device is kept powered on (`on`).
+    On,
+}
+
+#[cfg(target_os = "linux")]
+impl PowerControl {
+    pub(crate) fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "auto" => Some(PowerControl::Auto),
+            "on" => Some(PowerControl::On),
+            _ => None,
+        }
+    }
+}
+
+/// Runtime power management state of a device.
+///
+/// Returned by [`DeviceInfo::runtime_status`].
+#[cfg(target_os = "linux")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum RuntimeStatus {
+    /// The device is powered and operating normally.
+    Active,
+
+    /// The device is suspended.
+    Suspended,
+
+    /// The device is in the process of suspending.
+    Suspending,
+
+    /// The device is in the process of resuming.
+    Resuming,
+
+    /// Runtime power management failed, and is disabled until the error is
+    /// cleared.
+    Error,
+
+    /// Runtime power management is not enabled for the device.
+    Unsupported,
+}
+
+#[cfg(target_os = "linux")]
+impl RuntimeStatus {
+    pub(crate) fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "active" => Some(RuntimeStatus::Active),
+            "suspended" => Some(RuntimeStatus::Suspended),
+            "suspending" => Some(RuntimeStatus::Suspending),
+            "resuming" => Some(RuntimeStatus::Resuming),
+            "error" => Some(RuntimeStatus::Error),
+            "unsupported" => Some(RuntimeStatus::Unsupported),
+            _ => None,
+        }
+    }
+}
+
 /// Information about a system USB bus.
 ///
 /// Platform-specific fields:
@@ -506,14 +920,14 @@ impl UsbControllerType {
 /// * macOS: `registry_id`, `location_id`, `name`, `provider_class_name`, `class_name`
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 pub struct BusInfo {
-    #[cfg(any(target_os = "linux"))]
+    #[cfg(target_os = "linux")]
     pub(crate) path: SysfsPath,
 
     /// The phony root hub device
-    #[cfg(any(target_os = "linux"))]
+    #[cfg(target_os = "linux")]
     pub(crate) root_hub: DeviceInfo,
 
-    #[cfg(any(target_os = "linux"))]
+    #[cfg(target_os = "linux")]
     pub(crate) busnum: u8,
 
     #[cfg(target_os = "windows")]
@@ -558,7 +972,7 @@ pub struct BusInfo {
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 impl BusInfo {
     /// *(Linux-only)* Sysfs path for the bus.
-    #[cfg(any(target_os = "linux"))]
+    #[cfg(target_os = "linux")]
     pub fn sysfs_path(&self) -> &std::path::Path {
         &self.path.0
     }
@@ -566,13 +980,13 @@ impl BusInfo {
     /// *(Linux-only)* Bus number.
     ///
     /// On Linux, the `bus_id` is an integer and this provides the value as `u8`.
-    #[cfg(any(target_os = "linux"))]
+    #[cfg(target_os = "linux")]
     pub fn busnum(&self) -> u8 {
         self.busnum
     }
 
     /// *(Linux-only)* The root hub [`DeviceInfo`] representing the bus.
-    #[cfg(any(target_os = "linux"))]
+    #[cfg(target_os = "linux")]
     pub fn root_hub(&self) -> &DeviceInfo {
         &self.root_hub
     }
@@ -656,7 +1070,7 @@ impl BusInfo {
     /// * macOS: The [IONameMatched](https://developer.apple.com/documentation/bundleresources/information_property_list/ionamematch) key of the IOService entry.
     /// * Windows: Description field of the root hub device. How the bus will appear in Device Manager.
     pub fn system_name(&self) -> Option<&str> {
-        #[cfg(any(target_os = "linux"))]
+        #[cfg(target_os = "linux")]
         {
             self.root_hub.product_string()
         }
@@ -683,7 +1097,7 @@ impl std::fmt::Debug for BusInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut s = f.debug_struct("BusInfo");
 
-        #[cfg(any(target_os = "linux"))]
+        #[cfg(target_os = "linux")]
         {
             s.field("sysfs_path", &self.path);
             s.field("busnum", &self.busnum);
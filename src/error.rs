@@ -68,6 +68,7 @@ impl From<Error> for io::Error {
             ErrorKind::PermissionDenied => io::ErrorKind::PermissionDenied,
             ErrorKind::NotFound => io::ErrorKind::NotFound,
             ErrorKind::Unsupported => io::ErrorKind::Unsupported,
+            ErrorKind::InvalidInput => io::ErrorKind::InvalidInput,
             ErrorKind::Other => io::ErrorKind::Other,
         };
         io::Error::new(kind, err)
@@ -93,6 +94,9 @@ pub enum ErrorKind {
     /// The requested operation is not supported by the platform or its currently-configured driver.
     Unsupported,
 
+    /// An argument, such as a kernel driver name, is not valid.
+    InvalidInput,
+
     /// Uncategorized error.
     Other,
 }
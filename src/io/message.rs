@@ -0,0 +1,531 @@
+use std::{
+    future::poll_fn,
+    io::{Error, ErrorKind},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Write;
+
+use crate::transfer::{Buffer, BulkOrInterrupt};
+
+use super::{EndpointRead, EndpointWrite};
+
+/// Reader for protocols that delimit messages with short or zero-length
+/// packets, yielding one complete message at a time.
+///
+/// This can be obtained from [`EndpointRead::into_messages()`]. Each message
+/// ends with the first packet shorter than the endpoint's max packet size,
+/// including a zero-length packet sent after a message that is an exact
+/// multiple of the packet size.
+///
+/// Messages are read with [`read_message`](Self::read_message) (blocking),
+/// [`read_message_async`](Self::read_message_async), or through the
+/// [`Stream`](futures_core::Stream) implementation.
+///
+/// A message longer than the configured maximum size is read to its end and
+/// discarded, and reported as an [`ErrorKind::InvalidData`] error. The
+/// following message is then read normally.
+pub struct MessageRead<EpType: BulkOrInterrupt> {
+    reader: EndpointRead<EpType>,
+    max_message_size: usize,
+    partial: Vec<u8>,
+    overflow: bool,
+}
+
+enum ReadStep {
+    Consume(usize),
+    End,
+}
+
+/// Accumulate the data returned by the underlying reader into `partial`,
+/// without consuming it.
+fn step(
+    partial: &mut Vec<u8>,
+    overflow: &mut bool,
+    max_message_size: usize,
+    data: Result<&[u8], Error>,
+) -> Result<ReadStep, Error> {
+    let data = match data {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::TimedOut => return Err(e),
+        Err(e) => {
+            partial.clear();
+            *overflow = false;
+            return Err(e);
+        }
+    };
+
+    if data.is_empty() {
+        return Ok(ReadStep::End);
+    }
+
+    if !*overflow {
+        if partial.len() + data.len() > max_message_size {
+            *overflow = true;
+            *partial = Vec::new();
+        } else {
+            partial.extend_from_slice(data);
+        }
+    }
+    Ok(ReadStep::Consume(data.len()))
+}
+
+impl<EpType: BulkOrInterrupt> MessageRead<EpType> {
+    /// Create a new `MessageRead` wrapping the given `EndpointRead`.
+    ///
+    /// Messages longer than `max_message_size` bytes are discarded and
+    /// reported as an error.
+    pub fn new(reader: EndpointRead<EpType>, max_message_size: usize) -> Self {
+        Self {
+            reader,
+            max_message_size,
+            partial: Vec::new(),
+            overflow: false,
+        }
+    }
+
+    /// Get the maximum message size.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Get a reference to the underlying `EndpointRead`.
+    pub fn get_ref(&self) -> &EndpointRead<EpType> {
+        &self.reader
+    }
+
+    /// Get a mutable reference to the underlying `EndpointRead`.
+    ///
+    /// Reading from the `EndpointRead` directly while a message is partially
+    /// received will corrupt the message framing.
+    pub fn get_mut(&mut self) -> &mut EndpointRead<EpType> {
+        &mut self.reader
+    }
+
+    /// Destroy this `MessageRead` and return the underlying `EndpointRead`.
+    ///
+    /// Any partially received message is discarded.
+    pub fn into_inner(self) -> EndpointRead<EpType> {
+        self.reader
+    }
+
+    /// Read the next complete message, blocking until it has been received.
+    ///
+    /// Returns an [`ErrorKind::InvalidData`] error if the message exceeded
+    /// the maximum size, or [`ErrorKind::TimedOut`] if the reader's timeout
+    /// elapsed while waiting for a packet. After a timeout, the partially
+    /// received message is kept and the next call continues reading it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_message(&mut self) -> Result<Vec<u8>, Error> {
+        loop {
+            let data = self.reader.fill_buf_until_short();
+            match step(
+                &mut self.partial,
+                &mut self.overflow,
+                self.max_message_size,
+                data,
+            )? {
+                ReadStep::Consume(len) => self.reader.consume(len),
+                ReadStep::End => return self.finish(),
+            }
+        }
+    }
+
+    /// Read the next complete message.
+    ///
+    /// Async version of [`read_message`](Self::read_message).
+    pub async fn read_message_async(&mut self) -> Result<Vec<u8>, Error> {
+        poll_fn(|cx| self.poll_read_message(cx))
+            .await
+            .unwrap_or_else(|| {
+                Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "ended without short packet",
+                ))
+            })
+    }
+
+    /// Returns `None` if the reader ended between messages.
+    fn poll_read_message(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Vec<u8>, Error>>> {
+        loop {
+            let data = ready!(self.reader.poll_fill_buf_until_short(cx));
+            let in_progress = !self.partial.is_empty() || self.overflow;
+            match step(
+                &mut self.partial,
+                &mut self.overflow,
+                self.max_message_size,
+                data,
+            ) {
+                Ok(ReadStep::Consume(len)) => self.reader.consume(len),
+                Ok(ReadStep::End) => return Poll::Ready(Some(self.finish())),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof && !in_progress => {
+                    return Poll::Ready(None)
+                }
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>, Error> {
+        self.reader
+            .until_short_packet()
+            .consume_end()
+            .expect("reader is at end of message");
+
+        if std::mem::take(&mut self.overflow) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "message exceeds maximum size",
+            ));
+        }
+        Ok(std::mem::take(&mut self.partial))
+    }
+}
+
+impl<EpType: BulkOrInterrupt> futures_core::Stream for MessageRead<EpType> {
+    type Item = Result<Vec<u8>, Error>;
+
+    /// Read the next complete message.
+    ///
+    /// The stream ends only if the underlying reader has no transfers
+    /// configured and all pending transfers have completed. If that happens
+    /// partway through a message, an [`ErrorKind::UnexpectedEof`] error is
+    /// returned before the stream ends.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::into_inner(self).poll_read_message(cx)
+    }
+}
+
+/// Writer for protocols that delimit messages with short or zero-length
+/// packets, accepting one complete message at a time.
+///
+/// This can be obtained from [`EndpointWrite::into_messages()`]. Each message
+/// is terminated with a short packet, adding a zero-length packet when its
+/// length is a multiple of the endpoint's max packet size.
+///
+/// Messages are sent with [`write_message`](Self::write_message) (blocking),
+/// [`write_message_async`](Self::write_message_async), or through the
+/// [`Sink`](futures_sink::Sink) implementation, which accepts both
+/// `Vec<u8>` and [`Buffer`].
+///
+/// Messages longer than the configured maximum size are rejected with
+/// [`ErrorKind::InvalidInput`] without sending any data.
+pub struct MessageWrite<EpType: BulkOrInterrupt> {
+    writer: EndpointWrite<EpType>,
+    max_message_size: usize,
+    sending: Option<(Buffer, usize)>,
+}
+
+impl<EpType: BulkOrInterrupt> MessageWrite<EpType> {
+    /// Create a new `MessageWrite` wrapping the given `EndpointWrite`.
+    ///
+    /// Messages longer than `max_message_size` bytes are rejected.
+    pub fn new(writer: EndpointWrite<EpType>, max_message_size: usize) -> Self {
+        Self {
+            writer,
+            max_message_size,
+            sending: None,
+        }
+    }
+
+    /// Get the maximum message size.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Get a reference to the underlying `EndpointWrite`.
+    pub fn get_ref(&self) -> &EndpointWrite<EpType> {
+        &self.writer
+    }
+
+    /// Get a mutable reference to the underlying `EndpointWrite`.
+    ///
+    /// Writing to the `EndpointWrite` directly will corrupt the message
+    /// framing.
+    pub fn get_mut(&mut self) -> &mut EndpointWrite<EpType> {
+        &mut self.writer
+    }
+
+    /// Destroy this `MessageWrite` and return the underlying `EndpointWrite`.
+    ///
+    /// A message queued through the `Sink` implementation that has not been
+    /// fully written is discarded.
+    pub fn into_inner(self) -> EndpointWrite<EpType> {
+        self.writer
+    }
+
+    fn check_size(&self, len: usize) -> Result<(), Error> {
+        if len > self.max_message_size {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                "message exceeds maximum size",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write a complete message, terminated by a short or zero-length packet.
+    ///
+    /// This blocks until the message has been submitted, but not until the
+    /// transfers complete; use [`flush`](Self::flush) to wait for them.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write_message(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.check_size(message.len())?;
+        self.flush_sending()?;
+        self.writer.write_all(message)?;
+        self.writer.submit_end();
+        Ok(())
+    }
+
+    /// Write a complete message, terminated by a short or zero-length packet.
+    ///
+    /// Async version of [`write_message`](Self::write_message).
+    ///
+    /// This is cancel-safe: the message is queued before any of it is
+    /// written, so if the future is dropped partway through, the remainder of
+    /// the message is still sent before the next one.
+    pub async fn write_message_async(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.check_size(message.len())?;
+        poll_fn(|cx| self.poll_sending(cx)).await?;
+        self.start_send_buffer(message.to_vec().into())?;
+        poll_fn(|cx| self.poll_sending(cx)).await
+    }
+
+    /// Wait for all submitted messages to be sent.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.flush_sending()?;
+        self.writer.flush()
+    }
+
+    /// Wait for all submitted messages to be sent.
+    ///
+    /// Async version of [`flush`](Self::flush).
+    pub async fn flush_async(&mut self) -> Result<(), Error> {
+        poll_fn(|cx| self.poll_flush_all(cx)).await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn flush_sending(&mut self) -> Result<(), Error> {
+        if let Some((buf, pos)) = self.sending.take() {
+            self.writer.write_all(&buf[pos..])?;
+            self.writer.submit_end();
+        }
+        Ok(())
+    }
+
+    /// Write the remainder of a message queued by `start_send`.
+    fn poll_sending(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        while let Some((buf, pos)) = self.sending.as_mut() {
+            if *pos < buf.len() {
+                *pos += ready!(self.writer.poll_write(cx, &buf[*pos..]))?;
+            } else {
+                self.sending = None;
+                self.writer.submit_end();
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_flush_all(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        ready!(self.poll_sending(cx))?;
+        self.writer.poll_flush(cx)
+    }
+
+    fn start_send_buffer(&mut self, message: Buffer) -> Result<(), Error> {
+        assert!(
+            self.sending.is_none(),
+            "start_send called before poll_ready"
+        );
+        self.check_size(message.len())?;
+        self.sending = Some((message, 0));
+        Ok(())
+    }
+}
+
+impl<EpType: BulkOrInterrupt> futures_sink::Sink<Buffer> for MessageWrite<EpType> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::into_inner(self).poll_sending(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Buffer) -> Result<(), Error> {
+        Pin::into_inner(self).start_send_buffer(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::into_inner(self).poll_flush_all(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::into_inner(self).poll_flush_all(cx)
+    }
+}
+
+impl<EpType: BulkOrInterrupt> futures_sink::Sink<Vec<u8>> for MessageWrite<EpType> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::into_inner(self).poll_sending(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Vec<u8>) -> Result<(), Error> {
+        Pin::into_inner(self).start_send_buffer(item.into())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::into_inner(self).poll_flush_all(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::into_inner(self).poll_flush_all(cx)
+    }
+}
+
+#[test]
+fn test_step() {
+    let mut partial = Vec::new();
+    let mut overflow = false;
+
+    let r = step(&mut partial, &mut overflow, 4, Ok(&[1, 2, 3]));
+    assert!(matches!(r, Ok(ReadStep::Consume(3))));
+    let r = step(&mut partial, &mut overflow, 4, Ok(&[4]));
+    assert!(matches!(r, Ok(ReadStep::Consume(1))));
+    assert!(matches!(
+        step(&mut partial, &mut overflow, 4, Ok(&[])),
+        Ok(ReadStep::End)
+    ));
+    assert_eq!(partial, [1, 2, 3, 4]);
+    assert!(!overflow);
+
+    // A timeout keeps the partial message
+    partial.clear();
+    let _ = step(&mut partial, &mut overflow, 4, Ok(&[1, 2]));
+    let e = Error::new(ErrorKind::TimedOut, "timed out");
+    assert_eq!(
+        step(&mut partial, &mut overflow, 4, Err(e))
+            .err()
+            .unwrap()
+            .kind(),
+        ErrorKind::TimedOut
+    );
+    assert_eq!(partial, [1, 2]);
+
+    // Exceeding the maximum size discards the data but still consumes it
+    let r = step(&mut partial, &mut overflow, 4, Ok(&[3, 4, 5]));
+    assert!(matches!(r, Ok(ReadStep::Consume(3))));
+    assert!(overflow);
+    assert!(partial.is_empty());
+    let r = step(&mut partial, &mut overflow, 4, Ok(&[6]));
+    assert!(matches!(r, Ok(ReadStep::Consume(1))));
+    assert!(partial.is_empty());
+
+    // Other errors reset the message
+    let e = Error::new(ErrorKind::BrokenPipe, "stall");
+    assert_eq!(
+        step(&mut partial, &mut overflow, 4, Err(e))
+            .err()
+            .unwrap()
+            .kind(),
+        ErrorKind::BrokenPipe
+    );
+    assert!(!overflow);
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use std::{future::Future, pin::pin, task::Waker};
+
+    use super::*;
+    use crate::{
+        platform::fake::FakeDevice,
+        transfer::{Bulk, In, Out},
+    };
+
+    #[test]
+    fn read_messages() {
+        let fake = FakeDevice::new();
+        let mut messages = fake
+            .interface()
+            .endpoint::<Bulk, In>(0x81)
+            .unwrap()
+            .reader(64)
+            .with_num_transfers(1)
+            .into_messages(100);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                fake.complete_in(0x81, &[1; 64]);
+                fake.complete_in(0x81, &[2; 10]);
+                fake.complete_in(0x81, &[3; 64]);
+                fake.complete_in(0x81, &[]);
+                fake.complete_in(0x81, &[4; 64]);
+                fake.complete_in(0x81, &[4; 64]);
+                fake.complete_in(0x81, &[5]);
+            });
+            let message = messages.read_message().unwrap();
+            assert_eq!(message, [[1; 64].as_slice(), &[2; 10]].concat());
+            assert_eq!(messages.read_message().unwrap(), [3; 64]);
+            let e = messages.read_message().unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+        });
+    }
+
+    #[test]
+    fn write_message_async_cancel() {
+        let fake = FakeDevice::new();
+        let mut messages = fake
+            .interface()
+            .endpoint::<Bulk, Out>(0x01)
+            .unwrap()
+            .writer(64)
+            .with_num_transfers(1)
+            .into_messages(100);
+
+        // The first transfer fills, and the rest of the message has to wait
+        // for it to complete
+        {
+            let fut = pin!(messages.write_message_async(&[1; 100]));
+            let mut cx = Context::from_waker(Waker::noop());
+            assert!(fut.poll(&mut cx).is_pending());
+        }
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                futures_lite::future::block_on(messages.write_message_async(&[2; 10])).unwrap();
+            });
+            assert_eq!(fake.complete_out(0x01), [1; 64]);
+            assert_eq!(fake.complete_out(0x01), [1; 36]);
+            assert_eq!(fake.complete_out(0x01), [2; 10]);
+        });
+    }
+
+    #[test]
+    fn stream_reports_truncated_message() {
+        use futures_core::Stream;
+
+        let fake = FakeDevice::new();
+        let mut messages = fake
+            .interface()
+            .endpoint::<Bulk, In>(0x81)
+            .unwrap()
+            .reader(64)
+            .with_num_transfers(1)
+            .into_messages(100);
+        // The reader ends after a full packet, without a short packet
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(Pin::new(&mut messages).poll_next(&mut cx).is_pending());
+        messages.get_mut().set_num_transfers(0);
+        fake.complete_in(0x81, &[1; 64]);
+
+        let mut next =
+            || futures_lite::future::block_on(poll_fn(|cx| Pin::new(&mut messages).poll_next(cx)));
+        let e = next().unwrap().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+        assert!(next().is_none());
+    }
+}
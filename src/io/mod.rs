@@ -2,7 +2,9 @@
 //! and Interrupt endpoints.
 //!
 //! These types wrap an [`Endpoint`](crate::Endpoint) and manage transfers to
-//! provide a higher-level buffered API.
+//! provide a higher-level buffered API. [`EndpointStream`] and [`EndpointSink`]
+//! instead expose whole transfer buffers through the `futures` `Stream` and
+//! `Sink` traits.
 //!
 //! ## Examples
 //!
@@ -34,8 +36,48 @@
 //! rx_pkt.read_to_end(&mut v).unwrap();
 //! rx_pkt.consume_end().unwrap();
 //! ```
+//!
+//! ### Messages
+//!
+//! For protocols where each message ends with a short packet,
+//! [`MessageRead`] and [`MessageWrite`] handle the framing and zero-length
+//! packets:
+//!
+//! ```no_run
+//! use nusb::{self, MaybeFuture, transfer::{Bulk, In, Out}};
+//! # let device_info = nusb::list_devices().wait().unwrap().next().unwrap();
+//! # let device = device_info.open().wait().unwrap();
+//! # let interface = device.claim_interface(0).wait().unwrap();
+//! let mut tx = interface.endpoint::<Bulk, Out>(0x01).unwrap()
+//!     .writer(512)
+//!     .into_messages(4096);
+//! let mut rx = interface.endpoint::<Bulk, In>(0x81).unwrap()
+//!     .reader(512)
+//!     .into_messages(4096);
+//!
+//! tx.write_message(&[0x01, 0x02, 0x03]).unwrap();
+//! tx.flush().unwrap();
+//! let response: Vec<u8> = rx.read_message().unwrap();
+//! ```
 mod read;
 pub use read::*;
 
 mod write;
 pub use write::*;
+
+mod message;
+pub use message::*;
+
+mod stream;
+pub use stream::*;
+
+mod recovery;
+pub use recovery::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod timeout;
+#[cfg(not(target_arch = "wasm32"))]
+use timeout::PollTimeout;
+
+mod pair;
+pub use pair::*;
@@ -0,0 +1,560 @@
+use std::{
+    io::Error,
+    task::{ready, Context, Poll},
+};
+
+#[cfg(any(feature = "tokio", feature = "smol"))]
+use std::{io::IoSlice, pin::Pin};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    future::{poll_fn, Future},
+    io::{ErrorKind, Read, Write},
+    pin::pin,
+    time::{Duration, Instant},
+};
+
+use crate::transfer::BulkOrInterrupt;
+
+use super::{EndpointRead, EndpointWrite};
+
+/// How long a transaction waits for the cancelled transfers of an earlier,
+/// interrupted transaction to be returned.
+#[cfg(not(target_arch = "wasm32"))]
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// An [`EndpointWrite`] and [`EndpointRead`] used together for protocols
+/// that send a request on an OUT endpoint and receive the response on an IN
+/// endpoint.
+///
+/// [`transact`](Self::transact) and [`transact_async`](Self::transact_async)
+/// perform one exchange: the request is terminated with a short or
+/// zero-length packet, and the response is read until a short or
+/// zero-length packet.
+///
+/// `EndpointPair` also implements the IO traits of both halves, so it can be
+/// used as a single duplex stream, for example by codec frameworks that
+/// expect one object implementing both `AsyncRead` and `AsyncWrite`.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use nusb::{io::EndpointPair, transfer::{Bulk, In, Out}, MaybeFuture};
+/// # let device_info = nusb::list_devices().wait().unwrap().next().unwrap();
+/// # let device = device_info.open().wait().unwrap();
+/// # let interface = device.claim_interface(0).wait().unwrap();
+/// let tx = interface.endpoint::<Bulk, Out>(0x01).unwrap().writer(512);
+/// let rx = interface.endpoint::<Bulk, In>(0x81).unwrap().reader(512);
+/// let mut pair = EndpointPair::new(tx, rx);
+///
+/// let response = pair.transact(&[0x01, 0x02, 0x03], Duration::from_secs(1)).unwrap();
+/// ```
+pub struct EndpointPair<EpType: BulkOrInterrupt> {
+    write: EndpointWrite<EpType>,
+    read: EndpointRead<EpType>,
+
+    /// A transaction was cancelled, and its transfers must be drained before
+    /// the next one. Holds the reader's number of transfers to restore.
+    interrupted: Option<usize>,
+}
+
+impl<EpType: BulkOrInterrupt> EndpointPair<EpType> {
+    /// Create a new `EndpointPair` from the OUT and IN halves.
+    pub fn new(write: EndpointWrite<EpType>, read: EndpointRead<EpType>) -> Self {
+        Self {
+            write,
+            read,
+            interrupted: None,
+        }
+    }
+
+    /// Get a reference to the [`EndpointWrite`] half.
+    pub fn writer(&self) -> &EndpointWrite<EpType> {
+        &self.write
+    }
+
+    /// Get a mutable reference to the [`EndpointWrite`] half.
+    pub fn writer_mut(&mut self) -> &mut EndpointWrite<EpType> {
+        &mut self.write
+    }
+
+    /// Get a reference to the [`EndpointRead`] half.
+    pub fn reader(&self) -> &EndpointRead<EpType> {
+        &self.read
+    }
+
+    /// Get a mutable reference to the [`EndpointRead`] half.
+    pub fn reader_mut(&mut self) -> &mut EndpointRead<EpType> {
+        &mut self.read
+    }
+
+    /// Destroy this `EndpointPair` and return the two halves.
+    pub fn into_inner(self) -> (EndpointWrite<EpType>, EndpointRead<EpType>) {
+        (self.write, self.read)
+    }
+
+    /// Send a request and wait for the response, blocking.
+    ///
+    /// Any data already received on the IN endpoint is discarded first, so
+    /// that a late response to an earlier request is not mistaken for the
+    /// response to this one. The request is then written and terminated with
+    /// a short or zero-length packet, and the response is read until a short
+    /// or zero-length packet.
+    ///
+    /// If `timeout` elapses before the response is complete, the pending
+    /// transfers on both endpoints are cancelled and returned before an
+    /// error of kind [`ErrorKind::TimedOut`] is returned, so the pair can be
+    /// used for another transaction. If the host controller does not return
+    /// them within a second, the next transaction waits for them again, and
+    /// fails with [`ErrorKind::Other`] if they are still missing.
+    ///
+    /// The read and write timeouts of the halves are not used.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn transact(&mut self, request: &[u8], timeout: Duration) -> Result<Vec<u8>, Error> {
+        self.drain_interrupted()?;
+        let deadline = Instant::now().checked_add(timeout);
+
+        let mut t = Transaction::new(self);
+        t.pair.read.discard_ready();
+        let res = t.pair.exchange(request, deadline);
+        t.complete = !is_timeout(&res);
+        drop(t);
+
+        // If this fails, it is retried before the next transaction
+        let _ = self.drain_interrupted();
+        res
+    }
+
+    /// Wait for the transfers of a cancelled transaction to be returned.
+    ///
+    /// Fails if they are not returned within [`DRAIN_TIMEOUT`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn drain_interrupted(&mut self) -> Result<(), Error> {
+        if let Some(num_transfers) = self.interrupted {
+            let deadline = Instant::now() + DRAIN_TIMEOUT;
+            if !self.write.drain(deadline) || !self.read.drain(deadline) {
+                log::warn!("Cancelled transfers of an interrupted transaction were not returned");
+                return Err(Error::other(
+                    "cancelled transfers were not returned in time",
+                ));
+            }
+            self.read.set_num_transfers(num_transfers);
+            self.interrupted = None;
+        }
+        Ok(())
+    }
+
+    /// Async version of [Self::drain_interrupted].
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn drain_interrupted_async(&mut self) {
+        if let Some(num_transfers) = self.interrupted {
+            poll_fn(|cx| {
+                ready!(self.write.poll_drain(cx));
+                self.read.poll_drain(cx)
+            })
+            .await;
+            self.read.set_num_transfers(num_transfers);
+            self.interrupted = None;
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn exchange(&mut self, request: &[u8], deadline: Option<Instant>) -> Result<Vec<u8>, Error> {
+        let remaining = || deadline.map_or(Duration::MAX, |d| d - Instant::now().min(d));
+
+        self.write.set_write_timeout(remaining());
+        self.write.write_all(request)?;
+        self.write.set_write_timeout(remaining());
+        self.write.flush_end()?;
+
+        let mut response = Vec::new();
+        loop {
+            self.read.set_read_timeout(remaining());
+            let data = self.read.fill_buf_until_short()?;
+            if data.is_empty() {
+                self.read
+                    .until_short_packet()
+                    .consume_end()
+                    .expect("reader is at end of message");
+                return Ok(response);
+            }
+            response.extend_from_slice(data);
+            let len = data.len();
+            self.read.consume(len);
+        }
+    }
+
+    /// Send a request and wait for the response.
+    ///
+    /// Async version of [`transact`](Self::transact). The timeout does not
+    /// depend on an async runtime, and the read and write timeouts of the
+    /// halves are not used.
+    ///
+    /// If the returned future is dropped before it completes, the pending
+    /// transfers on both endpoints are cancelled as if the timeout had
+    /// elapsed. The next transaction waits for them to be returned before
+    /// sending its request. Until then, the reader behaves as after
+    /// [`EndpointRead::cancel_all`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn transact_async(
+        &mut self,
+        request: &[u8],
+        timeout: Duration,
+    ) -> Result<Vec<u8>, Error> {
+        self.drain_interrupted_async().await;
+
+        let mut t = Transaction::new(self);
+        t.pair.write.write_timeout = Duration::MAX;
+        t.pair.read.read_timeout = Duration::MAX;
+        t.pair.read.discard_ready();
+
+        let mut delay = pin!(t.pair.read.delay(timeout));
+        let res = {
+            let mut exchange = pin!(t.pair.exchange_async(request));
+            poll_fn(|cx| {
+                if let Poll::Ready(res) = exchange.as_mut().poll(cx) {
+                    return Poll::Ready(res);
+                }
+                ready!(delay.as_mut().poll(cx));
+                Poll::Ready(Err(Error::new(
+                    ErrorKind::TimedOut,
+                    "timeout waiting for response",
+                )))
+            })
+            .await
+        };
+        t.complete = !is_timeout(&res);
+        drop(t);
+
+        self.drain_interrupted_async().await;
+        res
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn exchange_async(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut src = request;
+        while !src.is_empty() {
+            let len = poll_fn(|cx| self.write.poll_write(cx, src)).await?;
+            src = &src[len..];
+        }
+        self.write.flush_end_async().await?;
+
+        let mut response = Vec::new();
+        poll_fn(|cx| self.poll_read_response(cx, &mut response)).await?;
+        Ok(response)
+    }
+
+    fn poll_read_response(
+        &mut self,
+        cx: &mut Context<'_>,
+        response: &mut Vec<u8>,
+    ) -> Poll<Result<(), Error>> {
+        loop {
+            let data = ready!(self.read.poll_fill_buf_until_short(cx))?;
+            if data.is_empty() {
+                self.read
+                    .until_short_packet()
+                    .consume_end()
+                    .expect("reader is at end of message");
+                return Poll::Ready(Ok(()));
+            }
+            response.extend_from_slice(data);
+            let len = data.len();
+            self.read.consume(len);
+        }
+    }
+}
+
+/// A transaction in progress, which restores the halves' timeouts when it
+/// ends, and cancels the pending transfers if it ends without a response
+/// because of a timeout or because the future was dropped.
+#[cfg(not(target_arch = "wasm32"))]
+struct Transaction<'a, EpType: BulkOrInterrupt> {
+    pair: &'a mut EndpointPair<EpType>,
+    write_timeout: Duration,
+    read_timeout: Duration,
+    num_transfers: usize,
+    complete: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a, EpType: BulkOrInterrupt> Transaction<'a, EpType> {
+    fn new(pair: &'a mut EndpointPair<EpType>) -> Self {
+        Transaction {
+            write_timeout: pair.write.write_timeout,
+            read_timeout: pair.read.read_timeout,
+            num_transfers: pair.read.num_transfers(),
+            pair,
+            complete: false,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<EpType: BulkOrInterrupt> Drop for Transaction<'_, EpType> {
+    fn drop(&mut self) {
+        self.pair.write.write_timeout = self.write_timeout;
+        self.pair.read.read_timeout = self.read_timeout;
+        if !self.complete {
+            self.pair.write.cancel_all();
+            self.pair.read.cancel_all();
+            self.pair.interrupted = Some(self.num_transfers);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_timeout<T>(res: &Result<T, Error>) -> bool {
+    res.as_ref().is_err_and(|e| e.kind() == ErrorKind::TimedOut)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<EpType: BulkOrInterrupt> Read for EndpointPair<EpType> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.read.read(buf)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<EpType: BulkOrInterrupt> Write for EndpointPair<EpType> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.write.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> Result<usize, Error> {
+        self.write.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.write.flush()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<EpType: BulkOrInterrupt> tokio::io::AsyncRead for EndpointPair<EpType> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<Result<(), Error>> {
+        Pin::new(&mut Pin::into_inner(self).read).poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<EpType: BulkOrInterrupt> tokio::io::AsyncWrite for EndpointPair<EpType> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Error>> {
+        Pin::new(&mut Pin::into_inner(self).write).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, Error>> {
+        Pin::new(&mut Pin::into_inner(self).write).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut Pin::into_inner(self).write).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut Pin::into_inner(self).write).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "smol")]
+impl<EpType: BulkOrInterrupt> futures_io::AsyncRead for EndpointPair<EpType> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Error>> {
+        Pin::new(&mut Pin::into_inner(self).read).poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "smol")]
+impl<EpType: BulkOrInterrupt> futures_io::AsyncWrite for EndpointPair<EpType> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Error>> {
+        Pin::new(&mut Pin::into_inner(self).write).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, Error>> {
+        Pin::new(&mut Pin::into_inner(self).write).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut Pin::into_inner(self).write).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut Pin::into_inner(self).write).poll_close(cx)
+    }
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use std::task::Waker;
+
+    use futures_lite::future::block_on;
+    use rustix::io::Errno;
+
+    use super::*;
+    use crate::{
+        platform::fake::FakeDevice,
+        transfer::{Bulk, In, Interrupt, Out},
+        Interface,
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn pair(interface: &Interface) -> EndpointPair<Bulk> {
+        let tx = interface.endpoint::<Bulk, Out>(0x01).unwrap().writer(64);
+        let rx = interface.endpoint::<Bulk, In>(0x81).unwrap().reader(64);
+        EndpointPair::new(tx, rx.with_num_transfers(2))
+    }
+
+    /// Answer a request with `response`, returning the request.
+    fn respond(fake: &FakeDevice, response: &[u8]) -> Vec<u8> {
+        let request = fake.complete_out(0x01);
+        fake.complete_in(0x81, response);
+        request
+    }
+
+    #[test]
+    fn transact_discards_stale_data() {
+        let fake = FakeDevice::new();
+        let interface = fake.interface();
+        let mut pair = pair(&interface);
+
+        std::thread::scope(|s| {
+            let device = s.spawn(|| respond(&fake, &[1]));
+            assert_eq!(pair.transact(&[10, 11], TIMEOUT).unwrap(), &[1]);
+            assert_eq!(device.join().unwrap(), &[10, 11]);
+        });
+
+        // A late response arrives on the other pending transfer. Once a
+        // transfer completed after it is returned, the event thread has
+        // handled it too.
+        fake.complete_in(0x81, &[2]);
+        let mut sync = interface.endpoint::<Interrupt, In>(0x82).unwrap();
+        sync.submit(sync.allocate(64));
+        fake.complete_in(0x82, &[]);
+        sync.wait_next_complete(TIMEOUT).unwrap();
+
+        std::thread::scope(|s| {
+            s.spawn(|| respond(&fake, &[3]));
+            assert_eq!(pair.transact(&[12], TIMEOUT).unwrap(), &[3]);
+        });
+    }
+
+    #[test]
+    fn transact_timeout_restores_reader() {
+        let fake = FakeDevice::new();
+        let interface = fake.interface();
+        let mut pair = pair(&interface);
+        pair.reader_mut().set_read_timeout(Duration::from_secs(7));
+
+        std::thread::scope(|s| {
+            s.spawn(|| fake.complete_out(0x01));
+            let err = pair.transact(&[10], Duration::from_millis(50)).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::TimedOut);
+        });
+        // The cancelled transfers were returned, and one of the reader's two
+        // transfers is submitted again by `set_num_transfers`
+        assert_eq!(fake.submitted(0x81), 1);
+        assert_eq!(pair.read.num_transfers(), 2);
+        assert_eq!(pair.read.read_timeout, Duration::from_secs(7));
+
+        std::thread::scope(|s| {
+            s.spawn(|| respond(&fake, &[1]));
+            assert_eq!(pair.transact(&[11], TIMEOUT).unwrap(), &[1]);
+        });
+    }
+
+    #[test]
+    fn transact_async_timeout_without_runtime() {
+        let fake = FakeDevice::new();
+        let interface = fake.interface();
+        let mut pair = pair(&interface);
+
+        std::thread::scope(|s| {
+            s.spawn(|| fake.complete_out(0x01));
+            let res = block_on(pair.transact_async(&[10], Duration::from_millis(50)));
+            assert_eq!(res.unwrap_err().kind(), ErrorKind::TimedOut);
+        });
+        // The cancelled transfers were returned, and one of the reader's two
+        // transfers is submitted again by `set_num_transfers`
+        assert_eq!(fake.submitted(0x81), 1);
+        assert_eq!(pair.read.num_transfers(), 2);
+        assert!(pair.interrupted.is_none());
+    }
+
+    #[test]
+    fn transact_async_dropped() {
+        let fake = FakeDevice::new();
+        let interface = fake.interface();
+        let mut pair = pair(&interface);
+        pair.reader_mut().set_read_timeout(Duration::from_secs(7));
+
+        {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut transact = pin!(pair.transact_async(&[10], TIMEOUT));
+            assert!(transact.as_mut().poll(&mut cx).is_pending());
+        }
+        assert_eq!(fake.submitted(0x01), 0);
+        assert_eq!(pair.read.read_timeout, Duration::from_secs(7));
+        assert_eq!(pair.interrupted, Some(2));
+
+        std::thread::scope(|s| {
+            let device = s.spawn(|| respond(&fake, &[1]));
+            let res = block_on(pair.transact_async(&[11], TIMEOUT));
+            assert_eq!(res.unwrap(), &[1]);
+            assert_eq!(device.join().unwrap(), &[11]);
+        });
+        assert_eq!(pair.read.num_transfers(), 2);
+    }
+
+    #[test]
+    fn transact_gives_up_on_cancelled_transfers() {
+        let fake = FakeDevice::new();
+        let interface = fake.interface();
+        let mut pair = pair(&interface);
+
+        fake.hold_discards(true);
+        std::thread::scope(|s| {
+            s.spawn(|| fake.complete_out(0x01));
+            let start = Instant::now();
+            let res = pair.transact(&[10], Duration::from_millis(50));
+            assert_eq!(res.unwrap_err().kind(), ErrorKind::TimedOut);
+            assert!(start.elapsed() < TIMEOUT);
+        });
+        assert_eq!(pair.interrupted, Some(2));
+
+        // Once the transfers are returned, the next transaction proceeds
+        fake.fail(0x81, Errno::NOENT);
+        fake.fail(0x81, Errno::NOENT);
+        std::thread::scope(|s| {
+            s.spawn(|| respond(&fake, &[1]));
+            assert_eq!(pair.transact(&[11], TIMEOUT).unwrap(), &[1]);
+        });
+        assert!(pair.interrupted.is_none());
+    }
+}
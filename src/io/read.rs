@@ -1,20 +1,22 @@
 use std::{
     error::Error,
     io::{BufRead, Read},
+    task::{ready, Context, Poll, Waker},
     time::Duration,
 };
 
 #[cfg(any(feature = "tokio", feature = "smol"))]
-use std::{
-    pin::Pin,
-    task::{ready, Context, Poll},
-};
+use std::pin::Pin;
 
 use crate::{
-    transfer::{Buffer, BulkOrInterrupt, In, TransferError},
+    transfer::{Buffer, BufferPool, BulkOrInterrupt, Completion, In, TransferError},
     Endpoint,
 };
 
+use super::MessageRead;
+#[cfg(not(target_arch = "wasm32"))]
+use super::{PollTimeout, Recovery, RecoveryPolicy, RecoveryStats};
+
 /// Wrapper for a Bulk or Interrupt IN [`Endpoint`](crate::Endpoint) that
 /// manages transfers to provide a higher-level buffered API.
 ///
@@ -43,16 +45,43 @@ pub struct EndpointRead<EpType: BulkOrInterrupt> {
     reading: Option<ReadBuffer>,
     num_transfers: usize,
     transfer_size: usize,
-    read_timeout: Duration,
+    pub(super) read_timeout: Duration,
+    pool: Option<BufferPool>,
+    #[cfg(not(target_arch = "wasm32"))]
+    recovery: Recovery,
+    #[cfg(not(target_arch = "wasm32"))]
+    poll_timeout: PollTimeout,
 }
 
 struct ReadBuffer {
     pos: usize,
     buf: Buffer,
     status: Result<(), TransferError>,
+
+    /// Tag of the completed transfer, kept when the buffer is resubmitted.
+    tag: u64,
 }
 
 impl ReadBuffer {
+    fn completed(c: Completion) -> Self {
+        ReadBuffer {
+            pos: 0,
+            buf: c.buffer,
+            status: c.status,
+            tag: c.tag,
+        }
+    }
+
+    /// A buffer reporting an error from recovery, with no transfer to re-use.
+    fn failed(error: TransferError) -> Self {
+        ReadBuffer {
+            pos: 0,
+            buf: Buffer::new(0),
+            status: Err(error),
+            tag: 0,
+        }
+    }
+
     #[inline]
     fn error(&self) -> Option<TransferError> {
         self.status.err().filter(|e| *e != TransferError::Cancelled)
@@ -101,10 +130,12 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
     ///
     /// The `transfer_size` parameter is the size of the buffer passed to the OS
     /// for each transfer. It will be rounded up to the next multiple of the
-    /// endpoint's max packet size.
+    /// endpoint's max packet size, and limited to the OS's
+    /// [maximum transfer size](crate::Capabilities::max_transfer_size).
     pub fn new(endpoint: Endpoint<EpType, In>, transfer_size: usize) -> Self {
-        let packet_size = endpoint.max_packet_size();
-        let transfer_size = (transfer_size.div_ceil(packet_size)).max(1) * packet_size;
+        let transfer_size = endpoint
+            .device_capabilities()
+            .transfer_size(transfer_size, endpoint.max_packet_size());
 
         Self {
             endpoint,
@@ -112,6 +143,11 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
             num_transfers: 1,
             transfer_size,
             read_timeout: Duration::MAX,
+            pool: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            recovery: Recovery::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            poll_timeout: PollTimeout::new(),
         }
     }
 
@@ -136,7 +172,7 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
         // Leave the last transfer to be submitted by `read` such that
         // a value of `1` only has transfers pending within `read` calls.
         while self.endpoint.pending() < num_transfers.saturating_sub(1) {
-            let buf = self.endpoint.allocate(self.transfer_size);
+            let buf = self.allocate();
             self.endpoint.submit(buf);
         }
     }
@@ -149,18 +185,23 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
         self
     }
 
-    /// Set the timeout for waiting for a transfer in the blocking `read` APIs.
+    /// Set the timeout for waiting for a transfer.
     ///
-    /// This affects the `std::io::Read` and `std::io::BufRead` implementations
-    /// only, and not the async trait implementations.
+    /// When a timeout occurs in the blocking `std::io::Read` and
+    /// `std::io::BufRead` implementations, the call fails but the transfer is
+    /// not cancelled and may complete later if the read is retried.
     ///
-    /// When a timeout occurs, the call fails but the transfer is not cancelled
-    /// and may complete later if the read is retried.
+    /// When a timeout occurs in the async trait implementations, all pending
+    /// transfers are cancelled before the call fails with
+    /// [`TimedOut`](std::io::ErrorKind::TimedOut). Data received by those
+    /// transfers before they were cancelled is kept and returned by the next
+    /// read, which also submits new transfers. The async timeout does not
+    /// depend on the async runtime.
     pub fn set_read_timeout(&mut self, timeout: Duration) {
         self.read_timeout = timeout;
     }
 
-    /// Set the timeout for an individual transfer for the blocking `read` APIs.
+    /// Set the timeout for waiting for a transfer.
     ///
     /// See [Self::set_read_timeout] -- this is for method chaining with `EndpointRead::new()`.
     pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
@@ -168,6 +209,32 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
         self
     }
 
+    /// Set the policy for handling failed transfers.
+    ///
+    /// By default, an error is returned from every read after a transfer
+    /// fails. See [`RecoveryPolicy`] for options to clear stalls, retry
+    /// faults, and continue after errors.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_recovery_policy(&mut self, policy: RecoveryPolicy) {
+        self.recovery.policy = policy;
+    }
+
+    /// Set the policy for handling failed transfers.
+    ///
+    /// See [Self::set_recovery_policy] (this version is for method chaining).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_recovery_policy(mut self, policy: RecoveryPolicy) -> Self {
+        self.set_recovery_policy(policy);
+        self
+    }
+
+    /// Get the counts of failed transfers handled by the
+    /// [recovery policy](Self::set_recovery_policy) or returned as errors.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn recovery_stats(&self) -> RecoveryStats {
+        self.recovery.stats
+    }
+
     /// Cancel all pending transfers.
     ///
     /// This sets [`num_transfers`](Self::set_num_transfers) to 0, so no further
@@ -182,6 +249,11 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
         self.endpoint.cancel_all();
     }
 
+    /// Get a reference to the underlying [`Endpoint`].
+    pub fn get_ref(&self) -> &Endpoint<EpType, In> {
+        &self.endpoint
+    }
+
     /// Destroy this `EndpointRead` and return the underlying [`Endpoint`].
     ///
     /// Any pending transfers are not cancelled.
@@ -189,6 +261,43 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
         self.endpoint
     }
 
+    /// Allocate transfer buffers from a [`BufferPool`] instead of the
+    /// endpoint.
+    ///
+    /// The pool is only used if its buffer size is at least the transfer
+    /// size. Buffers return to the pool when the `EndpointRead` is dropped.
+    pub fn set_buffer_pool(&mut self, pool: BufferPool) {
+        self.pool = Some(pool);
+    }
+
+    /// Allocate transfer buffers from a [`BufferPool`].
+    ///
+    /// See [Self::set_buffer_pool] (this version is for method chaining).
+    pub fn with_buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.set_buffer_pool(pool);
+        self
+    }
+
+    fn allocate(&self) -> Buffer {
+        match &self.pool {
+            Some(pool) if pool.buffer_size() >= self.transfer_size => {
+                let mut buf = pool.get();
+                buf.set_requested_len(self.transfer_size);
+                buf
+            }
+            _ => self.endpoint.allocate(self.transfer_size),
+        }
+    }
+
+    /// Convert this `EndpointRead` into a [`MessageRead`] that yields whole
+    /// messages delimited by short or zero-length packets.
+    ///
+    /// Messages longer than `max_message_size` are discarded and reported as
+    /// an error.
+    pub fn into_messages(self, max_message_size: usize) -> MessageRead<EpType> {
+        MessageRead::new(self, max_message_size)
+    }
+
     /// Get an [`EndpointReadUntilShortPacket`] adapter that will read only until
     /// the end of a short or zero-length packet.
     ///
@@ -200,6 +309,70 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
         EndpointReadUntilShortPacket { reader: self }
     }
 
+    /// Discard buffered data, including the data of transfers that have
+    /// already completed, without waiting for pending transfers.
+    pub(super) fn discard_ready(&mut self) {
+        if let Some(c) = self.reading.as_mut() {
+            c.status = Ok(());
+            c.clear_short_packet();
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        while let Some(c) = self.poll_timeout.take_kept() {
+            log::debug!("Discarding {} bytes of stale data", c.buffer.len());
+        }
+
+        let mut cx = Context::from_waker(Waker::noop());
+        for _ in 0..self.endpoint.pending() {
+            let Poll::Ready(c) = self.endpoint.poll_next_complete(&mut cx) else {
+                break;
+            };
+            log::debug!("Discarding {} bytes of stale data", c.buffer.len());
+            self.resubmit();
+            let mut stale = ReadBuffer::completed(c);
+            stale.status = Ok(());
+            stale.clear_short_packet();
+            self.reading = Some(stale);
+        }
+    }
+
+    /// Create a future that completes after `timeout`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(super) fn delay(&self, timeout: Duration) -> crate::timer::Delay {
+        self.endpoint.delay(timeout)
+    }
+
+    /// Get the configured number of concurrent transfers.
+    pub(super) fn num_transfers(&self) -> usize {
+        self.num_transfers
+    }
+
+    /// Wait for cancelled transfers to be returned, discarding their data.
+    ///
+    /// Returns `false` if some are still pending at `deadline`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(super) fn drain(&mut self, deadline: std::time::Instant) -> bool {
+        self.reading = None;
+        while self.endpoint.pending() > 0 {
+            let timeout = deadline.saturating_duration_since(std::time::Instant::now());
+            if self.endpoint.wait_next_complete(timeout).is_none() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Wait for cancelled transfers to be returned, discarding their data.
+    ///
+    /// Async version of [Self::drain].
+    pub(super) fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        while self.endpoint.pending() > 0 {
+            ready!(self.endpoint.poll_next_complete(cx));
+        }
+        self.reading = None;
+        Poll::Ready(())
+    }
+
     #[inline]
     fn has_data(&self) -> bool {
         self.reading.as_ref().is_some_and(|r| r.has_remaining())
@@ -215,7 +388,9 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
     fn resubmit(&mut self) {
         if let Some(c) = self.reading.take() {
             debug_assert!(!c.has_remaining());
-            self.endpoint.submit(c.buf);
+            if c.buf.requested_len() > 0 {
+                self.endpoint.submit_tagged(c.buf, c.tag);
+            }
         }
     }
 
@@ -225,7 +400,7 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
             self.resubmit();
             while self.endpoint.pending() < self.num_transfers {
                 // Allocate more buffers for any remaining transfers
-                let buf = self.endpoint.allocate(self.transfer_size);
+                let buf = self.allocate();
                 self.endpoint.submit(buf);
             }
         }
@@ -239,8 +414,25 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
         self.reading.as_ref().unwrap().remaining()
     }
 
+    /// Clear an error that is about to be returned by
+    /// [`remaining`](Self::remaining), if the recovery policy continues after
+    /// it, so that it is only returned once.
     #[inline]
-    fn consume(&mut self, len: usize) {
+    fn take_error(&mut self) -> Option<std::io::Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(c) = self.reading.as_mut() {
+            let e = c.error().filter(|_| c.pos >= c.buf.len())?;
+            if self.recovery.continue_after(e) {
+                c.status = Ok(());
+                c.clear_short_packet();
+                return Some(e.into());
+            }
+        }
+        None
+    }
+
+    #[inline]
+    pub(super) fn consume(&mut self, len: usize) {
         if let Some(ref mut c) = self.reading {
             c.consume(len);
         } else {
@@ -250,35 +442,99 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
 
     #[cfg(not(target_arch = "wasm32"))]
     fn wait(&mut self) -> Result<bool, std::io::Error> {
-        if self.start_read() {
-            let c = self.endpoint.wait_next_complete(self.read_timeout);
-            let c = c.ok_or(std::io::Error::new(
-                std::io::ErrorKind::TimedOut,
-                "timeout waiting for read",
-            ))?;
-            self.reading = Some(ReadBuffer {
-                pos: 0,
-                buf: c.buffer,
-                status: c.status,
-            });
-            Ok(true)
-        } else {
-            Ok(false)
+        loop {
+            if let Err(e) = self.recovery.wait(&mut self.endpoint) {
+                self.reading = Some(ReadBuffer::failed(e));
+                return Ok(true);
+            }
+
+            let c = match self.poll_timeout.take_kept() {
+                Some(c) => c,
+                None => {
+                    if !self.start_read() {
+                        return Ok(false);
+                    }
+
+                    let c = self.endpoint.wait_next_complete(self.read_timeout);
+                    c.ok_or(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "timeout waiting for read",
+                    ))?
+                }
+            };
+
+            if let Some(c) = self.recovery.check(&mut self.endpoint, c) {
+                self.reading = Some(ReadBuffer::completed(c));
+                return Ok(true);
+            }
         }
     }
 
-    #[cfg(any(feature = "tokio", feature = "smol"))]
-    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<bool> {
-        if self.start_read() {
-            let c = ready!(self.endpoint.poll_next_complete(cx));
-            self.reading = Some(ReadBuffer {
-                pos: 0,
-                buf: c.buffer,
-                status: c.status,
-            });
-            Poll::Ready(true)
-        } else {
-            Poll::Ready(false)
+    #[cfg(not(target_arch = "wasm32"))]
+    #[inline]
+    pub(super) fn fill_buf_until_short(&mut self) -> Result<&[u8], std::io::Error> {
+        while !self.has_data_or_short_end() {
+            if !self.wait()? {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "ended without short packet",
+                ));
+            }
+        }
+        if let Some(e) = self.take_error() {
+            return Err(e);
+        }
+        self.remaining()
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<bool, std::io::Error>> {
+        loop {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Err(e) = ready!(self.recovery.poll(&mut self.endpoint, cx)) {
+                self.reading = Some(ReadBuffer::failed(e));
+                return Poll::Ready(Ok(true));
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            let c = match self.poll_timeout.take_kept() {
+                Some(c) => c,
+                None => {
+                    // Don't submit new transfers until the cancelled ones are
+                    // returned
+                    if !self.poll_timeout.is_draining() && !self.start_read() {
+                        return Poll::Ready(Ok(false));
+                    }
+
+                    let Some(c) = ready!(self.poll_timeout.poll_next_complete(
+                        &mut self.endpoint,
+                        self.read_timeout,
+                        cx
+                    )) else {
+                        self.reading = None;
+                        return Poll::Ready(Err(std::io::Error::new(
+                            std::io::ErrorKind::TimedOut,
+                            "timeout waiting for read",
+                        )));
+                    };
+                    c
+                }
+            };
+
+            #[cfg(target_arch = "wasm32")]
+            let c = {
+                if !self.start_read() {
+                    return Poll::Ready(Ok(false));
+                }
+                ready!(self.endpoint.poll_next_complete(cx))
+            };
+
+            #[cfg(not(target_arch = "wasm32"))]
+            let Some(c) = self.recovery.check(&mut self.endpoint, c) else {
+                continue;
+            };
+
+            self.reading = Some(ReadBuffer::completed(c));
+            return Poll::Ready(Ok(true));
         }
     }
 
@@ -286,27 +542,32 @@ impl<EpType: BulkOrInterrupt> EndpointRead<EpType> {
     #[inline]
     fn poll_fill_buf(&mut self, cx: &mut Context<'_>) -> Poll<Result<&[u8], std::io::Error>> {
         while !self.has_data() {
-            if !ready!(self.poll(cx)) {
+            if !ready!(self.poll(cx))? {
                 return Poll::Ready(Ok(&[]));
             }
         }
+        if let Some(e) = self.take_error() {
+            return Poll::Ready(Err(e));
+        }
         Poll::Ready(self.remaining())
     }
 
-    #[cfg(any(feature = "tokio", feature = "smol"))]
     #[inline]
-    fn poll_fill_buf_until_short(
+    pub(super) fn poll_fill_buf_until_short(
         &mut self,
         cx: &mut Context<'_>,
     ) -> Poll<Result<&[u8], std::io::Error>> {
         while !self.has_data_or_short_end() {
-            if !ready!(self.poll(cx)) {
+            if !ready!(self.poll(cx))? {
                 return Poll::Ready(Err(std::io::Error::new(
                     std::io::ErrorKind::UnexpectedEof,
                     "ended without short packet",
                 )));
             }
         }
+        if let Some(e) = self.take_error() {
+            return Poll::Ready(Err(e));
+        }
         Poll::Ready(self.remaining())
     }
 }
@@ -331,6 +592,9 @@ impl<EpType: BulkOrInterrupt> BufRead for EndpointRead<EpType> {
                 return Ok(&[]);
             }
         }
+        if let Some(e) = self.take_error() {
+            return Err(e);
+        }
         self.remaining()
     }
 
@@ -469,20 +733,12 @@ impl<EpType: BulkOrInterrupt> Read for EndpointReadUntilShortPacket<'_, EpType>
 impl<EpType: BulkOrInterrupt> BufRead for EndpointReadUntilShortPacket<'_, EpType> {
     #[inline]
     fn fill_buf(&mut self) -> Result<&[u8], std::io::Error> {
-        while !self.reader.has_data_or_short_end() {
-            if !self.reader.wait()? {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::UnexpectedEof,
-                    "ended without short packet",
-                ));
-            }
-        }
-        self.reader.remaining()
+        self.reader.fill_buf_until_short()
     }
 
     #[inline]
     fn consume(&mut self, len: usize) {
-        if self.reader.has_data_or_short_end() {
+        if !self.reader.has_data_or_short_end() {
             assert!(len == 0, "consumed more than available");
         } else {
             self.reader.consume(len);
@@ -550,3 +806,131 @@ impl<EpType: BulkOrInterrupt> futures_io::AsyncBufRead
         Pin::into_inner(self).reader.consume(amt);
     }
 }
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use std::{
+        io::{BufRead, Read},
+        time::Duration,
+    };
+
+    use rustix::io::Errno;
+
+    use crate::{
+        io::RecoveryPolicy,
+        platform::fake::FakeDevice,
+        transfer::{Bulk, In},
+    };
+
+    #[test]
+    fn until_short_packet_consume() {
+        let fake = FakeDevice::new();
+        let mut reader = fake
+            .interface()
+            .endpoint::<Bulk, In>(0x81)
+            .unwrap()
+            .reader(64)
+            .with_num_transfers(1);
+
+        let mut until_short = reader.until_short_packet();
+        std::thread::scope(|s| {
+            s.spawn(|| fake.complete_in(0x81, &[1, 2, 3]));
+            assert_eq!(until_short.fill_buf().unwrap(), &[1, 2, 3]);
+        });
+        until_short.consume(2);
+        assert_eq!(until_short.fill_buf().unwrap(), &[3]);
+        until_short.consume(1);
+        assert!(until_short.is_end());
+        assert!(until_short.fill_buf().unwrap().is_empty());
+        until_short.consume(0);
+        until_short.consume_end().unwrap();
+    }
+
+    #[test]
+    fn continue_after_error() {
+        let fake = FakeDevice::new();
+        let mut reader = fake
+            .interface()
+            .endpoint::<Bulk, In>(0x81)
+            .unwrap()
+            .reader(64)
+            .with_num_transfers(1)
+            .with_recovery_policy(RecoveryPolicy::new().continue_after_error(true));
+
+        let mut buf = [0; 64];
+        std::thread::scope(|s| {
+            s.spawn(|| fake.fail(0x81, Errno::PROTO));
+            let err = reader.read(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        });
+        std::thread::scope(|s| {
+            s.spawn(|| fake.complete_in(0x81, &[1, 2]));
+            assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        });
+        assert_eq!(reader.recovery_stats().errors, 1);
+    }
+
+    #[test]
+    fn resubmit_keeps_tag() {
+        let fake = FakeDevice::new();
+        let mut ep = fake.interface().endpoint::<Bulk, In>(0x81).unwrap();
+        ep.submit_tagged(ep.allocate(64), 5);
+        let mut reader = ep
+            .reader(64)
+            .with_num_transfers(1)
+            .with_read_timeout(Duration::from_millis(10));
+
+        let mut buf = [0; 64];
+        std::thread::scope(|s| {
+            s.spawn(|| fake.complete_in(0x81, &[1, 2]));
+            assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        });
+
+        // The buffer is submitted again with its tag before the read times out
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        let mut ep = reader.into_inner();
+        fake.complete_in(0x81, &[3]);
+        let c = ep.wait_next_complete(Duration::from_secs(5)).unwrap();
+        assert_eq!(c.tag, 5);
+    }
+
+    #[test]
+    fn async_timeout_keeps_received_data() {
+        use std::{
+            future::poll_fn,
+            task::{Context, Waker},
+        };
+
+        use futures_lite::future::block_on;
+
+        let fake = FakeDevice::new();
+        let mut reader = fake
+            .interface()
+            .endpoint::<Bulk, In>(0x81)
+            .unwrap()
+            .reader(64)
+            .with_num_transfers(2)
+            .with_read_timeout(Duration::from_millis(50));
+
+        // The timeout elapses and both transfers are cancelled, but one of
+        // them completes with data before the cancellation takes effect
+        fake.hold_discards(true);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(reader.poll_fill_buf_until_short(&mut cx).is_pending());
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(reader.poll_fill_buf_until_short(&mut cx).is_pending());
+        fake.complete_in(0x81, &[1, 2]);
+        fake.fail(0x81, Errno::NOENT);
+
+        let mut fill = || {
+            block_on(poll_fn(|cx| {
+                reader
+                    .poll_fill_buf_until_short(cx)
+                    .map(|r| r.map(<[u8]>::to_vec))
+            }))
+        };
+        assert_eq!(fill().unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+        assert_eq!(fill().unwrap(), [1, 2]);
+    }
+}
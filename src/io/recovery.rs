@@ -0,0 +1,530 @@
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Instant,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    maybe_future::{block_on, blocking::BlockingTask},
+    timer::Delay,
+    transfer::{Buffer, BulkOrInterrupt, Completion, Direction, EndpointDirection, TransferError},
+    Endpoint, Error, ErrorKind, MaybeFuture,
+};
+
+/// How long blocking recovery waits for cancelled transfers to be returned
+/// before giving up.
+#[cfg(not(target_arch = "wasm32"))]
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Policy for handling failed transfers in [`EndpointRead`](super::EndpointRead)
+/// and [`EndpointWrite`](super::EndpointWrite).
+///
+/// The default policy returns every error to the caller, which matches the
+/// behavior without a policy. Each option below handles some errors
+/// internally instead, so that long-running streams survive transient
+/// failures. The number of errors handled is available from
+/// `recovery_stats()` on the reader or writer.
+///
+/// ```
+/// use std::time::Duration;
+/// use nusb::io::RecoveryPolicy;
+///
+/// let policy = RecoveryPolicy::new()
+///     .clear_halt(true)
+///     .retry_faults(3, Duration::from_millis(10))
+///     .continue_after_error(true);
+/// ```
+///
+/// In async code, clearing a halt runs on a background thread, which
+/// requires the `tokio` or `smol` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecoveryPolicy {
+    clear_halt: bool,
+    fault_retries: u32,
+    fault_backoff: Duration,
+    continue_after_error: bool,
+}
+
+impl RecoveryPolicy {
+    /// Create a policy that returns all errors to the caller.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Automatically clear a [`Stall`](crate::transfer::TransferError::Stall)
+    /// and continue.
+    ///
+    /// When a transfer fails with a stall, the remaining pending transfers
+    /// are cancelled, [`Endpoint::clear_halt`](crate::Endpoint::clear_halt)
+    /// is called, and new transfers are submitted. The stall is not returned
+    /// to the caller unless clearing the halt fails.
+    ///
+    /// For [`EndpointWrite`](super::EndpointWrite), the data of the stalled
+    /// and cancelled transfers is discarded, since the device refused it.
+    pub fn clear_halt(mut self, enable: bool) -> Self {
+        self.clear_halt = enable;
+        self
+    }
+
+    /// Retry transfers that fail with
+    /// [`Fault`](crate::transfer::TransferError::Fault) up to `max_retries`
+    /// times in a row.
+    ///
+    /// The first retry waits for `backoff`, and the delay doubles with each
+    /// consecutive fault. A successful transfer resets the count. Once the
+    /// retries are exhausted, the fault is returned to the caller.
+    ///
+    /// For [`EndpointRead`](super::EndpointRead), any partial data received by
+    /// the failed transfer is discarded. For
+    /// [`EndpointWrite`](super::EndpointWrite), the pending transfers are
+    /// cancelled and the unsent data is submitted again in order. Transfers
+    /// submitted again keep their [`tag`](crate::transfer::Completion::tag).
+    pub fn retry_faults(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.fault_retries = max_retries;
+        self.fault_backoff = backoff;
+        self
+    }
+
+    /// Keep reading after returning an error.
+    ///
+    /// By default, once [`EndpointRead`](super::EndpointRead) returns an
+    /// error, it returns the same error from every subsequent read. With this
+    /// option, each error is returned once, and the next read continues with
+    /// the next transfer. Errors due to the device disconnecting are always
+    /// repeated.
+    ///
+    /// [`EndpointWrite`](super::EndpointWrite) always continues after
+    /// returning an error.
+    pub fn continue_after_error(mut self, enable: bool) -> Self {
+        self.continue_after_error = enable;
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.fault_backoff
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+    }
+}
+
+/// Counts of failed transfers seen by [`EndpointRead`](super::EndpointRead) or
+/// [`EndpointWrite`](super::EndpointWrite), as handled by its
+/// [`RecoveryPolicy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RecoveryStats {
+    /// Number of stalls cleared by [`RecoveryPolicy::clear_halt`].
+    pub stalls_cleared: u64,
+
+    /// Number of retries after a fault, by [`RecoveryPolicy::retry_faults`].
+    pub retries: u64,
+
+    /// Number of failed transfers whose error was returned to the caller.
+    pub errors: u64,
+}
+
+/// Recovery state shared by `EndpointRead` and `EndpointWrite`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct Recovery {
+    pub(crate) policy: RecoveryPolicy,
+    pub(crate) stats: RecoveryStats,
+    faults: u32,
+    state: State,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+enum State {
+    Idle,
+
+    /// Pending transfers were cancelled, waiting for them to be returned.
+    Draining(Recovering),
+
+    /// Clearing the halt or waiting out the backoff. The action is started
+    /// on the first poll.
+    Waiting(Recovering, Option<Action>),
+}
+
+/// Recovery action started by `poll`, which `wait` also finishes rather
+/// than starting it over.
+#[cfg(not(target_arch = "wasm32"))]
+enum Action {
+    Backoff(Delay),
+    ClearHalt(BlockingTask<Result<(), Error>>),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Future for Action {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::into_inner(self) {
+            Action::Backoff(delay) => Pin::new(delay).poll(cx).map(Ok),
+            Action::ClearHalt(task) => Pin::new(task).poll(cx),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Recovering {
+    error: TransferError,
+    delay: Option<Duration>,
+
+    /// Buffers to submit again, with the tags of the failed transfers.
+    resubmit: Vec<(Buffer, u64)>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Recovery {
+    pub(crate) fn new() -> Self {
+        Self {
+            policy: RecoveryPolicy::default(),
+            stats: RecoveryStats::default(),
+            faults: 0,
+            state: State::Idle,
+        }
+    }
+
+    /// Whether the error just returned to the caller should be cleared so
+    /// that reading can continue.
+    pub(crate) fn continue_after(&self, error: TransferError) -> bool {
+        self.policy.continue_after_error && error != TransferError::Disconnected
+    }
+
+    /// Check a completion against the policy.
+    ///
+    /// Returns the completion if it should be passed to the caller, or
+    /// `None` if recovery has started and must be driven with
+    /// [`wait`](Self::wait) or [`poll`](Self::poll).
+    pub(crate) fn check<EpType: BulkOrInterrupt, Dir: EndpointDirection>(
+        &mut self,
+        endpoint: &mut Endpoint<EpType, Dir>,
+        c: Completion,
+    ) -> Option<Completion> {
+        let error = match c.status {
+            Ok(()) => {
+                self.faults = 0;
+                return Some(c);
+            }
+            Err(TransferError::Stall) if self.policy.clear_halt => TransferError::Stall,
+            Err(TransferError::Fault) if self.faults < self.policy.fault_retries => {
+                TransferError::Fault
+            }
+            Err(e) => {
+                if e != TransferError::Cancelled {
+                    self.stats.errors += 1;
+                }
+                return Some(c);
+            }
+        };
+
+        let mut r = Recovering {
+            error,
+            delay: None,
+            resubmit: Vec::new(),
+        };
+
+        if error == TransferError::Stall {
+            log::debug!(
+                "Clearing stall on endpoint {:02x}",
+                endpoint.endpoint_address()
+            );
+            self.stats.stalls_cleared += 1;
+            endpoint.cancel_all();
+            self.state = State::Draining(r);
+        } else {
+            self.faults += 1;
+            self.stats.retries += 1;
+            let delay = self.policy.backoff(self.faults);
+            log::debug!(
+                "Retrying after fault on endpoint {:02x} in {delay:?} (attempt {})",
+                endpoint.endpoint_address(),
+                self.faults
+            );
+            r.delay = Some(delay);
+            r.resubmit.push(unsent::<Dir>(c));
+
+            // An IN transfer can be retried independently of the others, but
+            // OUT data must be sent again in order.
+            if Dir::DIR == Direction::Out {
+                endpoint.cancel_all();
+                self.state = State::Draining(r);
+            } else {
+                self.state = State::Waiting(r, None);
+            }
+        }
+        None
+    }
+
+    /// Complete recovery, blocking.
+    ///
+    /// If cancelled transfers are not returned within [`DRAIN_TIMEOUT`],
+    /// recovery fails with the original error.
+    pub(crate) fn wait<EpType: BulkOrInterrupt, Dir: EndpointDirection>(
+        &mut self,
+        endpoint: &mut Endpoint<EpType, Dir>,
+    ) -> Result<(), TransferError> {
+        let mut r = match mem::replace(&mut self.state, State::Idle) {
+            State::Idle => return Ok(()),
+            State::Draining(r) => r,
+            // Finish an action started by `poll` rather than starting it over
+            State::Waiting(r, Some(action)) => {
+                let res = block_on(action);
+                return self.finish(endpoint, r, res);
+            }
+            State::Waiting(r, None) => {
+                let res = Self::run_blocking(endpoint, &r);
+                return self.finish(endpoint, r, res);
+            }
+        };
+
+        let deadline = Instant::now() + DRAIN_TIMEOUT;
+        while endpoint.pending() > 0 {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let Some(c) = endpoint.wait_next_complete(timeout) else {
+                let e = Error::new(
+                    ErrorKind::Other,
+                    "cancelled transfers were not returned in time",
+                );
+                return self.finish(endpoint, r, Err(e));
+            };
+            r.drained::<Dir>(c);
+        }
+
+        let res = Self::run_blocking(endpoint, &r);
+        self.finish(endpoint, r, res)
+    }
+
+    fn run_blocking<EpType: BulkOrInterrupt, Dir: EndpointDirection>(
+        endpoint: &mut Endpoint<EpType, Dir>,
+        r: &Recovering,
+    ) -> Result<(), Error> {
+        match r.delay {
+            Some(delay) => {
+                std::thread::sleep(delay);
+                Ok(())
+            }
+            None => endpoint.clear_halt().wait(),
+        }
+    }
+
+    /// Complete recovery from a `poll` method.
+    pub(crate) fn poll<EpType: BulkOrInterrupt, Dir: EndpointDirection>(
+        &mut self,
+        endpoint: &mut Endpoint<EpType, Dir>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), TransferError>> {
+        loop {
+            match &mut self.state {
+                State::Idle => return Poll::Ready(Ok(())),
+                State::Draining(r) => {
+                    while endpoint.pending() > 0 {
+                        let c = ready!(endpoint.poll_next_complete(cx));
+                        r.drained::<Dir>(c);
+                    }
+                    let State::Draining(r) = mem::replace(&mut self.state, State::Idle) else {
+                        unreachable!()
+                    };
+                    self.state = State::Waiting(r, None);
+                }
+                State::Waiting(r, action) => {
+                    let action = action.get_or_insert_with(|| match r.delay {
+                        Some(delay) => Action::Backoff(endpoint.delay(delay)),
+                        None => Action::ClearHalt(endpoint.clear_halt_task()),
+                    });
+                    let res = ready!(Pin::new(action).poll(cx));
+                    let State::Waiting(r, _) = mem::replace(&mut self.state, State::Idle) else {
+                        unreachable!()
+                    };
+                    return Poll::Ready(self.finish(endpoint, r, res));
+                }
+            }
+        }
+    }
+
+    fn finish<EpType: BulkOrInterrupt, Dir: EndpointDirection>(
+        &mut self,
+        endpoint: &mut Endpoint<EpType, Dir>,
+        r: Recovering,
+        res: Result<(), Error>,
+    ) -> Result<(), TransferError> {
+        match res {
+            Ok(()) => {
+                for (buf, tag) in r.resubmit {
+                    endpoint.submit_tagged(buf, tag);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to recover endpoint {:02x} from {}: {e}",
+                    endpoint.endpoint_address(),
+                    r.error
+                );
+                self.stats.errors += 1;
+                Err(r.error)
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Recovering {
+    fn drained<Dir: EndpointDirection>(&mut self, c: Completion) {
+        // Transfers cancelled for a retry are submitted again. Transfers
+        // cancelled for a stall are discarded.
+        if self.delay.is_some() && c.status.is_err() {
+            self.resubmit.push(unsent::<Dir>(c));
+        }
+    }
+}
+
+/// Get a buffer to submit again for a failed transfer, and its tag.
+#[cfg(not(target_arch = "wasm32"))]
+fn unsent<Dir: EndpointDirection>(c: Completion) -> (Buffer, u64) {
+    if Dir::DIR == Direction::Out && c.actual_len > 0 {
+        let mut buf = Buffer::new(c.buffer.len() - c.actual_len);
+        buf.extend_from_slice(&c.buffer[c.actual_len..]);
+        (buf, c.tag)
+    } else {
+        (c.buffer, c.tag)
+    }
+}
+
+#[test]
+fn test_backoff() {
+    let policy = RecoveryPolicy::new().retry_faults(3, Duration::from_millis(10));
+    assert_eq!(policy.backoff(1), Duration::from_millis(10));
+    assert_eq!(policy.backoff(3), Duration::from_millis(40));
+    assert_eq!(policy.backoff(100), Duration::from_millis(10 << 16));
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use std::{future::poll_fn, task::Waker};
+
+    use rustix::io::Errno;
+
+    use super::*;
+    use crate::{
+        platform::fake::FakeDevice,
+        transfer::{Bulk, In, Out},
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn recovery(backoff: Duration) -> Recovery {
+        let mut recovery = Recovery::new();
+        recovery.policy = RecoveryPolicy::new()
+            .clear_halt(true)
+            .retry_faults(2, backoff);
+        recovery
+    }
+
+    fn out_buffer(data: &[u8]) -> Buffer {
+        let mut buf = Buffer::new(data.len());
+        buf.extend_from_slice(data);
+        buf
+    }
+
+    #[test]
+    fn stall_drains_and_clears_halt() {
+        let fake = FakeDevice::new();
+        let mut ep = fake.interface().endpoint::<Bulk, In>(0x81).unwrap();
+        let mut recovery = recovery(Duration::ZERO);
+        ep.submit(ep.allocate(64));
+        ep.submit(ep.allocate(64));
+
+        fake.fail(0x81, Errno::PIPE);
+        let c = ep.wait_next_complete(TIMEOUT).unwrap();
+        assert!(recovery.check(&mut ep, c).is_none());
+        assert!(matches!(recovery.state, State::Draining(_)));
+        assert_eq!(fake.submitted(0x81), 0);
+
+        recovery.wait(&mut ep).unwrap();
+        assert!(matches!(recovery.state, State::Idle));
+        assert_eq!(fake.halts_cleared(0x81), 1);
+        assert_eq!(ep.pending(), 0);
+        assert_eq!(recovery.stats.stalls_cleared, 1);
+        assert_eq!(recovery.stats.errors, 0);
+    }
+
+    #[test]
+    fn fault_resubmits_in_order_without_runtime() {
+        let fake = FakeDevice::new();
+        let mut ep = fake.interface().endpoint::<Bulk, Out>(0x01).unwrap();
+        let mut recovery = recovery(Duration::from_millis(20));
+        ep.submit_tagged(out_buffer(&[1; 8]), 1);
+        ep.submit_tagged(out_buffer(&[2; 8]), 2);
+
+        fake.fail(0x01, Errno::PROTO);
+        let c = ep.wait_next_complete(TIMEOUT).unwrap();
+        assert!(recovery.check(&mut ep, c).is_none());
+        assert!(matches!(recovery.state, State::Draining(_)));
+
+        // The backoff uses the device's timer, so no async runtime is needed
+        futures_lite::future::block_on(poll_fn(|cx| recovery.poll(&mut ep, cx))).unwrap();
+        assert!(matches!(recovery.state, State::Idle));
+        assert_eq!(recovery.stats.retries, 1);
+
+        for (data, tag) in [([1; 8], 1), ([2; 8], 2)] {
+            assert_eq!(fake.complete_out(0x01), data);
+            let c = ep.wait_next_complete(TIMEOUT).unwrap();
+            assert_eq!((c.status, c.tag), (Ok(()), tag));
+        }
+    }
+
+    #[test]
+    fn wait_finishes_backoff_started_by_poll() {
+        let fake = FakeDevice::new();
+        let mut ep = fake.interface().endpoint::<Bulk, In>(0x81).unwrap();
+        let mut recovery = recovery(Duration::from_millis(300));
+        ep.submit(ep.allocate(64));
+
+        fake.fail(0x81, Errno::PROTO);
+        let c = ep.wait_next_complete(TIMEOUT).unwrap();
+        assert!(recovery.check(&mut ep, c).is_none());
+        assert!(matches!(recovery.state, State::Waiting(_, None)));
+
+        let start = Instant::now();
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(recovery.poll(&mut ep, &mut cx).is_pending());
+        assert!(matches!(recovery.state, State::Waiting(_, Some(_))));
+        std::thread::sleep(Duration::from_millis(200));
+
+        // Starting the backoff over would take until 500ms
+        recovery.wait(&mut ep).unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(300), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(450), "{elapsed:?}");
+        assert_eq!(fake.submitted(0x81), 1);
+    }
+
+    #[test]
+    fn wait_gives_up_on_cancelled_transfers() {
+        let fake = FakeDevice::new();
+        let mut ep = fake.interface().endpoint::<Bulk, In>(0x81).unwrap();
+        let mut recovery = recovery(Duration::ZERO);
+        ep.submit(ep.allocate(64));
+        ep.submit(ep.allocate(64));
+
+        fake.hold_discards(true);
+        fake.fail(0x81, Errno::PIPE);
+        let c = ep.wait_next_complete(TIMEOUT).unwrap();
+        assert!(recovery.check(&mut ep, c).is_none());
+
+        assert_eq!(recovery.wait(&mut ep), Err(TransferError::Stall));
+        assert!(matches!(recovery.state, State::Idle));
+        assert_eq!(recovery.stats.errors, 1);
+        assert_eq!(fake.halts_cleared(0x81), 0);
+
+        fake.fail(0x81, Errno::NOENT);
+        let c = ep.wait_next_complete(TIMEOUT).unwrap();
+        assert_eq!(c.status, Err(TransferError::Cancelled));
+    }
+}
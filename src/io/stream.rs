@@ -0,0 +1,347 @@
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use crate::{
+    transfer::{Buffer, BulkOrInterrupt, In, Out, TransferError},
+    Endpoint,
+};
+
+/// Wrapper for a Bulk or Interrupt IN [`Endpoint`] implementing
+/// [`Stream`](futures_core::Stream), yielding the buffer of each completed
+/// transfer.
+///
+/// This can be obtained from [`Endpoint::stream()`]. The stream keeps the
+/// configured number of transfers in flight, submitting a new transfer each
+/// time it is polled. Buffers passed back to [`recycle`](Self::recycle) are
+/// re-used for subsequent transfers instead of allocating new ones.
+///
+/// Failed transfers are yielded as errors, and the stream continues to submit
+/// transfers afterwards. The stream ends once
+/// [`set_num_transfers(0)`](Self::set_num_transfers) or
+/// [`cancel_all`](Self::cancel_all) has been called and all pending transfers
+/// have been returned.
+///
+/// A [`Stall`](TransferError::Stall) or
+/// [`Disconnected`](TransferError::Disconnected) error can't be fixed by
+/// resubmitting, so after yielding it, the stream cancels the remaining
+/// transfers and ends as if [`cancel_all`](Self::cancel_all) had been called.
+/// After clearing the halt, call [`set_num_transfers`](Self::set_num_transfers)
+/// to resume streaming.
+pub struct EndpointStream<EpType: BulkOrInterrupt> {
+    endpoint: Endpoint<EpType, In>,
+    transfer_size: usize,
+    num_transfers: usize,
+    free: Vec<Buffer>,
+
+    /// A stall or disconnect ended the stream, so errors of the remaining
+    /// transfers are not reported.
+    failed: bool,
+}
+
+impl<EpType: BulkOrInterrupt> EndpointStream<EpType> {
+    /// Create a new `EndpointStream` wrapping the given endpoint.
+    ///
+    /// The `transfer_size` parameter is the size of the buffer passed to the OS
+    /// for each transfer. It will be rounded up to the next multiple of the
+    /// endpoint's max packet size, and limited to the OS's
+    /// [maximum transfer size](crate::Capabilities::max_transfer_size).
+    pub fn new(endpoint: Endpoint<EpType, In>, transfer_size: usize) -> Self {
+        let transfer_size = endpoint
+            .device_capabilities()
+            .transfer_size(transfer_size, endpoint.max_packet_size());
+
+        Self {
+            endpoint,
+            transfer_size,
+            num_transfers: 1,
+            free: Vec::new(),
+            failed: false,
+        }
+    }
+
+    /// Set the number of concurrent transfers.
+    ///
+    /// A value of 1 (default) means that a transfer is only submitted when the
+    /// stream is polled. For continuous streaming, a value of 2 or more is
+    /// recommended so that the host controller can continue to receive data
+    /// while the application processes a completed transfer.
+    ///
+    /// A value of 0 means no further transfers will be submitted, and the
+    /// stream ends after the pending transfers complete.
+    ///
+    /// Additional transfers are submitted the next time the stream is polled.
+    pub fn set_num_transfers(&mut self, num_transfers: usize) {
+        self.num_transfers = num_transfers;
+        self.failed = false;
+    }
+
+    /// Set the number of concurrent transfers.
+    ///
+    /// See [Self::set_num_transfers] (this version is for method chaining).
+    pub fn with_num_transfers(mut self, num_transfers: usize) -> Self {
+        self.set_num_transfers(num_transfers);
+        self
+    }
+
+    /// Return a buffer yielded by the stream so it can be re-used for a
+    /// subsequent transfer.
+    ///
+    /// Buffers smaller than the transfer size are dropped.
+    pub fn recycle(&mut self, buf: Buffer) {
+        if buf.capacity() >= self.transfer_size && self.free.len() < self.num_transfers {
+            self.free.push(buf);
+        }
+    }
+
+    /// Cancel all pending transfers.
+    ///
+    /// This sets [`num_transfers`](Self::set_num_transfers) to 0, so no further
+    /// transfers will be submitted. Transfers that were cancelled before
+    /// receiving any data are skipped, and then the stream ends.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn cancel_all(&mut self) {
+        self.num_transfers = 0;
+        self.endpoint.cancel_all();
+    }
+
+    /// Stop submitting transfers after an error that resubmitting won't fix.
+    fn fail(&mut self) {
+        self.num_transfers = 0;
+        self.failed = true;
+        #[cfg(not(target_arch = "wasm32"))]
+        self.endpoint.cancel_all();
+    }
+
+    /// Get a reference to the underlying [`Endpoint`].
+    pub fn get_ref(&self) -> &Endpoint<EpType, In> {
+        &self.endpoint
+    }
+
+    /// Destroy this `EndpointStream` and return the underlying [`Endpoint`].
+    ///
+    /// Any pending transfers are not cancelled.
+    pub fn into_inner(self) -> Endpoint<EpType, In> {
+        self.endpoint
+    }
+
+    fn submit_transfers(&mut self) {
+        while self.endpoint.pending() < self.num_transfers {
+            let buf = match self.free.pop() {
+                Some(mut buf) => {
+                    buf.set_requested_len(self.transfer_size);
+                    buf
+                }
+                None => self.endpoint.allocate(self.transfer_size),
+            };
+            self.endpoint.submit(buf);
+        }
+    }
+}
+
+impl<EpType: BulkOrInterrupt> futures_core::Stream for EndpointStream<EpType> {
+    type Item = Result<Buffer, TransferError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = Pin::into_inner(self);
+        loop {
+            this.submit_transfers();
+            if this.endpoint.pending() == 0 {
+                return Poll::Ready(None);
+            }
+
+            let c = ready!(this.endpoint.poll_next_complete(cx));
+            match c.status {
+                Ok(()) => return Poll::Ready(Some(Ok(c.buffer))),
+                Err(TransferError::Cancelled) if this.num_transfers == 0 => {
+                    if !c.buffer.is_empty() {
+                        return Poll::Ready(Some(Ok(c.buffer)));
+                    }
+                    this.recycle(c.buffer);
+                }
+                Err(_) if this.failed => this.recycle(c.buffer),
+                Err(e) => {
+                    this.recycle(c.buffer);
+                    if matches!(e, TransferError::Stall | TransferError::Disconnected) {
+                        this.fail();
+                    }
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+        }
+    }
+}
+
+/// Wrapper for a Bulk or Interrupt OUT [`Endpoint`] implementing
+/// [`Sink<Buffer>`](futures_sink::Sink), submitting each buffer as a
+/// transfer.
+///
+/// This can be obtained from [`Endpoint::sink()`]. The sink applies
+/// backpressure once the configured number of transfers are pending, waiting
+/// for one to complete before accepting another buffer. A failed transfer is
+/// reported as an error from the next call to `poll_ready`, `poll_flush` or
+/// `poll_close`.
+///
+/// Buffers of completed transfers are kept and handed out again by
+/// [`allocate`](Self::allocate).
+pub struct EndpointSink<EpType: BulkOrInterrupt> {
+    endpoint: Endpoint<EpType, Out>,
+    num_transfers: usize,
+    free: Vec<Buffer>,
+}
+
+impl<EpType: BulkOrInterrupt> EndpointSink<EpType> {
+    /// Create a new `EndpointSink` wrapping the given endpoint.
+    pub fn new(endpoint: Endpoint<EpType, Out>) -> Self {
+        Self {
+            endpoint,
+            num_transfers: 1,
+            free: Vec::new(),
+        }
+    }
+
+    /// Set the maximum number of concurrent transfers.
+    ///
+    /// The sink accepts buffers until this many transfers are pending. The
+    /// default is 1, and values below 1 are treated as 1.
+    pub fn set_num_transfers(&mut self, num_transfers: usize) {
+        self.num_transfers = num_transfers.max(1);
+    }
+
+    /// Set the maximum number of concurrent transfers.
+    ///
+    /// See [Self::set_num_transfers] (this version is for method chaining).
+    pub fn with_num_transfers(mut self, num_transfers: usize) -> Self {
+        self.set_num_transfers(num_transfers);
+        self
+    }
+
+    /// Get an empty buffer with a capacity of at least `len` bytes, re-using
+    /// the buffer of a completed transfer if possible.
+    ///
+    /// See [`Endpoint::allocate`].
+    pub fn allocate(&mut self, len: usize) -> Buffer {
+        if let Some(i) = self.free.iter().position(|b| b.capacity() >= len) {
+            let mut buf = self.free.swap_remove(i);
+            buf.clear();
+            buf
+        } else {
+            self.endpoint.allocate(len)
+        }
+    }
+
+    /// Get a reference to the underlying [`Endpoint`].
+    pub fn get_ref(&self) -> &Endpoint<EpType, Out> {
+        &self.endpoint
+    }
+
+    /// Destroy this `EndpointSink` and return the underlying [`Endpoint`].
+    ///
+    /// Any pending transfers are not cancelled.
+    pub fn into_inner(self) -> Endpoint<EpType, Out> {
+        self.endpoint
+    }
+
+    fn poll_one(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TransferError>> {
+        let c = ready!(self.endpoint.poll_next_complete(cx));
+        if self.free.len() < self.num_transfers {
+            self.free.push(c.buffer);
+        }
+        Poll::Ready(c.status)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TransferError>> {
+        while self.endpoint.pending() > 0 {
+            ready!(self.poll_one(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<EpType: BulkOrInterrupt> futures_sink::Sink<Buffer> for EndpointSink<EpType> {
+    type Error = TransferError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), TransferError>> {
+        let this = Pin::into_inner(self);
+        while this.endpoint.pending() >= this.num_transfers {
+            ready!(this.poll_one(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Buffer) -> Result<(), TransferError> {
+        Pin::into_inner(self).endpoint.submit(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), TransferError>> {
+        Pin::into_inner(self).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), TransferError>> {
+        Pin::into_inner(self).poll_flush(cx)
+    }
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use futures_lite::{future::block_on, StreamExt};
+    use rustix::io::Errno;
+
+    use super::*;
+    use crate::{platform::fake::FakeDevice, transfer::Bulk};
+
+    #[test]
+    fn stall_ends_stream() {
+        let fake = FakeDevice::new();
+        let mut stream = fake
+            .interface()
+            .endpoint::<Bulk, In>(0x81)
+            .unwrap()
+            .stream(64)
+            .with_num_transfers(2);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                fake.complete_in(0x81, &[1]);
+                fake.fail(0x81, Errno::PIPE);
+            });
+            assert_eq!(&block_on(stream.next()).unwrap().unwrap()[..], &[1]);
+            let next = block_on(stream.next()).unwrap();
+            assert_eq!(next.unwrap_err(), TransferError::Stall);
+        });
+
+        // The other transfer is cancelled rather than yielding another error
+        assert!(block_on(stream.next()).is_none());
+        assert_eq!(fake.submitted(0x81), 0);
+
+        // Streaming resumes once re-enabled
+        stream.set_num_transfers(1);
+        std::thread::scope(|s| {
+            s.spawn(|| fake.complete_in(0x81, &[2]));
+            assert_eq!(&block_on(stream.next()).unwrap().unwrap()[..], &[2]);
+        });
+    }
+
+    #[test]
+    fn disconnect_ends_stream() {
+        let fake = FakeDevice::new();
+        let mut stream = fake
+            .interface()
+            .endpoint::<Bulk, In>(0x81)
+            .unwrap()
+            .stream(64)
+            .with_num_transfers(3);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                fake.wait_submitted(0x81, 3);
+                fake.disconnect();
+            });
+            let next = block_on(stream.next()).unwrap();
+            assert_eq!(next.unwrap_err(), TransferError::Disconnected);
+        });
+        assert!(block_on(stream.next()).is_none());
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use crate::{
+    timer::Delay,
+    transfer::{BulkOrInterrupt, Completion, Direction, EndpointDirection, TransferError},
+    Endpoint,
+};
+
+/// Timeout for waiting on transfers from the `poll` methods of
+/// `EndpointRead` and `EndpointWrite`.
+///
+/// Unlike the blocking timeout, the pending transfers are cancelled when the
+/// timeout elapses, because the caller may drop the future that is waiting.
+pub(crate) struct PollTimeout {
+    delay: Option<Delay>,
+
+    /// Transfers were cancelled, waiting for them to be returned.
+    cancelled: bool,
+
+    /// IN transfers returned with data after the timeout, to be read next.
+    kept: VecDeque<Completion>,
+}
+
+impl PollTimeout {
+    pub(crate) fn new() -> Self {
+        Self {
+            delay: None,
+            cancelled: false,
+            kept: VecDeque::new(),
+        }
+    }
+
+    /// Whether transfers cancelled by the timeout are still being returned.
+    pub(crate) fn is_draining(&self) -> bool {
+        self.cancelled
+    }
+
+    /// Take the oldest IN transfer returned with data while draining after a
+    /// timeout, once the timeout has been reported.
+    pub(crate) fn take_kept(&mut self) -> Option<Completion> {
+        if self.cancelled {
+            return None;
+        }
+        self.kept.pop_front()
+    }
+
+    /// Poll for the next completion, with `timeout` measured from the first
+    /// poll that had to wait.
+    ///
+    /// If the timeout elapses, all pending transfers are cancelled and
+    /// returned before this returns `None`. IN transfers that received data
+    /// or completed before they were cancelled are kept for
+    /// [`take_kept`](Self::take_kept), and the others are discarded.
+    pub(crate) fn poll_next_complete<EpType: BulkOrInterrupt, Dir: EndpointDirection>(
+        &mut self,
+        endpoint: &mut Endpoint<EpType, Dir>,
+        timeout: Duration,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Completion>> {
+        if !self.cancelled {
+            if let Poll::Ready(c) = endpoint.poll_next_complete(cx) {
+                self.delay = None;
+                return Poll::Ready(Some(c));
+            }
+
+            if timeout == Duration::MAX {
+                return Poll::Pending;
+            }
+
+            let delay = self.delay.get_or_insert_with(|| endpoint.delay(timeout));
+            ready!(Pin::new(delay).poll(cx));
+
+            log::debug!(
+                "Timeout on endpoint {:02x}, cancelling {} transfers",
+                endpoint.endpoint_address(),
+                endpoint.pending()
+            );
+            self.delay = None;
+            self.cancelled = true;
+            endpoint.cancel_all();
+        }
+
+        while endpoint.pending() > 0 {
+            let c = ready!(endpoint.poll_next_complete(cx));
+            let empty = c.status == Err(TransferError::Cancelled) && c.buffer.is_empty();
+            if Dir::DIR == Direction::In && !empty {
+                self.kept.push_back(c);
+            }
+        }
+        self.cancelled = false;
+        Poll::Ready(None)
+    }
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use std::future::poll_fn;
+
+    use futures_lite::future::block_on;
+
+    use super::*;
+    use crate::{
+        platform::fake::FakeDevice,
+        transfer::{Bulk, In},
+    };
+
+    #[test]
+    fn completes_before_timeout() {
+        let fake = FakeDevice::new();
+        let mut ep = fake.interface().endpoint::<Bulk, In>(0x81).unwrap();
+        let mut timeout = PollTimeout::new();
+        ep.submit(ep.allocate(64));
+
+        std::thread::scope(|s| {
+            s.spawn(|| fake.complete_in(0x81, &[1]));
+            let c = block_on(poll_fn(|cx| {
+                timeout.poll_next_complete(&mut ep, Duration::from_secs(5), cx)
+            }));
+            assert_eq!(&c.unwrap().buffer[..], &[1]);
+        });
+        assert!(timeout.delay.is_none());
+    }
+
+    #[test]
+    fn timeout_cancels_and_drains() {
+        let fake = FakeDevice::new();
+        let mut ep = fake.interface().endpoint::<Bulk, In>(0x81).unwrap();
+        let mut timeout = PollTimeout::new();
+        ep.submit(ep.allocate(64));
+        ep.submit(ep.allocate(64));
+
+        let c = block_on(poll_fn(|cx| {
+            timeout.poll_next_complete(&mut ep, Duration::from_millis(50), cx)
+        }));
+        assert!(c.is_none());
+        assert_eq!(ep.pending(), 0);
+        assert_eq!(fake.submitted(0x81), 0);
+        assert!(!timeout.cancelled);
+        assert!(timeout.take_kept().is_none());
+
+        // The next wait starts a new timeout
+        ep.submit(ep.allocate(64));
+        std::thread::scope(|s| {
+            s.spawn(|| fake.complete_in(0x81, &[2]));
+            let c = block_on(poll_fn(|cx| {
+                timeout.poll_next_complete(&mut ep, Duration::from_secs(5), cx)
+            }));
+            assert_eq!(&c.unwrap().buffer[..], &[2]);
+        });
+    }
+}
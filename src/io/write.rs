@@ -1,16 +1,20 @@
 use crate::{
-    transfer::{Buffer, BulkOrInterrupt, Completion, Out},
+    transfer::{Buffer, BufferPool, BulkOrInterrupt, Completion, Out},
     Endpoint,
 };
 use std::{
     future::poll_fn,
-    io::{Error, ErrorKind, Write},
-    task::{Context, Poll},
+    io::{Error, ErrorKind, IoSlice, Write},
+    task::{ready, Context, Poll},
     time::Duration,
 };
 
 #[cfg(any(feature = "tokio", feature = "smol"))]
-use std::{pin::Pin, task::ready};
+use std::pin::Pin;
+
+use super::MessageWrite;
+#[cfg(not(target_arch = "wasm32"))]
+use super::{PollTimeout, Recovery, RecoveryPolicy, RecoveryStats};
 
 /// Wrapper for a Bulk or Interrupt OUT [`Endpoint`](crate::Endpoint) that
 /// manages transfers to provide a higher-level buffered API.
@@ -34,7 +38,12 @@ pub struct EndpointWrite<EpType: BulkOrInterrupt> {
     writing: Option<Buffer>,
     transfer_size: usize,
     num_transfers: usize,
-    write_timeout: Duration,
+    pub(super) write_timeout: Duration,
+    pool: Option<BufferPool>,
+    #[cfg(not(target_arch = "wasm32"))]
+    recovery: Recovery,
+    #[cfg(not(target_arch = "wasm32"))]
+    poll_timeout: PollTimeout,
 }
 
 impl<EpType: BulkOrInterrupt> EndpointWrite<EpType> {
@@ -42,12 +51,15 @@ impl<EpType: BulkOrInterrupt> EndpointWrite<EpType> {
     ///
     /// The `transfer_size` parameter is the size of the buffer passed to the OS
     /// for each transfer. It will be rounded up to the next multiple of the
-    /// endpoint's max packet size. Data will be buffered and sent in chunks of
-    /// this size, unless `flush` or [`submit`](Self::submit) are
-    /// called to force sending a partial buffer immediately.
+    /// endpoint's max packet size, and limited to the OS's
+    /// [maximum transfer size](crate::Capabilities::max_transfer_size). Data
+    /// will be buffered and sent in chunks of this size, unless `flush` or
+    /// [`submit`](Self::submit) are called to force sending a partial buffer
+    /// immediately.
     pub fn new(endpoint: Endpoint<EpType, Out>, transfer_size: usize) -> Self {
-        let packet_size = endpoint.max_packet_size();
-        let transfer_size = (transfer_size.div_ceil(packet_size)).max(1) * packet_size;
+        let transfer_size = endpoint
+            .device_capabilities()
+            .transfer_size(transfer_size, endpoint.max_packet_size());
 
         Self {
             endpoint,
@@ -55,6 +67,11 @@ impl<EpType: BulkOrInterrupt> EndpointWrite<EpType> {
             transfer_size,
             num_transfers: 1,
             write_timeout: Duration::MAX,
+            pool: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            recovery: Recovery::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            poll_timeout: PollTimeout::new(),
         }
     }
 
@@ -80,21 +97,25 @@ impl<EpType: BulkOrInterrupt> EndpointWrite<EpType> {
         self
     }
 
-    /// Set the timeout for a transfer in the blocking `write` APIs.
+    /// Set the timeout for waiting for a transfer to complete.
     ///
-    /// This affects the `std::io::Write` implementation only, and not the async
-    /// trait implementations.
+    /// When a timeout occurs in the blocking `std::io::Write` implementation,
+    /// writing new data fails but transfers for previously-written data are
+    /// not cancelled. The data passed in the failed `write` call is not
+    /// written to the buffer, though note that functions like `write_all` that
+    /// call `write` multiple times may have successfully written some of the
+    /// data.
     ///
-    /// When a timeout occurs, writing new data fails but transfers for
-    /// previously-written data are not cancelled. The data passed in the failed
-    /// `write` call is not written to the buffer, though note that functions
-    /// like `write_all` that call `write` multiple times may have successfully
-    /// written some of the data.
+    /// When a timeout occurs in the async trait implementations, all pending
+    /// transfers are cancelled before the call fails with
+    /// [`TimedOut`](ErrorKind::TimedOut). Some of their data may already have
+    /// been sent to the device. Data that was buffered but not yet submitted
+    /// is kept. The async timeout does not depend on the async runtime.
     pub fn set_write_timeout(&mut self, timeout: Duration) {
         self.write_timeout = timeout;
     }
 
-    /// Set the timeout for an individual transfer for the blocking `write` APIs.
+    /// Set the timeout for waiting for a transfer to complete.
     ///
     /// See [Self::set_write_timeout] -- this is for method chaining with `EndpointWrite::new()`.
     pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
@@ -102,6 +123,71 @@ impl<EpType: BulkOrInterrupt> EndpointWrite<EpType> {
         self
     }
 
+    /// Allocate transfer buffers from a [`BufferPool`] instead of the
+    /// endpoint.
+    ///
+    /// The pool is only used if its buffer size is at least the transfer
+    /// size. Each transfer still sends at most the transfer size. Completed
+    /// buffers are reused for later writes, and return to the pool when they
+    /// are no longer needed or the `EndpointWrite` is dropped.
+    pub fn set_buffer_pool(&mut self, pool: BufferPool) {
+        self.pool = Some(pool);
+    }
+
+    /// Allocate transfer buffers from a [`BufferPool`].
+    ///
+    /// See [Self::set_buffer_pool] (this version is for method chaining).
+    pub fn with_buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.set_buffer_pool(pool);
+        self
+    }
+
+    /// Set the policy for handling failed transfers.
+    ///
+    /// By default, the error of a failed transfer is returned from the next
+    /// call that waits for a transfer to complete. See [`RecoveryPolicy`] for
+    /// options to clear stalls and retry faults.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_recovery_policy(&mut self, policy: RecoveryPolicy) {
+        self.recovery.policy = policy;
+    }
+
+    /// Set the policy for handling failed transfers.
+    ///
+    /// See [Self::set_recovery_policy] (this version is for method chaining).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_recovery_policy(mut self, policy: RecoveryPolicy) -> Self {
+        self.set_recovery_policy(policy);
+        self
+    }
+
+    /// Get the counts of failed transfers handled by the
+    /// [recovery policy](Self::set_recovery_policy) or returned as errors.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn recovery_stats(&self) -> RecoveryStats {
+        self.recovery.stats
+    }
+
+    fn allocate(&self) -> Buffer {
+        match &self.pool {
+            Some(pool) if pool.buffer_size() >= self.transfer_size => pool.get(),
+            _ => self.endpoint.allocate(self.transfer_size),
+        }
+    }
+
+    /// Convert this `EndpointWrite` into a [`MessageWrite`] that sends whole
+    /// messages terminated by short or zero-length packets.
+    ///
+    /// Messages longer than `max_message_size` are rejected with an error.
+    pub fn into_messages(self, max_message_size: usize) -> MessageWrite<EpType> {
+        MessageWrite::new(self, max_message_size)
+    }
+
+    /// Get a reference to the underlying [`Endpoint`].
+    pub fn get_ref(&self) -> &Endpoint<EpType, Out> {
+        &self.endpoint
+    }
+
     /// Destroy this `EndpointWrite` and return the underlying [`Endpoint`].
     ///
     /// Any pending transfers are not cancelled.
@@ -113,7 +199,7 @@ impl<EpType: BulkOrInterrupt> EndpointWrite<EpType> {
         debug_assert_eq!(self.writing.as_ref().map_or(0, |b| b.len()), 0);
         let mut buf = c.buffer;
         if buf.capacity() > 0 && self.endpoint.pending() < self.num_transfers {
-            debug_assert!(buf.capacity() == self.transfer_size);
+            debug_assert!(buf.capacity() >= self.transfer_size);
             buf.clear();
             self.writing = Some(buf);
         }
@@ -122,40 +208,143 @@ impl<EpType: BulkOrInterrupt> EndpointWrite<EpType> {
 
     #[cfg(not(target_arch = "wasm32"))]
     fn wait_one(&mut self) -> Result<(), Error> {
+        self.recovery.wait(&mut self.endpoint)?;
+        if self.endpoint.pending() == 0 {
+            return Ok(());
+        }
+
         let t = self.endpoint.wait_next_complete(self.write_timeout);
         let t = t.ok_or_else(|| Error::new(ErrorKind::TimedOut, "write timeout"))?;
-        self.handle_completion(t)
+        match self.recovery.check(&mut self.endpoint, t) {
+            Some(t) => self.handle_completion(t),
+            None => Ok(self.recovery.wait(&mut self.endpoint)?),
+        }
     }
 
     fn poll_one(&mut self, cx: &mut Context) -> Poll<Result<(), Error>> {
-        self.endpoint
-            .poll_next_complete(cx)
-            .map(|c| self.handle_completion(c))
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            ready!(self.recovery.poll(&mut self.endpoint, cx))?;
+            if self.endpoint.pending() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let Some(c) = ready!(self.poll_timeout.poll_next_complete(
+            &mut self.endpoint,
+            self.write_timeout,
+            cx
+        )) else {
+            return Poll::Ready(Err(Error::new(ErrorKind::TimedOut, "write timeout")));
+        };
+
+        #[cfg(target_arch = "wasm32")]
+        let c = ready!(self.endpoint.poll_next_complete(cx));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let Some(c) = self.recovery.check(&mut self.endpoint, c) else {
+            return self.poll_one(cx);
+        };
+
+        Poll::Ready(self.handle_completion(c))
     }
 
-    #[cfg(any(feature = "tokio", feature = "smol"))]
-    fn poll_write(&mut self, cx: &mut Context<'_>, src: &[u8]) -> Poll<Result<usize, Error>> {
+    pub(super) fn poll_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        src: &[u8],
+    ) -> Poll<Result<usize, Error>> {
         let buf = loop {
             if let Some(buf) = self.writing.as_mut() {
                 break buf;
             }
             if self.endpoint.pending() < self.num_transfers {
-                self.writing = Some(self.endpoint.allocate(self.transfer_size));
+                self.writing = Some(self.allocate());
             } else {
                 ready!(self.poll_one(cx))?;
             }
         };
 
-        let len = src.len().min(buf.remaining_capacity());
+        let len = src.len().min(self.transfer_size - buf.len());
         buf.extend_from_slice(&src[..len]);
 
-        if buf.remaining_capacity() == 0 {
+        if buf.len() == self.transfer_size {
             self.endpoint.submit(self.writing.take().unwrap());
         }
 
         Poll::Ready(Ok(len))
     }
 
+    /// Whether writing more data would have to wait for a transfer to complete.
+    fn would_block(&self) -> bool {
+        self.writing.is_none() && self.endpoint.pending() >= self.num_transfers
+    }
+
+    #[cfg(any(feature = "tokio", feature = "smol"))]
+    fn poll_write_vectored(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, Error>> {
+        let mut total = 0;
+        for src in bufs {
+            let mut src = &src[..];
+            while !src.is_empty() {
+                if total > 0 && self.would_block() {
+                    return Poll::Ready(Ok(total));
+                }
+                // Bytes already copied must be reported even if this write
+                // has to wait after all.
+                let len = match self.poll_write(cx, src) {
+                    Poll::Pending if total > 0 => return Poll::Ready(Ok(total)),
+                    r => ready!(r)?,
+                };
+                total += len;
+                src = &src[len..];
+            }
+        }
+        Poll::Ready(Ok(total))
+    }
+
+    /// Cancel all pending transfers and discard any buffered data that has not
+    /// been submitted.
+    ///
+    /// Data that was already sent by a cancelled transfer may have been
+    /// received by the device. The cancelled transfers are still returned by
+    /// the endpoint, so subsequent calls that wait for transfers return an
+    /// error of kind [`Interrupted`](ErrorKind::Interrupted) for each transfer
+    /// that was cancelled before it completed.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn cancel_all(&mut self) {
+        self.writing = None;
+        self.endpoint.cancel_all();
+    }
+
+    /// Wait for cancelled transfers to be returned, discarding their status.
+    ///
+    /// Returns `false` if some are still pending at `deadline`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(super) fn drain(&mut self, deadline: std::time::Instant) -> bool {
+        while self.endpoint.pending() > 0 {
+            let timeout = deadline.saturating_duration_since(std::time::Instant::now());
+            if self.endpoint.wait_next_complete(timeout).is_none() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Wait for cancelled transfers to be returned, discarding their status.
+    ///
+    /// Async version of [Self::drain].
+    pub(super) fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        while self.endpoint.pending() > 0 {
+            ready!(self.endpoint.poll_next_complete(cx));
+        }
+        Poll::Ready(())
+    }
+
     /// Submit any buffered data to the OS immediately.
     ///
     /// This submits the current buffer even if it is not full, but does not wait
@@ -163,7 +352,7 @@ impl<EpType: BulkOrInterrupt> EndpointWrite<EpType> {
     /// [Write::flush]). If the buffer is empty, this does nothing.
     pub fn submit(&mut self) {
         if self.writing.as_ref().is_some_and(|b| !b.is_empty()) {
-            self.endpoint.submit(self.writing.take().unwrap())
+            self.endpoint.submit(self.writing.take().unwrap());
         }
     }
 
@@ -215,8 +404,7 @@ impl<EpType: BulkOrInterrupt> EndpointWrite<EpType> {
         Ok(())
     }
 
-    #[cfg(any(feature = "tokio", feature = "smol"))]
-    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+    pub(super) fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
         self.submit();
         while self.endpoint.pending() > 0 {
             ready!(self.poll_one(cx))?;
@@ -251,22 +439,44 @@ impl<EpType: BulkOrInterrupt> Write for EndpointWrite<EpType> {
                 break buf;
             }
             if self.endpoint.pending() < self.num_transfers {
-                self.writing = Some(self.endpoint.allocate(self.transfer_size));
+                self.writing = Some(self.allocate());
             } else {
                 self.wait_one()?
             }
         };
 
-        let len = src.len().min(buf.remaining_capacity());
+        let len = src.len().min(self.transfer_size - buf.len());
         buf.extend_from_slice(&src[..len]);
 
-        if buf.remaining_capacity() == 0 {
+        if buf.len() == self.transfer_size {
             self.endpoint.submit(self.writing.take().unwrap());
         }
 
         Ok(len)
     }
 
+    /// Write data from a sequence of buffers to the endpoint.
+    ///
+    /// The slices are copied into the same transfer buffers as `write()`, so
+    /// for example a header and payload are sent in a single transfer without
+    /// the caller concatenating them. This writes as much as possible without
+    /// blocking once some data has been written.
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        let mut total = 0;
+        for src in bufs {
+            let mut src = &src[..];
+            while !src.is_empty() {
+                if total > 0 && self.would_block() {
+                    return Ok(total);
+                }
+                let len = self.write(src)?;
+                total += len;
+                src = &src[len..];
+            }
+        }
+        Ok(total)
+    }
+
     /// Submit any buffered data immediately and wait for all pending transfers
     /// to complete or fail.
     fn flush(&mut self) -> std::io::Result<()> {
@@ -290,6 +500,16 @@ impl<EpType: BulkOrInterrupt> futures_io::AsyncWrite for EndpointWrite<EpType> {
         Pin::into_inner(self).poll_write(cx, buf)
     }
 
+    /// Write data from a sequence of buffers to the endpoint, gathering them
+    /// into the same transfer buffers.
+    fn poll_write_vectored(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::into_inner(self).poll_write_vectored(cx, bufs)
+    }
+
     /// Submit any buffered data immediately and wait for all pending transfers
     /// to complete or fail.
     fn poll_flush(
@@ -323,6 +543,20 @@ impl<EpType: BulkOrInterrupt> tokio::io::AsyncWrite for EndpointWrite<EpType> {
         Pin::into_inner(self).poll_write(cx, buf)
     }
 
+    /// Write data from a sequence of buffers to the endpoint, gathering them
+    /// into the same transfer buffers.
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::into_inner(self).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
     /// Submit any buffered data immediately and wait for all pending transfers
     /// to complete or fail.
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
@@ -336,3 +570,93 @@ impl<EpType: BulkOrInterrupt> tokio::io::AsyncWrite for EndpointWrite<EpType> {
         Pin::into_inner(self).poll_flush(cx)
     }
 }
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use std::io::{IoSlice, Write};
+
+    use crate::{
+        platform::fake::FakeDevice,
+        transfer::{Bulk, Out},
+    };
+
+    #[test]
+    fn write_vectored_stops_before_blocking() {
+        let fake = FakeDevice::new();
+        let mut writer = fake
+            .interface()
+            .endpoint::<Bulk, Out>(0x01)
+            .unwrap()
+            .writer(64)
+            .with_num_transfers(1);
+
+        let bufs = [IoSlice::new(&[1; 40]), IoSlice::new(&[2; 40])];
+        assert_eq!(writer.write_vectored(&bufs).unwrap(), 64);
+        assert_eq!(fake.submitted(0x01), 1);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let sent = fake.complete_out(0x01);
+                assert_eq!(sent, [[1; 40].as_slice(), &[2; 24]].concat());
+            });
+            assert_eq!(
+                writer.write_vectored(&[IoSlice::new(&[2; 16])]).unwrap(),
+                16
+            );
+        });
+    }
+
+    #[test]
+    fn larger_pool_buffers() {
+        let fake = FakeDevice::new();
+        let pool = fake.device().buffer_pool(4096, 2);
+        let mut writer = fake
+            .interface()
+            .endpoint::<Bulk, Out>(0x01)
+            .unwrap()
+            .writer(512)
+            .with_num_transfers(2)
+            .with_buffer_pool(pool.clone());
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for _ in 0..4 {
+                    assert_eq!(fake.complete_out(0x01), [1; 512]);
+                }
+            });
+            // Completed buffers are reused, and transfers stay at 512 bytes
+            writer.write_all(&[1; 2048]).unwrap();
+            writer.flush().unwrap();
+        });
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.exhausted), (2, 0));
+    }
+
+    #[cfg(any(feature = "tokio", feature = "smol"))]
+    #[test]
+    fn poll_write_vectored_reports_partial_write() {
+        use std::task::{Context, Poll, Waker};
+
+        let fake = FakeDevice::new();
+        let mut writer = fake
+            .interface()
+            .endpoint::<Bulk, Out>(0x01)
+            .unwrap()
+            .writer(64)
+            .with_num_transfers(1);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let bufs = [IoSlice::new(&[1; 40]), IoSlice::new(&[2; 40])];
+        let res = writer.poll_write_vectored(&mut cx, &bufs);
+        assert!(matches!(res, Poll::Ready(Ok(64))));
+
+        // Nothing can be written until the transfer completes
+        let bufs = [IoSlice::new(&[2; 16])];
+        assert!(writer.poll_write_vectored(&mut cx, &bufs).is_pending());
+        fake.complete_out(0x01);
+        let res = futures_lite::future::block_on(std::future::poll_fn(|cx| {
+            writer.poll_write_vectored(cx, &bufs)
+        }));
+        assert_eq!(res.unwrap(), 16);
+    }
+}
@@ -218,6 +218,11 @@
 //!
 //! These features do not affect and are not required for transfers, which are
 //! implemented on top of natively-async OS APIs.
+//!
+//! ## Serialization
+//!
+//! Enable the cargo feature `serde` to serialize device and bus information
+//! and descriptors. See the [`snapshot`] module.
 
 mod platform;
 
@@ -226,10 +231,17 @@ mod enumeration;
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 pub use enumeration::BusInfo;
 pub use enumeration::{DeviceId, DeviceInfo, InterfaceInfo, Speed, UsbControllerType};
+#[cfg(target_os = "linux")]
+pub use enumeration::{PowerControl, RuntimeStatus};
 
 mod device;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use device::ControlQueue;
 pub use device::{Device, Endpoint, Interface};
 
+mod capabilities;
+pub use capabilities::{Capabilities, UsbfsCapabilities};
+
 pub mod transfer;
 
 #[cfg(any(
@@ -240,9 +252,21 @@ pub mod transfer;
 ))]
 pub mod hotplug;
 
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+pub mod lsusb;
+
+#[cfg(feature = "serde")]
+pub mod snapshot;
+
+#[cfg(target_os = "linux")]
+pub mod broker;
+
 mod maybe_future;
 pub use maybe_future::MaybeFuture;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod timer;
+
 mod bitset;
 
 pub mod io;
@@ -0,0 +1,778 @@
+//! Text reports of device descriptors in the format of `lsusb -v`.
+//!
+//! ### Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use nusb::{MaybeFuture, lsusb::VerboseReport};
+//!
+//! for info in nusb::list_devices().wait().unwrap() {
+//!     let Ok(device) = info.open().wait() else { continue };
+//!     print!("{}", VerboseReport::new(&info, &device, Duration::from_millis(100)));
+//! }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter, Result},
+    num::NonZeroU8,
+    time::Duration,
+};
+
+use crate::{
+    descriptors::{
+        ConfigurationDescriptor, Descriptor, DescriptorIter, DeviceDescriptor, EndpointDescriptor,
+        TransferType, DESCRIPTOR_TYPE_CONFIGURATION, DESCRIPTOR_TYPE_DEVICE,
+        DESCRIPTOR_TYPE_ENDPOINT, DESCRIPTOR_TYPE_INTERFACE, DESCRIPTOR_TYPE_INTERFACE_ASSOCIATION,
+        DESCRIPTOR_TYPE_SUPERSPEED_COMPANION,
+    },
+    Device, DeviceInfo, MaybeFuture,
+};
+
+const DESCRIPTOR_TYPE_BOS: u8 = 0x0F;
+const DESCRIPTOR_TYPE_DEVICE_CAPABILITY: u8 = 0x10;
+const DESCRIPTOR_TYPE_HID: u8 = 0x21;
+const DESCRIPTOR_TYPE_CS_INTERFACE: u8 = 0x24;
+
+const CLASS_COMMUNICATIONS: u8 = 0x02;
+const CLASS_HID: u8 = 0x03;
+
+/// Column at which `lsusb` right-aligns field values.
+const VALUE_COLUMN: usize = 25;
+
+/// A snapshot of a device's descriptors and strings that formats as the
+/// output of `lsusb -v`.
+///
+/// All device IO happens in [`VerboseReport::new`], so the report can be
+/// formatted any number of times with [`Display`].
+#[derive(Clone, Debug)]
+pub struct VerboseReport {
+    bus_id: String,
+    device_address: u8,
+    device_descriptor: DeviceDescriptor,
+    configurations: Vec<Vec<u8>>,
+    bos: Option<Vec<u8>>,
+    strings: BTreeMap<u8, String>,
+}
+
+impl VerboseReport {
+    /// Read the descriptors and strings of an opened device.
+    ///
+    /// This requests the string descriptors referenced by the device,
+    /// configuration, interface association and interface descriptors in
+    /// the device's first supported language, and the BOS descriptor for
+    /// devices reporting USB 2.01 or later. Requests that fail are left out
+    /// of the report; for the device strings, the values cached by the OS in
+    /// `info` are used instead.
+    ///
+    /// The `timeout` applies to each request individually.
+    pub fn new(info: &DeviceInfo, device: &Device, timeout: Duration) -> VerboseReport {
+        let device_descriptor = device.device_descriptor();
+        let configurations: Vec<Vec<u8>> = device
+            .configurations()
+            .map(|c| c.as_bytes().to_vec())
+            .collect();
+
+        let language = device
+            .get_string_descriptor_supported_languages(timeout)
+            .wait()
+            .ok()
+            .and_then(|mut langs| langs.next());
+
+        let mut indexes = vec![
+            device_descriptor.manufacturer_string_index(),
+            device_descriptor.product_string_index(),
+            device_descriptor.serial_number_string_index(),
+        ];
+        for config in &configurations {
+            let Some(config) = ConfigurationDescriptor::new(config) else {
+                continue;
+            };
+            indexes.push(config.string_index());
+            for desc in config.descriptors() {
+                match desc.descriptor_type() {
+                    DESCRIPTOR_TYPE_INTERFACE if desc.len() >= 9 => {
+                        indexes.push(NonZeroU8::new(desc[8]))
+                    }
+                    DESCRIPTOR_TYPE_INTERFACE_ASSOCIATION if desc.len() >= 8 => {
+                        indexes.push(NonZeroU8::new(desc[7]))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut strings = BTreeMap::new();
+        if let Some(language) = language {
+            for index in indexes.into_iter().flatten() {
+                if strings.contains_key(&index.get()) {
+                    continue;
+                }
+                if let Ok(s) = device
+                    .get_string_descriptor(index, language, timeout)
+                    .wait()
+                {
+                    strings.insert(index.get(), s);
+                }
+            }
+        }
+
+        let cached = [
+            (
+                device_descriptor.manufacturer_string_index(),
+                info.manufacturer_string(),
+            ),
+            (
+                device_descriptor.product_string_index(),
+                info.product_string(),
+            ),
+            (
+                device_descriptor.serial_number_string_index(),
+                info.serial_number(),
+            ),
+        ];
+        for (index, value) in cached {
+            if let (Some(index), Some(value)) = (index, value) {
+                strings
+                    .entry(index.get())
+                    .or_insert_with(|| value.to_owned());
+            }
+        }
+
+        let bos = if device_descriptor.usb_version() >= 0x0201 {
+            device
+                .get_descriptor(DESCRIPTOR_TYPE_BOS, 0, 0, timeout)
+                .wait()
+                .ok()
+                .filter(|d| d.len() >= 5 && d[1] == DESCRIPTOR_TYPE_BOS)
+        } else {
+            None
+        };
+
+        VerboseReport {
+            bus_id: info.bus_id().to_owned(),
+            device_address: info.device_address(),
+            device_descriptor,
+            configurations,
+            bos,
+            strings,
+        }
+    }
+
+    fn string(&self, index: u8) -> &str {
+        self.strings.get(&index).map_or("", |s| s.as_str())
+    }
+}
+
+impl Display for VerboseReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let d = &self.device_descriptor;
+        let index = |i: Option<NonZeroU8>| i.map_or(0, NonZeroU8::get);
+        let manufacturer = index(d.manufacturer_string_index());
+        let product = index(d.product_string_index());
+        let serial = index(d.serial_number_string_index());
+
+        writeln!(
+            f,
+            "Bus {} Device {:03}: ID {:04x}:{:04x} {} {}",
+            self.bus_id,
+            self.device_address,
+            d.vendor_id(),
+            d.product_id(),
+            self.string(manufacturer),
+            self.string(product),
+        )?;
+
+        writeln!(f, "Device Descriptor:")?;
+        field(f, 2, "bLength", d.as_bytes()[0], "")?;
+        field(f, 2, "bDescriptorType", DESCRIPTOR_TYPE_DEVICE, "")?;
+        field(f, 2, "bcdUSB", Bcd(d.usb_version()), "")?;
+        described(f, 2, "bDeviceClass", d.class(), class_name(d.class()))?;
+        described(f, 2, "bDeviceSubClass", d.subclass(), "")?;
+        described(f, 2, "bDeviceProtocol", d.protocol(), "")?;
+        field(f, 2, "bMaxPacketSize0", d.max_packet_size_0(), "")?;
+        described(
+            f,
+            2,
+            "idVendor",
+            format_args!("0x{:04x}", d.vendor_id()),
+            self.string(manufacturer),
+        )?;
+        described(
+            f,
+            2,
+            "idProduct",
+            format_args!("0x{:04x}", d.product_id()),
+            self.string(product),
+        )?;
+        field(f, 2, "bcdDevice", Bcd(d.device_version()), "")?;
+        for (name, index) in [
+            ("iManufacturer", manufacturer),
+            ("iProduct", product),
+            ("iSerial", serial),
+        ] {
+            described(f, 2, name, index, self.string(index))?;
+        }
+        field(f, 2, "bNumConfigurations", d.num_configurations(), "")?;
+
+        for config in &self.configurations {
+            if let Some(config) = ConfigurationDescriptor::new(config) {
+                self.fmt_configuration(f, &config)?;
+            }
+        }
+
+        if let Some(bos) = &self.bos {
+            fmt_bos(f, bos)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl VerboseReport {
+    fn fmt_configuration(&self, f: &mut Formatter<'_>, c: &ConfigurationDescriptor) -> Result {
+        let bytes = c.as_bytes();
+        let attributes = c.attributes();
+        let power_unit = if self.device_descriptor.usb_version() >= 0x0300 {
+            8
+        } else {
+            2
+        };
+
+        writeln!(f, "  Configuration Descriptor:")?;
+        field(f, 4, "bLength", bytes[0], "")?;
+        field(f, 4, "bDescriptorType", DESCRIPTOR_TYPE_CONFIGURATION, "")?;
+        field(f, 4, "wTotalLength", Hex16(bytes.len() as u16), "")?;
+        field(f, 4, "bNumInterfaces", c.num_interfaces(), "")?;
+        field(f, 4, "bConfigurationValue", c.configuration_value(), "")?;
+        described(f, 4, "iConfiguration", bytes[6], self.string(bytes[6]))?;
+        field(f, 4, "bmAttributes", Hex8(attributes), "")?;
+        if attributes & 0x80 == 0 {
+            writeln!(f, "      (Missing must-be-set bit!)")?;
+        }
+        if attributes & 0x40 != 0 {
+            writeln!(f, "      Self Powered")?;
+        } else {
+            writeln!(f, "      (Bus Powered)")?;
+        }
+        if attributes & 0x20 != 0 {
+            writeln!(f, "      Remote Wakeup")?;
+        }
+        if attributes & 0x10 != 0 {
+            writeln!(f, "      Battery Powered")?;
+        }
+        field(f, 4, "MaxPower", c.max_power() as u32 * power_unit, "mA")?;
+
+        // Descriptors following an interface or endpoint descriptor are
+        // nested under it.
+        let mut indent = 4;
+        let mut interface_class = None;
+        let mut endpoint_type = None;
+
+        for desc in c.descriptors() {
+            match desc.descriptor_type() {
+                DESCRIPTOR_TYPE_INTERFACE if desc.len() >= 9 => {
+                    interface_class = Some(desc[5]);
+                    endpoint_type = None;
+                    self.fmt_interface(f, &desc)?;
+                    indent = 6;
+                }
+                DESCRIPTOR_TYPE_ENDPOINT if desc.len() >= 7 => {
+                    let ep = EndpointDescriptor::new_unchecked(&desc);
+                    endpoint_type = Some(ep.transfer_type());
+                    fmt_endpoint(f, &ep)?;
+                    indent = 8;
+                }
+                DESCRIPTOR_TYPE_SUPERSPEED_COMPANION if desc.len() >= 6 && indent == 8 => {
+                    fmt_superspeed_companion(f, &desc, endpoint_type)?;
+                }
+                DESCRIPTOR_TYPE_INTERFACE_ASSOCIATION if desc.len() >= 8 => {
+                    // An association groups the interfaces that follow, so it
+                    // is never nested under the previous interface.
+                    interface_class = None;
+                    endpoint_type = None;
+                    self.fmt_interface_association(f, &desc)?;
+                    indent = 4;
+                }
+                DESCRIPTOR_TYPE_HID if interface_class == Some(CLASS_HID) && desc.len() >= 6 => {
+                    fmt_hid(f, indent, &desc)?;
+                }
+                DESCRIPTOR_TYPE_CS_INTERFACE
+                    if interface_class == Some(CLASS_COMMUNICATIONS) && desc.len() >= 3 =>
+                {
+                    fmt_cdc(f, indent, &desc)?;
+                }
+                _ => unrecognized(f, indent, &desc)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fmt_interface(&self, f: &mut Formatter<'_>, desc: &Descriptor) -> Result {
+        writeln!(f, "    Interface Descriptor:")?;
+        field(f, 6, "bLength", desc[0], "")?;
+        field(f, 6, "bDescriptorType", desc[1], "")?;
+        field(f, 6, "bInterfaceNumber", desc[2], "")?;
+        field(f, 6, "bAlternateSetting", desc[3], "")?;
+        field(f, 6, "bNumEndpoints", desc[4], "")?;
+        described(f, 6, "bInterfaceClass", desc[5], class_name(desc[5]))?;
+        described(f, 6, "bInterfaceSubClass", desc[6], "")?;
+        described(f, 6, "bInterfaceProtocol", desc[7], "")?;
+        described(f, 6, "iInterface", desc[8], self.string(desc[8]))
+    }
+
+    fn fmt_interface_association(&self, f: &mut Formatter<'_>, desc: &Descriptor) -> Result {
+        writeln!(f, "    Interface Association:")?;
+        field(f, 6, "bLength", desc[0], "")?;
+        field(f, 6, "bDescriptorType", desc[1], "")?;
+        field(f, 6, "bFirstInterface", desc[2], "")?;
+        field(f, 6, "bInterfaceCount", desc[3], "")?;
+        described(f, 6, "bFunctionClass", desc[4], class_name(desc[4]))?;
+        described(f, 6, "bFunctionSubClass", desc[5], "")?;
+        described(f, 6, "bFunctionProtocol", desc[6], "")?;
+        described(f, 6, "iFunction", desc[7], self.string(desc[7]))
+    }
+}
+
+fn fmt_endpoint(f: &mut Formatter<'_>, ep: &EndpointDescriptor) -> Result {
+    let attributes = ep.attributes();
+    let transfer_type = match ep.transfer_type() {
+        TransferType::Control => "Control",
+        TransferType::Isochronous => "Isochronous",
+        TransferType::Bulk => "Bulk",
+        TransferType::Interrupt => "Interrupt",
+    };
+    let synch_type =
+        ["None", "Asynchronous", "Adaptive", "Synchronous"][(attributes as usize >> 2) & 3];
+    let usage_type = ["Data", "Feedback", "Implicit feedback Data", "(reserved)"]
+        [(attributes as usize >> 4) & 3];
+    let direction = if ep.address() & 0x80 != 0 {
+        "IN"
+    } else {
+        "OUT"
+    };
+
+    writeln!(f, "      Endpoint Descriptor:")?;
+    field(f, 8, "bLength", ep.as_bytes()[0], "")?;
+    field(f, 8, "bDescriptorType", DESCRIPTOR_TYPE_ENDPOINT, "")?;
+    field(
+        f,
+        8,
+        "bEndpointAddress",
+        Hex8(ep.address()),
+        &format!("  EP {} {direction}", ep.address() & 0x0f),
+    )?;
+    field(f, 8, "bmAttributes", attributes, "")?;
+    writeln!(f, "          Transfer Type            {transfer_type}")?;
+    writeln!(f, "          Synch Type               {synch_type}")?;
+    writeln!(f, "          Usage Type               {usage_type}")?;
+    field(
+        f,
+        8,
+        "wMaxPacketSize",
+        Hex16(ep.max_packet_size_raw()),
+        &format!(
+            "  {}x {} bytes",
+            ep.packets_per_microframe(),
+            ep.max_packet_size()
+        ),
+    )?;
+    field(f, 8, "bInterval", ep.interval(), "")
+}
+
+fn fmt_superspeed_companion(
+    f: &mut Formatter<'_>,
+    desc: &Descriptor,
+    endpoint_type: Option<TransferType>,
+) -> Result {
+    field(f, 8, "bMaxBurst", desc[2], "")?;
+    match endpoint_type {
+        Some(TransferType::Bulk) if desc[3] & 0x1f != 0 => {
+            field(f, 8, "MaxStreams", 1u32 << (desc[3] & 0x1f), "")
+        }
+        Some(TransferType::Isochronous) if desc[3] & 0x03 != 0 => {
+            field(f, 8, "Mult", desc[3] & 0x03, "")
+        }
+        _ => Ok(()),
+    }
+}
+
+fn fmt_hid(f: &mut Formatter<'_>, indent: usize, desc: &Descriptor) -> Result {
+    writeln!(f, "{:indent$}HID Device Descriptor:", "")?;
+    let indent = indent + 2;
+    field(f, indent, "bLength", desc[0], "")?;
+    field(f, indent, "bDescriptorType", desc[1], "")?;
+    field(
+        f,
+        indent,
+        "bcdHID",
+        Bcd(u16::from_le_bytes([desc[2], desc[3]])),
+        "",
+    )?;
+    described(
+        f,
+        indent,
+        "bCountryCode",
+        desc[4],
+        if desc[4] == 0 { "Not supported" } else { "" },
+    )?;
+    field(f, indent, "bNumDescriptors", desc[5], "")?;
+    for class_desc in desc[6..].chunks_exact(3).take(desc[5] as usize) {
+        let name = match class_desc[0] {
+            0x22 => "Report",
+            0x23 => "Physical",
+            _ => "",
+        };
+        described(f, indent, "bDescriptorType", class_desc[0], name)?;
+        field(
+            f,
+            indent,
+            "wDescriptorLength",
+            u16::from_le_bytes([class_desc[1], class_desc[2]]),
+            "",
+        )?;
+    }
+    Ok(())
+}
+
+fn fmt_cdc(f: &mut Formatter<'_>, indent: usize, desc: &Descriptor) -> Result {
+    let field_indent = indent + 2;
+    match (desc[2], desc.len()) {
+        (0x00, 5..) => {
+            writeln!(f, "{:indent$}CDC Header:", "")?;
+            field(
+                f,
+                field_indent,
+                "bcdCDC",
+                Bcd(u16::from_le_bytes([desc[3], desc[4]])),
+                "",
+            )
+        }
+        (0x01, 5..) => {
+            writeln!(f, "{:indent$}CDC Call Management:", "")?;
+            field(f, field_indent, "bmCapabilities", Hex8(desc[3]), "")?;
+            if desc[3] & 0x01 != 0 {
+                writeln!(f, "{:w$}call management", "", w = field_indent + 2)?;
+            }
+            if desc[3] & 0x02 != 0 {
+                writeln!(f, "{:w$}use DataInterface", "", w = field_indent + 2)?;
+            }
+            field(f, field_indent, "bDataInterface", desc[4], "")
+        }
+        (0x02, 4..) => {
+            writeln!(f, "{:indent$}CDC ACM:", "")?;
+            field(f, field_indent, "bmCapabilities", Hex8(desc[3]), "")?;
+            let capabilities = [
+                "get/set/clear comm features",
+                "line coding and serial state",
+                "sends break",
+                "connection notifications",
+            ];
+            for (bit, name) in capabilities.iter().enumerate() {
+                if desc[3] & (1 << bit) != 0 {
+                    writeln!(f, "{:w$}{name}", "", w = field_indent + 2)?;
+                }
+            }
+            Ok(())
+        }
+        (0x06, 5..) => {
+            writeln!(f, "{:indent$}CDC Union:", "")?;
+            field(f, field_indent, "bMasterInterface", desc[3], "")?;
+            field(f, field_indent, "bSlaveInterface", desc[4], "")?;
+            for slave in &desc[5..] {
+                writeln!(f, "{:w$}{slave}", "", w = field_indent + 20)?;
+            }
+            Ok(())
+        }
+        _ => unrecognized(f, indent, desc),
+    }
+}
+
+fn fmt_bos(f: &mut Formatter<'_>, bos: &[u8]) -> Result {
+    writeln!(f, "Binary Object Store Descriptor:")?;
+    field(f, 2, "bLength", bos[0], "")?;
+    field(f, 2, "bDescriptorType", bos[1], "")?;
+    field(
+        f,
+        2,
+        "wTotalLength",
+        Hex16(u16::from_le_bytes([bos[2], bos[3]])),
+        "",
+    )?;
+    field(f, 2, "bNumDeviceCaps", bos[4], "")?;
+
+    let caps = bos.get(bos[0] as usize..).unwrap_or_default();
+
+    for desc in DescriptorIter::new(caps) {
+        if desc.descriptor_type() != DESCRIPTOR_TYPE_DEVICE_CAPABILITY || desc.len() < 3 {
+            unrecognized(f, 2, &desc)?;
+            continue;
+        }
+
+        match (desc[2], desc.len()) {
+            (0x02, 7..) => {
+                let attributes = u32::from_le_bytes([desc[3], desc[4], desc[5], desc[6]]);
+                writeln!(f, "  USB 2.0 Extension Device Capability:")?;
+                fmt_capability_header(f, &desc)?;
+                field(f, 4, "bmAttributes", format_args!("0x{attributes:08x}"), "")?;
+                if attributes & 0x02 != 0 {
+                    writeln!(f, "      Link Power Management (LPM) Supported")?;
+                }
+            }
+            (0x03, 10..) => {
+                let speeds = u16::from_le_bytes([desc[4], desc[5]]);
+                let speed_names = [
+                    "Low Speed (1Mbps)",
+                    "Full Speed (12Mbps)",
+                    "High Speed (480Mbps)",
+                    "SuperSpeed (5Gbps)",
+                ];
+                writeln!(f, "  SuperSpeed USB Device Capability:")?;
+                fmt_capability_header(f, &desc)?;
+                field(f, 4, "bmAttributes", Hex8(desc[3]), "")?;
+                if desc[3] & 0x02 != 0 {
+                    writeln!(f, "      Latency Tolerance Messages (LTM) Supported")?;
+                }
+                field(f, 4, "wSpeedsSupported", Hex16(speeds), "")?;
+                for (bit, name) in speed_names.iter().enumerate() {
+                    if speeds & (1 << bit) != 0 {
+                        writeln!(f, "      Device can operate at {name}")?;
+                    }
+                }
+                field(f, 4, "bFunctionalitySupport", desc[6], "")?;
+                if let Some(name) = speed_names.get(desc[6] as usize) {
+                    writeln!(f, "      Lowest fully-functional device speed is {name}")?;
+                }
+                field(f, 4, "bU1DevExitLat", desc[7], " micro seconds")?;
+                field(
+                    f,
+                    4,
+                    "bU2DevExitLat",
+                    u16::from_le_bytes([desc[8], desc[9]]),
+                    " micro seconds",
+                )?;
+            }
+            (0x04, 20..) => {
+                let id = &desc[4..20];
+                writeln!(f, "  Container ID Device Capability:")?;
+                fmt_capability_header(f, &desc)?;
+                field(f, 4, "bReserved", desc[3], "")?;
+                writeln!(
+                    f,
+                    "    ContainerID             {{{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}}}",
+                    id[3], id[2], id[1], id[0], id[5], id[4], id[7], id[6],
+                    id[8], id[9], id[10], id[11], id[12], id[13], id[14], id[15]
+                )?;
+            }
+            _ => unrecognized(f, 2, &desc)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn fmt_capability_header(f: &mut Formatter<'_>, desc: &Descriptor) -> Result {
+    field(f, 4, "bLength", desc[0], "")?;
+    field(f, 4, "bDescriptorType", desc[1], "")?;
+    field(f, 4, "bDevCapabilityType", desc[2], "")
+}
+
+fn unrecognized(f: &mut Formatter<'_>, indent: usize, desc: &Descriptor) -> Result {
+    write!(f, "{:indent$}** UNRECOGNIZED: ", "")?;
+    for b in desc.iter() {
+        write!(f, " {b:02x}")?;
+    }
+    writeln!(f)
+}
+
+/// Write a field name with its value right-aligned at `lsusb`'s value column.
+fn field(
+    f: &mut Formatter<'_>,
+    indent: usize,
+    name: &str,
+    value: impl Display,
+    suffix: &str,
+) -> Result {
+    let value = value.to_string();
+    let width = VALUE_COLUMN.saturating_sub(name.len()).max(value.len() + 1);
+    writeln!(f, "{:indent$}{name}{value:>width$}{suffix}", "")
+}
+
+/// Write a field followed by a space and its description, which may be empty.
+fn described(
+    f: &mut Formatter<'_>,
+    indent: usize,
+    name: &str,
+    value: impl Display,
+    description: &str,
+) -> Result {
+    field(f, indent, name, value, &format!(" {description}"))
+}
+
+struct Bcd(u16);
+
+impl Display for Bcd {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{:x}.{:02x}", self.0 >> 8, self.0 & 0xff)
+    }
+}
+
+struct Hex8(u8);
+
+impl Display for Hex8 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "0x{:02x}", self.0)
+    }
+}
+
+struct Hex16(u16);
+
+impl Display for Hex16 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "0x{:04x}", self.0)
+    }
+}
+
+/// Name of a [USB-IF defined class code](https://www.usb.org/defined-class-codes)
+/// as shown by `lsusb`.
+fn class_name(class: u8) -> &'static str {
+    match class {
+        0x00 => "[unknown]",
+        0x01 => "Audio",
+        0x02 => "Communications",
+        0x03 => "Human Interface Device",
+        0x05 => "Physical Interface Device",
+        0x06 => "Imaging",
+        0x07 => "Printer",
+        0x08 => "Mass Storage",
+        0x09 => "Hub",
+        0x0a => "CDC Data",
+        0x0b => "Chip/SmartCard",
+        0x0d => "Content Security",
+        0x0e => "Video",
+        0x0f => "Personal Healthcare",
+        0x10 => "Audio/Video",
+        0x11 => "Billboard",
+        0x12 => "Type-C Bridge",
+        0xdc => "Diagnostic",
+        0xe0 => "Wireless",
+        0xef => "Miscellaneous Device",
+        0xfe => "Application Specific Interface",
+        0xff => "Vendor Specific Class",
+        _ => "",
+    }
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_report() {
+    let report = VerboseReport {
+        bus_id: "001".into(),
+        device_address: 1,
+        device_descriptor: DeviceDescriptor::new(&[
+            0x12, 0x01, 0x00, 0x02, 0x09, 0x00, 0x01, 0x40, 0x6b,
+            0x1d, 0x02, 0x00, 0x10, 0x05, 0x03, 0x02, 0x01, 0x01
+        ]).unwrap(),
+        configurations: vec![vec![
+            0x09, 0x02, 0x19, 0x00, 0x01, 0x01, 0x00, 0xe0, 0x00,
+            0x09, 0x04, 0x00, 0x00, 0x01, 0x09, 0x00, 0x00, 0x00,
+            0x07, 0x05, 0x81, 0x03, 0x04, 0x00, 0x0c
+        ]],
+        bos: None,
+        strings: [(2, "EHCI Host Controller".into()), (3, "Linux 5.16.0 ehci_hcd".into())].into(),
+    };
+
+    let expected = "\
+Bus 001 Device 001: ID 1d6b:0002 Linux 5.16.0 ehci_hcd EHCI Host Controller
+Device Descriptor:
+  bLength                18
+  bDescriptorType         1
+  bcdUSB               2.00
+  bDeviceClass            9 Hub
+  bDeviceSubClass         0
+  bDeviceProtocol         1
+  bMaxPacketSize0        64
+  idVendor           0x1d6b Linux 5.16.0 ehci_hcd
+  idProduct          0x0002 EHCI Host Controller
+  bcdDevice            5.10
+  iManufacturer           3 Linux 5.16.0 ehci_hcd
+  iProduct                2 EHCI Host Controller
+  iSerial                 1
+  bNumConfigurations      1
+  Configuration Descriptor:
+    bLength                 9
+    bDescriptorType         2
+    wTotalLength       0x0019
+    bNumInterfaces          1
+    bConfigurationValue     1
+    iConfiguration          0
+    bmAttributes         0xe0
+      Self Powered
+      Remote Wakeup
+    MaxPower                0mA
+    Interface Descriptor:
+      bLength                 9
+      bDescriptorType         4
+      bInterfaceNumber        0
+      bAlternateSetting       0
+      bNumEndpoints           1
+      bInterfaceClass         9 Hub
+      bInterfaceSubClass      0
+      bInterfaceProtocol      0
+      iInterface              0
+      Endpoint Descriptor:
+        bLength                 7
+        bDescriptorType         5
+        bEndpointAddress     0x81  EP 1 IN
+        bmAttributes            3
+          Transfer Type            Interrupt
+          Synch Type               None
+          Usage Type               Data
+        wMaxPacketSize     0x0004  1x 4 bytes
+        bInterval              12
+";
+
+    // `lsusb` leaves a trailing space after an empty description
+    let report = report.to_string();
+    let lines: Vec<&str> = report.lines().map(str::trim_end).collect();
+    assert_eq!(lines, expected.lines().collect::<Vec<_>>());
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_report_interface_association() {
+    let report = VerboseReport {
+        bus_id: "001".into(),
+        device_address: 2,
+        device_descriptor: DeviceDescriptor::new(&[
+            0x12, 0x01, 0x00, 0x02, 0xef, 0x02, 0x01, 0x40, 0x34,
+            0x12, 0x78, 0x56, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01
+        ]).unwrap(),
+        configurations: vec![vec![
+            0x09, 0x02, 0x2a, 0x00, 0x02, 0x01, 0x00, 0x80, 0x32,
+            0x09, 0x04, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x00,
+            0x07, 0x05, 0x81, 0x02, 0x00, 0x02, 0x00,
+            0x08, 0x0b, 0x01, 0x01, 0x0e, 0x03, 0x00, 0x00,
+            0x09, 0x04, 0x01, 0x00, 0x00, 0x0e, 0x01, 0x00, 0x00,
+        ]],
+        bos: None,
+        strings: BTreeMap::new(),
+    };
+
+    let report = report.to_string();
+    let lines: Vec<&str> = report.lines().map(str::trim_end).collect();
+    let start = lines.iter().position(|l| l.trim() == "Interface Association:").unwrap();
+    assert_eq!(lines[start..start + 10], [
+        "    Interface Association:",
+        "      bLength                 8",
+        "      bDescriptorType        11",
+        "      bFirstInterface         1",
+        "      bInterfaceCount         1",
+        "      bFunctionClass         14 Video",
+        "      bFunctionSubClass       3",
+        "      bFunctionProtocol       0",
+        "      iFunction               0",
+        "    Interface Descriptor:",
+    ]);
+}
@@ -4,6 +4,9 @@ use std::{
     task::{Context, Poll},
 };
 
+#[cfg(not(target_arch = "wasm32"))]
+use std::{pin::pin, task::Waker};
+
 /// IO that may be performed synchronously or asynchronously.
 ///
 /// A `MaybeFuture` can be run asynchronously with `.await`, or
@@ -202,3 +205,100 @@ impl<F: Future, T: FnOnce(F::Output) -> R, R> Future for MapFut<F, T> {
         })
     }
 }
+
+/// How the inner actions of a [`Sequence`] are performed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    /// The sequence is being run by [`MaybeFuture::wait`].
+    #[cfg(not(target_arch = "wasm32"))]
+    Blocking,
+
+    /// The sequence is being awaited.
+    Async,
+}
+
+impl Mode {
+    /// Perform an inner action, waiting on it in blocking mode, or awaiting
+    /// it in async mode.
+    pub(crate) async fn run<M: MaybeFuture>(self, action: M) -> M::Output {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Mode::Blocking => action.wait(),
+            Mode::Async => action.await,
+        }
+    }
+}
+
+/// A `MaybeFuture` that performs a sequence of other actions, written once
+/// as an `async` block.
+///
+/// When awaited, the inner actions are awaited. With `wait()`, the inner
+/// actions use their blocking implementation, so the sequence completes in a
+/// single poll and does not need an async runtime.
+pub(crate) struct Sequence<F>(F);
+
+impl<F> Sequence<F> {
+    pub(crate) fn new(f: F) -> Self {
+        Sequence(f)
+    }
+}
+
+impl<F: FnOnce(Mode) -> Fut, Fut: Future> IntoFuture for Sequence<F> {
+    type Output = Fut::Output;
+    type IntoFuture = Fut;
+
+    fn into_future(self) -> Self::IntoFuture {
+        (self.0)(Mode::Async)
+    }
+}
+
+impl<F: FnOnce(Mode) -> Fut + NonWasmSend, Fut: Future + NonWasmSend> MaybeFuture for Sequence<F> {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn wait(self) -> Self::Output {
+        let fut = pin!((self.0)(Mode::Blocking));
+        match fut.poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(r) => r,
+            Poll::Pending => unreachable!("blocking sequence returned Pending"),
+        }
+    }
+}
+
+/// Block the current thread until a future that is already in progress
+/// completes.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+    struct Unpark(std::thread::Thread);
+
+    impl std::task::Wake for Unpark {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(std::sync::Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = pin!(fut);
+    loop {
+        if let Poll::Ready(r) = fut.as_mut().poll(&mut cx) {
+            return r;
+        }
+        std::thread::park();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn test_sequence() {
+    let seq = |n: u32| {
+        Sequence::new(move |mode: Mode| async move {
+            let a = mode.run(Ready(n)).await;
+            let b = mode.run(Ready(1).map(|x: u32| x + 1)).await;
+            (mode, a + b)
+        })
+    };
+    assert_eq!(seq(1).wait(), (Mode::Blocking, 3));
+    assert_eq!(
+        futures_lite::future::block_on(seq(2).into_future()),
+        (Mode::Async, 4)
+    );
+}
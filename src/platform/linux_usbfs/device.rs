@@ -2,20 +2,22 @@ use std::{
     collections::{BTreeMap, VecDeque},
     ffi::c_void,
     fs::File,
-    io::{Read, Seek},
-    mem::ManuallyDrop,
+    future::Future,
+    io::{IoSlice, Read, Seek},
+    mem::{ManuallyDrop, MaybeUninit},
+    pin::Pin,
     sync::{
-        atomic::{AtomicU8, Ordering},
+        atomic::{AtomicU64, AtomicU8, Ordering},
         Arc, Mutex, MutexGuard, Weak,
     },
-    task::{Context, Poll},
+    task::{Context, Poll, Waker},
     time::{Duration, Instant},
 };
 
 use log::{debug, error, warn};
 use rustix::{
     event::epoll::EventFlags,
-    fd::{AsFd, AsRawFd, FromRawFd, OwnedFd},
+    fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
     fs::Timespec,
     io::Errno,
     time::{timerfd_create, timerfd_settime, Itimerspec, TimerfdFlags, TimerfdTimerFlags},
@@ -23,7 +25,7 @@ use rustix::{
 use slab::Slab;
 
 use super::{
-    errno_to_transfer_error, events,
+    errno_to_transfer_error, events, fd_passing,
     usbfs::{self, Urb},
     TransferData,
 };
@@ -46,15 +48,24 @@ use crate::{
             notify_completion, take_completed_from_queue, Idle, Notify, Pending, TransferFuture,
         },
         request_type, Buffer, Completion, ControlIn, ControlOut, ControlType, Direction, Recipient,
-        TransferError,
+        TransferError, SETUP_PACKET_SIZE,
     },
-    DeviceInfo, Error, ErrorKind, Speed,
+    Capabilities, DeviceInfo, Error, ErrorKind, Speed, UsbfsCapabilities,
 };
 
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 struct TimeoutEntry {
     deadline: Instant,
-    urb: *mut Urb,
+    target: TimeoutTarget,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum TimeoutTarget {
+    /// Cancel the URB when the deadline expires.
+    Urb(*mut Urb),
+
+    /// Wake the `LinuxDelay` with this ID when the deadline expires.
+    Delay(u64),
 }
 
 unsafe impl Send for TimeoutEntry {}
@@ -74,8 +85,14 @@ pub(crate) struct LinuxDevice {
 
     active_config: AtomicU8,
 
+    /// Queried once when the device is opened.
+    capabilities: Capabilities,
+
     timerfd: OwnedFd,
-    timeouts: Mutex<BTreeMap<TimeoutEntry, ()>>,
+
+    /// Pending deadlines, with the waker for `TimeoutTarget::Delay` entries.
+    timeouts: Mutex<BTreeMap<TimeoutEntry, Option<Waker>>>,
+    next_delay_id: AtomicU64,
 }
 
 impl LinuxDevice {
@@ -116,6 +133,24 @@ impl LinuxDevice {
         Blocking::new(move || unimplemented!())
     }
 
+    /// Receive a usbfs fd sent with `send_fd` and wrap it.
+    pub(crate) fn from_socket(
+        socket: BorrowedFd,
+    ) -> impl MaybeFuture<Output = Result<Arc<LinuxDevice>, Error>> {
+        let socket = socket.try_clone_to_owned();
+        Blocking::new(move || {
+            let socket = socket
+                .map_err(|e| Error::new_io(ErrorKind::Other, "failed to duplicate socket", e))?;
+            let fd = fd_passing::recv_fd(socket.as_fd())?;
+            debug!("Received fd {} as usbfs device", fd.as_raw_fd());
+            Self::create_inner(
+                fd,
+                #[cfg(not(target_os = "android"))]
+                None,
+            )
+        })
+    }
+
     pub(crate) fn from_fd(
         fd: OwnedFd,
     ) -> impl MaybeFuture<Output = Result<Arc<LinuxDevice>, Error>> {
@@ -164,12 +199,29 @@ impl LinuxDevice {
         #[cfg(target_os = "android")]
         let active_config = guess_active_configuration(&fd, &descriptors);
 
+        Self::from_parts(
+            fd,
+            descriptors,
+            #[cfg(not(target_os = "android"))]
+            sysfs,
+            active_config,
+        )
+    }
+
+    pub(super) fn from_parts(
+        fd: OwnedFd,
+        descriptors: Vec<u8>,
+        #[cfg(not(target_os = "android"))] sysfs: Option<SysfsPath>,
+        active_config: u8,
+    ) -> Result<Arc<LinuxDevice>, Error> {
         let timerfd = timerfd_create(
             rustix::time::TimerfdClockId::Monotonic,
             TimerfdFlags::CLOEXEC | TimerfdFlags::NONBLOCK,
         )
         .map_err(|e| Error::new_os(ErrorKind::Other, "failed to create timerfd", e).log_error())?;
 
+        let capabilities = query_capabilities(&fd);
+
         let arc = Arc::new_cyclic(|weak| {
             let events_id = DEVICES.lock().unwrap().insert(weak.clone());
             LinuxDevice {
@@ -179,8 +231,10 @@ impl LinuxDevice {
                 #[cfg(not(target_os = "android"))]
                 sysfs,
                 active_config: AtomicU8::new(active_config),
+                capabilities,
                 timerfd,
                 timeouts: Mutex::new(BTreeMap::new()),
+                next_delay_id: AtomicU64::new(0),
             }
         });
 
@@ -216,6 +270,7 @@ impl LinuxDevice {
         debug!("Handling events for device {}", self.events_id);
         match usbfs::reap_urb_ndelay(&self.fd) {
             Ok(urb) => {
+                let reaped = Instant::now();
                 let transfer_data: *mut TransferData = unsafe { &(*urb) }.usercontext.cast();
 
                 {
@@ -231,12 +286,26 @@ impl LinuxDevice {
 
                     if let Some(deadline) = transfer.deadline {
                         let mut timeouts = self.timeouts.lock().unwrap();
-                        timeouts.remove(&TimeoutEntry { deadline, urb });
+                        let expired = timeouts
+                            .remove(&TimeoutEntry {
+                                deadline,
+                                target: TimeoutTarget::Urb(urb),
+                            })
+                            .is_none();
                         self.update_timeouts(timeouts, Instant::now());
+
+                        // The entry is only removed early by `handle_timeouts`,
+                        // so a cancelled URB without one was cancelled by the
+                        // timeout rather than by the user.
+                        let status = transfer.urb().status.abs();
+                        if expired && status == Errno::NOENT.raw_os_error() {
+                            unsafe { (*urb).status = -Errno::TIMEDOUT.raw_os_error() };
+                        }
                     }
                 };
 
                 // SAFETY: pointer came from submit via kernel and we're now done with it
+                unsafe { (*transfer_data).completed = Some(reaped) };
                 unsafe { notify_completion::<super::TransferData>(transfer_data) }
             }
             Err(Errno::AGAIN) => {}
@@ -268,26 +337,39 @@ impl LinuxDevice {
 
         rustix::io::read(self.timerfd.as_fd(), &mut [0u8; 8]).ok();
 
+        let mut expired = Vec::new();
         let mut timeouts = self.timeouts.lock().unwrap();
         while let Some(entry) = timeouts.first_entry() {
             if entry.key().deadline > now {
                 break;
             }
 
-            let urb = entry.remove_entry().0.urb;
-
-            unsafe {
-                match usbfs::discard_urb(&self.fd, urb) {
-                    Ok(()) => debug!("Cancelled URB {urb:?} after timeout"),
-                    Err(e) => debug!("Failed to cancel timed out URB {urb:?}: {e}"),
-                }
+            match entry.remove_entry() {
+                (
+                    TimeoutEntry {
+                        target: TimeoutTarget::Urb(urb),
+                        ..
+                    },
+                    _,
+                ) => unsafe {
+                    match usbfs::discard_urb(&self.fd, urb) {
+                        Ok(()) => debug!("Cancelled URB {urb:?} after timeout"),
+                        Err(e) => debug!("Failed to cancel timed out URB {urb:?}: {e}"),
+                    }
+                },
+                (_, waker) => expired.extend(waker),
             }
         }
 
         self.update_timeouts(timeouts, now);
+        expired.into_iter().for_each(Waker::wake);
     }
 
-    fn update_timeouts(&self, timeouts: MutexGuard<BTreeMap<TimeoutEntry, ()>>, now: Instant) {
+    fn update_timeouts(
+        &self,
+        timeouts: MutexGuard<BTreeMap<TimeoutEntry, Option<Waker>>>,
+        now: Instant,
+    ) {
         const TIMESPEC_ZERO: Timespec = Timespec {
             tv_sec: 0,
             tv_nsec: 0,
@@ -366,6 +448,10 @@ impl LinuxDevice {
         })
     }
 
+    pub(crate) fn allocate(&self, len: usize) -> Result<Buffer, Errno> {
+        Buffer::mmap(&self.fd, len)
+    }
+
     pub(crate) fn reset(self: Arc<Self>) -> impl MaybeFuture<Output = Result<(), Error>> {
         Blocking::new(move || {
             usbfs::reset(&self.fd).map_err(|e| match e {
@@ -402,6 +488,24 @@ impl LinuxDevice {
         })
     }
 
+    pub(crate) fn delay(self: &Arc<Self>, timeout: Duration) -> LinuxDelay {
+        LinuxDelay {
+            device: self.clone(),
+            deadline: Instant::now().checked_add(timeout),
+            id: None,
+        }
+    }
+
+    pub(crate) fn control_queue(self: Arc<Self>) -> LinuxControlQueue {
+        LinuxControlQueue {
+            device: self,
+            notify: Arc::new(Notify::new()),
+            pending: VecDeque::new(),
+            idle_in: None,
+            idle_out: None,
+        }
+    }
+
     fn handle_claim_interface_result(
         self: Arc<Self>,
         interface_number: u8,
@@ -477,8 +581,9 @@ impl LinuxDevice {
         })
     }
 
-    pub(crate) fn submit(&self, transfer: Idle<TransferData>) -> Pending<TransferData> {
+    pub(crate) fn submit(&self, mut transfer: Idle<TransferData>) -> Pending<TransferData> {
         let len = transfer.urb().buffer_length;
+        transfer.submitted = Some(Instant::now());
         let pending = transfer.pre_submit();
         let urb = pending.urb_ptr();
 
@@ -494,6 +599,7 @@ impl LinuxDevice {
                 debug!("Failed to submit URB {urb:?}: {len} bytes on ep {ep:x}: {e} {u:?}");
                 u.actual_length = 0;
                 u.status = e.raw_os_error();
+                (*pending.as_ptr()).completed = Some(Instant::now());
                 notify_completion::<super::TransferData>(pending.as_ptr().cast());
             } else {
                 debug!("Submitted URB {urb:?}: {len} bytes on ep {ep:x}");
@@ -510,7 +616,10 @@ impl LinuxDevice {
     ) -> Pending<TransferData> {
         let urb = transfer.urb_ptr();
         let now = Instant::now();
-        let deadline = now + timeout;
+        let Some(deadline) = now.checked_add(timeout) else {
+            // Too far in the future to ever expire
+            return self.submit(transfer);
+        };
         transfer.deadline = Some(deadline);
 
         // Hold the lock across `submit`, so that it can't complete before we
@@ -522,7 +631,8 @@ impl LinuxDevice {
         // This can only be false if submit failed, because we hold the timeouts lock
         // and would block the completion handler.
         if !r.is_complete() {
-            timeouts.insert(TimeoutEntry { deadline, urb }, ());
+            let target = TimeoutTarget::Urb(urb);
+            timeouts.insert(TimeoutEntry { deadline, target }, None);
             self.update_timeouts(timeouts, now);
         }
 
@@ -540,6 +650,57 @@ impl LinuxDevice {
         }
     }
 
+    pub(crate) fn drop_privileges(&self, interface_mask: u32) -> Result<(), Error> {
+        usbfs::drop_privileges(&self.fd, interface_mask).map_err(|e| match e {
+            Errno::NOTTY => Error::new_os(
+                ErrorKind::Unsupported,
+                "USBDEVFS_DROP_PRIVILEGES not supported by kernel",
+                e,
+            ),
+            Errno::NODEV => Error::new_os(ErrorKind::Disconnected, "device disconnected", e),
+            _ => Error::new_os(ErrorKind::Other, "failed to drop privileges", e),
+        })
+    }
+
+    pub(crate) fn forbid_suspend(&self) -> Result<(), Error> {
+        usbfs::forbid_suspend(&self.fd).map_err(|e| suspend_error(e, "failed to forbid suspend"))
+    }
+
+    pub(crate) fn allow_suspend(&self) -> Result<(), Error> {
+        usbfs::allow_suspend(&self.fd).map_err(|e| suspend_error(e, "failed to allow suspend"))
+    }
+
+    pub(crate) fn wait_for_resume(self: Arc<Self>) -> impl MaybeFuture<Output = Result<(), Error>> {
+        Blocking::new(move || {
+            usbfs::wait_for_resume(&self.fd)
+                .map_err(|e| suspend_error(e, "failed to wait for resume"))
+        })
+    }
+
+    /// Send the usbfs fd to the process on the other end of a Unix socket.
+    pub(crate) fn send_fd(&self, socket: BorrowedFd) -> Result<(), Error> {
+        use rustix::net::{sendmsg, SendAncillaryBuffer, SendAncillaryMessage, SendFlags};
+
+        let fds = [self.fd.as_fd()];
+        let mut space = [MaybeUninit::uninit(); rustix::cmsg_space!(ScmRights(1))];
+        let mut control = SendAncillaryBuffer::new(&mut space);
+        control.push(SendAncillaryMessage::ScmRights(&fds));
+
+        // At least one byte of data must accompany the ancillary message.
+        sendmsg(
+            socket,
+            &[IoSlice::new(&[0])],
+            &mut control,
+            SendFlags::NOSIGNAL,
+        )
+        .map_err(|e| Error::new_os(ErrorKind::Other, "failed to send device fd", e))?;
+        Ok(())
+    }
+
+    pub(crate) fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
     pub(crate) fn speed(&self) -> Option<Speed> {
         usbfs::get_speed(&self.fd)
             .inspect_err(|e| log::error!("USBDEVFS_GET_SPEED failed: {e}"))
@@ -556,6 +717,28 @@ impl LinuxDevice {
     }
 }
 
+fn query_capabilities(fd: &OwnedFd) -> Capabilities {
+    let caps = usbfs::get_capabilities(fd)
+        .inspect_err(|e| log::debug!("USBDEVFS_GET_CAPABILITIES failed: {e}"))
+        .map(UsbfsCapabilities)
+        .ok();
+
+    let memory_limit = std::fs::read_to_string("/sys/module/usbcore/parameters/usbfs_memory_mb")
+        .ok()
+        .and_then(|s| s.trim().parse::<usize>().ok())
+        .filter(|&mb| mb != 0)
+        .map(|mb| mb * 1024 * 1024);
+
+    Capabilities {
+        zero_copy: caps.is_some_and(|c| c.mmap()),
+        // Kernels without USBDEVFS_CAP_NO_PACKET_SIZE_LIM reject transfers
+        // over 16 KiB. If the capabilities are unknown, assume no limit.
+        max_transfer_size: caps.filter(|c| !c.no_packet_size_limit()).map(|_| 16384),
+        memory_limit,
+        usbfs: caps,
+    }
+}
+
 fn read_all_from_fd(fd: &OwnedFd) -> Result<Vec<u8>, std::io::Error> {
     let mut file = unsafe { ManuallyDrop::new(File::from_raw_fd(fd.as_raw_fd())) };
     file.seek(std::io::SeekFrom::Start(0))?;
@@ -656,6 +839,10 @@ impl LinuxInterface {
         self.device.clone().control_out(data, timeout)
     }
 
+    pub(crate) fn control_queue(&self) -> LinuxControlQueue {
+        self.device.clone().control_queue()
+    }
+
     pub fn get_alt_setting(&self) -> u8 {
         self.state.lock().unwrap().alt_setting
     }
@@ -762,6 +949,10 @@ impl LinuxEndpoint {
         self.inner.address
     }
 
+    pub(crate) fn interface(&self) -> &Arc<LinuxInterface> {
+        &self.inner.interface
+    }
+
     pub(crate) fn pending(&self) -> usize {
         self.pending.len()
     }
@@ -774,13 +965,21 @@ impl LinuxEndpoint {
         }
     }
 
+    /// Cancel the pending transfer at `index` in submission order.
+    pub(crate) fn cancel(&mut self, index: usize) {
+        self.inner.interface.device.cancel(&mut self.pending[index]);
+    }
+
     fn get_transfer(&mut self) -> Idle<TransferData> {
-        self.idle_transfer.take().unwrap_or_else(|| {
+        let mut transfer = self.idle_transfer.take().unwrap_or_else(|| {
             Idle::new(
                 self.inner.clone(),
                 super::TransferData::new(self.inner.address, self.inner.ep_type),
             )
-        })
+        });
+        // Clear the deadline from a previous `submit_timeout`
+        transfer.deadline = None;
+        transfer
     }
 
     pub(crate) fn submit(&mut self, data: Buffer) {
@@ -790,6 +989,17 @@ impl LinuxEndpoint {
             .push_back(self.inner.interface.device.submit(transfer));
     }
 
+    pub(crate) fn submit_timeout(&mut self, data: Buffer, timeout: Duration) {
+        let mut transfer = self.get_transfer();
+        transfer.set_buffer(data);
+        self.pending.push_back(
+            self.inner
+                .interface
+                .device
+                .submit_timeout(transfer, timeout),
+        );
+    }
+
     pub(crate) fn submit_err(&mut self, data: Buffer, error: TransferError) {
         assert_eq!(error, TransferError::InvalidArgument);
         let mut transfer = self.get_transfer();
@@ -819,6 +1029,10 @@ impl LinuxEndpoint {
         })
     }
 
+    pub(crate) fn delay(&self, timeout: Duration) -> LinuxDelay {
+        self.inner.interface.device.delay(timeout)
+    }
+
     pub(crate) fn clear_halt(&self) -> impl MaybeFuture<Output = Result<(), Error>> {
         let inner = self.inner.clone();
         Blocking::new(move || {
@@ -832,7 +1046,7 @@ impl LinuxEndpoint {
     }
 
     pub(crate) fn allocate(&self, len: usize) -> Result<Buffer, Errno> {
-        Buffer::mmap(&self.inner.interface.device.fd, len).inspect_err(|e| {
+        self.inner.interface.device.allocate(len).inspect_err(|e| {
             warn!(
                 "Failed to allocate zero-copy buffer of length {len} for endpoint {}: {e}",
                 self.inner.address
@@ -854,6 +1068,104 @@ impl Drop for LinuxEndpoint {
     }
 }
 
+/// Control transfers on endpoint 0 with caller-provided buffers.
+///
+/// The buffers passed to `submit` start with the SETUP packet, which is
+/// removed from the buffer of the completion.
+pub(crate) struct LinuxControlQueue {
+    device: Arc<LinuxDevice>,
+    notify: Arc<Notify>,
+
+    /// A queue of pending transfers, expected to complete in order
+    pending: VecDeque<Pending<TransferData>>,
+
+    idle_in: Option<Idle<TransferData>>,
+    idle_out: Option<Idle<TransferData>>,
+}
+
+impl LinuxControlQueue {
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub(crate) fn cancel_all(&mut self) {
+        for transfer in self.pending.iter_mut().rev() {
+            self.device.cancel(transfer);
+        }
+    }
+
+    pub(crate) fn allocate(&self, len: usize) -> Buffer {
+        let len = len + SETUP_PACKET_SIZE;
+        self.device
+            .allocate(len)
+            .unwrap_or_else(|_| Buffer::new(len))
+    }
+
+    pub(crate) fn submit(&mut self, direction: Direction, data: Buffer, timeout: Duration) {
+        let (idle, address) = match direction {
+            Direction::In => (&mut self.idle_in, 0x80),
+            Direction::Out => (&mut self.idle_out, 0x00),
+        };
+        let mut transfer = idle.take().unwrap_or_else(|| {
+            Idle::new(
+                self.notify.clone(),
+                TransferData::new(address, TransferType::Control),
+            )
+        });
+        transfer.deadline = None;
+        transfer.set_buffer(data);
+        self.pending
+            .push_back(self.device.submit_timeout(transfer, timeout));
+    }
+
+    pub(crate) fn poll_next_complete(&mut self, cx: &mut Context) -> Poll<Completion> {
+        self.notify.subscribe(cx);
+        match take_completed_from_queue(&mut self.pending) {
+            Some(transfer) => Poll::Ready(self.complete(transfer)),
+            None => Poll::Pending,
+        }
+    }
+
+    pub(crate) fn wait_next_complete(&mut self, timeout: Duration) -> Option<Completion> {
+        self.notify
+            .wait_timeout(timeout, || take_completed_from_queue(&mut self.pending))
+            .map(|transfer| self.complete(transfer))
+    }
+
+    fn complete(&mut self, mut transfer: Idle<TransferData>) -> Completion {
+        let direction = Direction::from_address(transfer.urb().endpoint);
+        let mut completion = transfer.take_completion();
+
+        // Remove the SETUP packet so that the buffer holds only the data
+        let buf = &mut completion.buffer;
+        let len = match direction {
+            Direction::In => completion.actual_len,
+            Direction::Out => (buf.len as usize).saturating_sub(SETUP_PACKET_SIZE),
+        };
+        unsafe { std::ptr::copy(buf.ptr.add(SETUP_PACKET_SIZE), buf.ptr, len) };
+        buf.len = len as u32;
+        buf.requested_len = buf.requested_len.saturating_sub(SETUP_PACKET_SIZE as u32);
+
+        match direction {
+            Direction::In => self.idle_in = Some(transfer),
+            Direction::Out => self.idle_out = Some(transfer),
+        }
+        completion
+    }
+}
+
+impl Drop for LinuxControlQueue {
+    fn drop(&mut self) {
+        if !self.pending.is_empty() {
+            debug!(
+                "Dropping control queue with {} pending transfers",
+                self.pending.len()
+            );
+            self.cancel_all();
+        }
+    }
+}
+
 impl AsRef<Notify> for EndpointInner {
     fn as_ref(&self) -> &Notify {
         &self.notify
@@ -866,3 +1178,238 @@ impl Drop for EndpointInner {
         state.endpoints.clear(self.address);
     }
 }
+
+/// Future that completes after a timeout, using the device's timerfd.
+pub(crate) struct LinuxDelay {
+    device: Arc<LinuxDevice>,
+
+    /// `None` if the timeout is too long to represent, and never elapses.
+    deadline: Option<Instant>,
+
+    /// Set once the deadline is registered with the device.
+    id: Option<u64>,
+}
+
+impl Future for LinuxDelay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = Pin::into_inner(self);
+        let Some(deadline) = this.deadline else {
+            return Poll::Pending;
+        };
+        let now = Instant::now();
+        if now >= deadline {
+            return Poll::Ready(());
+        }
+
+        let device = &this.device;
+        let mut timeouts = device.timeouts.lock().unwrap();
+        match this.id {
+            Some(id) => {
+                let key = TimeoutEntry {
+                    deadline,
+                    target: TimeoutTarget::Delay(id),
+                };
+                match timeouts.get_mut(&key) {
+                    Some(waker) => *waker = Some(cx.waker().clone()),
+                    // Already removed by `handle_timeouts`
+                    None => return Poll::Ready(()),
+                }
+            }
+            None => {
+                let id = device.next_delay_id.fetch_add(1, Ordering::Relaxed);
+                let target = TimeoutTarget::Delay(id);
+                timeouts.insert(TimeoutEntry { deadline, target }, Some(cx.waker().clone()));
+                this.id = Some(id);
+                device.update_timeouts(timeouts, now);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for LinuxDelay {
+    fn drop(&mut self) {
+        if let (Some(deadline), Some(id)) = (self.deadline, self.id) {
+            // The timerfd may still fire for this deadline, which is harmless.
+            self.device.timeouts.lock().unwrap().remove(&TimeoutEntry {
+                deadline,
+                target: TimeoutTarget::Delay(id),
+            });
+        }
+    }
+}
+
+fn suspend_error(e: Errno, message: &'static str) -> Error {
+    match e {
+        Errno::NOTTY => Error::new_os(
+            ErrorKind::Unsupported,
+            "usbfs suspend control not supported by kernel",
+            e,
+        ),
+        Errno::NODEV => Error::new_os(ErrorKind::Disconnected, "device disconnected", e),
+        _ => Error::new_os(ErrorKind::Other, message, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Waker;
+
+    use futures_lite::future::block_on;
+
+    use super::*;
+    use crate::{
+        platform::fake::FakeDevice,
+        transfer::{Bulk, In},
+    };
+
+    #[test]
+    fn delay() {
+        let fake = FakeDevice::new();
+        let ep = fake.interface().endpoint::<Bulk, In>(0x81).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut long = ep.delay(Duration::from_secs(60));
+        assert!(Pin::new(&mut long).poll(&mut cx).is_pending());
+        let device = long.device.clone();
+        assert_eq!(device.timeouts.lock().unwrap().len(), 1);
+
+        // The timerfd is re-armed for an earlier deadline
+        let start = Instant::now();
+        block_on(ep.delay(Duration::from_millis(50)));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(50), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+
+        drop(long);
+        assert!(device.timeouts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn urb_timeout() {
+        let fake = FakeDevice::new();
+        let mut ep = fake.interface().endpoint::<Bulk, In>(0x81).unwrap();
+
+        // The timerfd fires and the cancelled URB is reported as timed out
+        ep.submit_with_timeout(ep.allocate(64), Duration::from_millis(50), 1);
+        let c = ep.wait_next_complete(Duration::from_secs(5)).unwrap();
+        assert_eq!((c.status, c.tag), (Err(TransferError::TimedOut), 1));
+        assert_eq!(fake.submitted(0x81), 0);
+
+        // Cancelling before the deadline is still reported as cancelled
+        ep.submit_with_timeout(ep.allocate(64), Duration::from_secs(60), 0);
+        ep.cancel_all();
+        let c = ep.wait_next_complete(Duration::from_secs(5)).unwrap();
+        assert_eq!(c.status, Err(TransferError::Cancelled));
+    }
+
+    #[test]
+    fn control_queue_strips_setup() {
+        use crate::transfer::{ControlIn, ControlOut, ControlType, Recipient};
+
+        let fake = FakeDevice::new();
+        let mut queue = fake.device().control_queue();
+        let timeout = Duration::from_secs(5);
+
+        let request = ControlIn {
+            control_type: ControlType::Vendor,
+            recipient: Recipient::Device,
+            request: 1,
+            value: 2,
+            index: 3,
+            length: 16,
+        };
+        let id = queue.submit_in(request, queue.allocate(16), timeout);
+        fake.complete_in(0x80, &[1, 2, 3]);
+        let c = queue.wait_next_complete(timeout).unwrap();
+        assert_eq!((c.status, c.id), (Ok(()), id));
+        assert_eq!(&c.buffer[..], &[1, 2, 3]);
+        assert_eq!(c.buffer.requested_len(), 16);
+
+        // The buffer is re-used for an OUT transfer
+        let request = ControlOut {
+            control_type: ControlType::Vendor,
+            recipient: Recipient::Device,
+            request: 4,
+            value: 5,
+            index: 6,
+            data: &[7, 8, 9, 10],
+        };
+        queue.submit_out(request, c.buffer, timeout);
+        let sent = fake.complete_out(0x00);
+        assert_eq!(sent[..SETUP_PACKET_SIZE], request.setup_packet());
+        assert_eq!(&sent[SETUP_PACKET_SIZE..], &[7, 8, 9, 10]);
+        let c = queue.wait_next_complete(timeout).unwrap();
+        assert_eq!(c.status, Ok(()));
+        assert_eq!(&c.buffer[..], &[7, 8, 9, 10]);
+    }
+
+    #[test]
+    fn control_command_without_runtime() {
+        use std::future::IntoFuture;
+
+        use crate::transfer::{ControlIn, ControlOut, ControlType, Recipient, StatusPoll};
+
+        let fake = FakeDevice::new();
+        let device = fake.device();
+        let command = ControlOut {
+            control_type: ControlType::Vendor,
+            recipient: Recipient::Device,
+            request: 0x40,
+            value: 0,
+            index: 0,
+            data: &[1],
+        };
+        let status = ControlIn {
+            control_type: ControlType::Vendor,
+            recipient: Recipient::Device,
+            request: 0x41,
+            value: 0,
+            index: 0,
+            length: 1,
+        };
+        let poll =
+            StatusPoll::new(Duration::from_millis(20), Duration::from_secs(5)).stall_retries(1);
+
+        // The wait between polls uses the device's timer, so awaiting this
+        // doesn't need an async runtime
+        let res = std::thread::scope(|s| {
+            s.spawn(|| {
+                let sent = fake.complete_out(0x00);
+                assert_eq!(sent[..SETUP_PACKET_SIZE], command.setup_packet());
+                assert_eq!(&sent[SETUP_PACKET_SIZE..], &[1]);
+                fake.fail(0x80, Errno::PIPE);
+                fake.complete_in(0x80, &[1]);
+                fake.complete_in(0x80, &[0]);
+            });
+            let start = Instant::now();
+            let res = block_on(
+                device
+                    .control_command(command, status, |s| s[0] == 0, poll)
+                    .into_future(),
+            );
+            assert!(start.elapsed() >= Duration::from_millis(40));
+            res
+        });
+        assert_eq!(res, Ok(vec![0]));
+    }
+
+    #[test]
+    fn from_socket_reports_sender_errors() {
+        use std::os::unix::net::UnixStream;
+
+        use crate::Device;
+
+        let (server, client) = UnixStream::pair().unwrap();
+        fd_passing::send_error(server.as_fd(), ErrorKind::Busy).unwrap();
+        let err = Device::from_socket(&client).wait().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Busy);
+
+        // The peer closes without sending anything
+        drop(server);
+        let err = Device::from_socket(&client).wait().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+    }
+}
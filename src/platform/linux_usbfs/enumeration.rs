@@ -1,9 +1,10 @@
 use log::debug;
 use log::warn;
+use rustix::io::Errno;
 use std::fs;
 use std::io;
 use std::num::ParseIntError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use crate::enumeration::InterfaceInfo;
@@ -64,6 +65,29 @@ impl SysfsPath {
         self.parse_attr(attr, |s| s.parse())
     }
 
+    /// Read an attribute that is not cached in `DeviceInfo`, converting errors
+    /// to the public error type.
+    pub(crate) fn read_attr_with<T>(
+        &self,
+        attr: &str,
+        parse: impl FnOnce(&str) -> Option<T>,
+    ) -> Result<T, Error> {
+        self.parse_attr(attr, |s| parse(s).ok_or(()))
+            .map_err(|SysfsError(path, e)| match e {
+                SysfsErrorKind::Io(e) => {
+                    debug!("Failed to read sysfs attribute {path:?}: {e}");
+                    Error::new_io(ErrorKind::Other, "failed to read sysfs attribute", e)
+                }
+                SysfsErrorKind::Parse(v) => {
+                    debug!(
+                        "Unexpected value {:?} in sysfs attribute {path:?}",
+                        v.trim()
+                    );
+                    Error::new(ErrorKind::Other, "unexpected sysfs attribute value")
+                }
+            })
+    }
+
     fn read_attr_hex<T: FromHexStr>(&self, attr: &str) -> Result<T, SysfsError> {
         self.parse_attr(attr, |s| T::from_hex_str(s.strip_prefix("0x").unwrap_or(s)))
     }
@@ -84,6 +108,48 @@ impl SysfsPath {
         })?
     }
 
+    /// Write a control attribute such as `authorized` or `bind`.
+    pub(crate) fn write_attr(&self, attr: &str, value: &str) -> Result<(), Error> {
+        let attr_path = self.0.join(attr);
+        debug!("Writing {value:?} to {attr_path:?}");
+        fs::write(&attr_path, value).map_err(|e| match Errno::from_io_error(&e) {
+            Some(Errno::ACCESS | Errno::PERM) => Error::new_io(
+                ErrorKind::PermissionDenied,
+                "sysfs attribute permission denied",
+                e,
+            ),
+            Some(Errno::NOENT) => {
+                Error::new_io(ErrorKind::NotFound, "sysfs attribute not found", e)
+            }
+            Some(Errno::NODEV) => Error::new_io(ErrorKind::Disconnected, "device disconnected", e),
+            Some(Errno::BUSY) => Error::new_io(ErrorKind::Busy, "device or driver is busy", e),
+            _ => Error::new_io(ErrorKind::Other, "failed to write sysfs attribute", e),
+        })
+    }
+
+    /// Find the sysfs directory of an interface of the device's active
+    /// configuration, such as `1-6:1.0`.
+    pub(crate) fn interface(&self, interface_number: u8) -> Result<SysfsPath, Error> {
+        self.children()
+            .filter(|i| {
+                i.0.file_name()
+                    .unwrap_or_default()
+                    .as_encoded_bytes()
+                    .contains(&b':')
+            })
+            .find(|i| i.read_attr_hex::<u8>("bInterfaceNumber").ok() == Some(interface_number))
+            .ok_or(Error::new(ErrorKind::NotFound, "interface not found"))
+    }
+
+    /// Name of the directory, e.g. `1-6` for a device or `1-6:1.0` for an
+    /// interface.
+    pub(crate) fn name(&self) -> &str {
+        self.0
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+    }
+
     fn children(&self) -> impl Iterator<Item = SysfsPath> {
         fs::read_dir(&self.0)
             .ok()
@@ -111,6 +177,31 @@ impl FromHexStr for u16 {
     }
 }
 
+impl FromHexStr for u32 {
+    fn from_hex_str(s: &str) -> Result<Self, ParseIntError> {
+        u32::from_str_radix(s, 16)
+    }
+}
+
+/// Sysfs directory of a USB interface driver, e.g. `/sys/bus/usb/drivers/cdc_acm`.
+pub fn sysfs_usb_driver(driver: &str) -> Result<SysfsPath, Error> {
+    driver_in(Path::new("/sys/bus/usb/drivers"), driver)
+}
+
+/// Find a driver's directory in `drivers`. A driver that isn't loaded is
+/// reported the same as a malformed name, rather than as a missing sysfs
+/// attribute of the device.
+fn driver_in(drivers: &Path, driver: &str) -> Result<SysfsPath, Error> {
+    if driver.is_empty() || driver.contains('/') || driver.starts_with('.') {
+        return Err(Error::new(ErrorKind::InvalidInput, "invalid driver name"));
+    }
+    let path = drivers.join(driver);
+    if !path.is_dir() {
+        return Err(Error::new(ErrorKind::InvalidInput, "unknown driver"));
+    }
+    Ok(SysfsPath(path))
+}
+
 fn sysfs_list_usb() -> Result<fs::ReadDir, Error> {
     fs::read_dir("/sys/bus/usb/devices/").map_err(|e| match e.kind() {
         io::ErrorKind::NotFound => {
@@ -197,10 +288,10 @@ pub fn probe_device(path: SysfsPath) -> Result<DeviceInfo, SysfsError> {
     let busnum = path.read_attr("busnum")?;
     let device_address = path.read_attr("devnum")?;
 
-    let port_chain = path
-        .read_attr::<String>("devpath")
-        .ok()
-        .filter(|p| p != "0") // root hub should be empty but devpath is 0
+    let devpath = path.read_attr::<String>("devpath").unwrap_or_default();
+
+    let port_chain = Some(&devpath)
+        .filter(|p| *p != "0") // root hub should be empty but devpath is 0
         .and_then(|p| {
             p.split('.')
                 .map(|v| v.parse::<u8>().ok())
@@ -210,6 +301,33 @@ pub fn probe_device(path: SysfsPath) -> Result<DeviceInfo, SysfsError> {
 
     Ok(DeviceInfo {
         busnum,
+        devpath,
+        num_configurations: path.read_attr("bNumConfigurations").ok(),
+        // empty when the device is unconfigured
+        configuration_value: path.read_attr("bConfigurationValue").ok(),
+        max_power: path
+            .parse_attr("bMaxPower", |s| s.trim_end_matches("mA").parse())
+            .ok(),
+        removable: path
+            .read_attr::<String>("removable")
+            .ok()
+            .and_then(|s| match s.as_str() {
+                "removable" => Some(true),
+                "fixed" => Some(false),
+                _ => None,
+            }),
+        authorized: path.read_attr::<u8>("authorized").map_or(true, |v| v != 0),
+        avoid_reset_quirk: path
+            .read_attr::<u8>("avoid_reset_quirk")
+            .is_ok_and(|v| v != 0),
+        quirks: path.read_attr_hex("quirks").unwrap_or(0),
+        ltm_capable: path
+            .read_attr::<String>("ltm_capable")
+            .is_ok_and(|s| s == "yes"),
+        lanes: path
+            .read_attr("tx_lanes")
+            .and_then(|tx| Ok((tx, path.read_attr("rx_lanes")?)))
+            .ok(),
         bus_id: format!("{busnum:03}"),
         device_address,
         port_chain,
@@ -252,6 +370,8 @@ pub fn probe_device(path: SysfsPath) -> Result<DeviceInfo, SysfsError> {
                         subclass: i.read_attr_hex("bInterfaceSubClass").ok()?,
                         protocol: i.read_attr_hex("bInterfaceProtocol").ok()?,
                         interface_string: i.read_attr("interface").ok(),
+                        alternate_setting: i.read_attr("bAlternateSetting").unwrap_or(0),
+                        driver: i.readlink_attr_filename("driver").ok(),
                     })
                 })
                 .collect();
@@ -261,3 +381,155 @@ pub fn probe_device(path: SysfsPath) -> Result<DeviceInfo, SysfsError> {
         path,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PowerControl, RuntimeStatus};
+
+    /// A directory laid out like a sysfs USB device, removed when dropped.
+    struct FakeSysfs(PathBuf);
+
+    impl FakeSysfs {
+        fn new(name: &str, attrs: &[(&str, &str)]) -> FakeSysfs {
+            let dir = std::env::temp_dir().join(format!("nusb-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            let device = [
+                ("busnum", "1"),
+                ("devnum", "4"),
+                ("idVendor", "1234"),
+                ("idProduct", "5678"),
+                ("bcdDevice", "0100"),
+                ("version", " 2.00"),
+                ("bDeviceClass", "00"),
+                ("bDeviceSubClass", "00"),
+                ("bDeviceProtocol", "00"),
+            ];
+            for (attr, value) in device.iter().chain(attrs) {
+                let path = dir.join(attr);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, format!("{value}\n")).unwrap();
+            }
+            FakeSysfs(dir)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+
+        fn probe(&self) -> DeviceInfo {
+            probe_device(SysfsPath(self.0.clone())).unwrap()
+        }
+    }
+
+    impl Drop for FakeSysfs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn power_attributes_are_read_on_each_call() {
+        let sysfs = FakeSysfs::new(
+            "power",
+            &[
+                ("power/control", "auto"),
+                ("power/autosuspend_delay_ms", "2000"),
+                ("power/runtime_status", "active"),
+            ],
+        );
+        let info = sysfs.probe();
+        assert_eq!(info.power_control().unwrap(), PowerControl::Auto);
+        assert_eq!(
+            info.autosuspend_delay().unwrap(),
+            Some(std::time::Duration::from_secs(2))
+        );
+        assert_eq!(info.runtime_status().unwrap(), RuntimeStatus::Active);
+
+        let power = sysfs.path().join("power");
+        fs::write(power.join("control"), "on\n").unwrap();
+        fs::write(power.join("autosuspend_delay_ms"), "-1\n").unwrap();
+        fs::write(power.join("runtime_status"), "suspended\n").unwrap();
+        assert_eq!(info.power_control().unwrap(), PowerControl::On);
+        assert_eq!(info.autosuspend_delay().unwrap(), None);
+        assert_eq!(info.runtime_status().unwrap(), RuntimeStatus::Suspended);
+
+        fs::remove_file(power.join("control")).unwrap();
+        assert!(info.power_control().is_err());
+    }
+
+    #[test]
+    fn device_attributes() {
+        let sysfs = FakeSysfs::new(
+            "attributes",
+            &[
+                ("bNumConfigurations", "2"),
+                ("bConfigurationValue", "1"),
+                ("bMaxPower", "500mA"),
+                ("removable", "removable"),
+                ("authorized", "0"),
+                ("avoid_reset_quirk", "1"),
+                ("quirks", "0x400"),
+                ("ltm_capable", "yes"),
+                ("tx_lanes", "2"),
+                ("rx_lanes", "1"),
+                ("1-4:1.0/bInterfaceNumber", "00"),
+                ("1-4:1.0/bInterfaceClass", "ff"),
+                ("1-4:1.0/bInterfaceSubClass", "00"),
+                ("1-4:1.0/bInterfaceProtocol", "00"),
+                ("1-4:1.0/bAlternateSetting", " 1"),
+            ],
+        );
+        std::os::unix::fs::symlink(
+            "../../../bus/usb/drivers/usbfs",
+            sysfs.path().join("1-4:1.0/driver"),
+        )
+        .unwrap();
+
+        let info = sysfs.probe();
+        assert_eq!(info.num_configurations(), Some(2));
+        assert_eq!(info.configuration_value(), Some(1));
+        assert_eq!(info.max_power(), Some(500));
+        assert_eq!(info.removable(), Some(true));
+        assert!(!info.authorized());
+        assert!(info.avoid_reset_quirk());
+        assert_eq!(info.quirks(), 0x400);
+        assert!(info.ltm_capable());
+        assert_eq!(info.lanes(), Some((2, 1)));
+
+        let interface = info.interfaces().next().unwrap();
+        assert_eq!(interface.class(), 0xff);
+        assert_eq!(interface.alternate_setting(), 1);
+        assert_eq!(interface.driver(), Some("usbfs"));
+    }
+
+    #[test]
+    fn missing_optional_attributes() {
+        let sysfs = FakeSysfs::new("missing", &[("removable", "unknown")]);
+        let info = sysfs.probe();
+        assert_eq!(info.num_configurations(), None);
+        assert_eq!(info.configuration_value(), None);
+        assert_eq!(info.max_power(), None);
+        assert_eq!(info.removable(), None);
+        assert!(info.authorized());
+        assert!(!info.avoid_reset_quirk());
+        assert_eq!(info.quirks(), 0);
+        assert!(!info.ltm_capable());
+        assert_eq!(info.lanes(), None);
+        assert_eq!(info.interfaces().count(), 0);
+    }
+
+    #[test]
+    fn driver_names() {
+        let sysfs = FakeSysfs::new("drivers", &[("usbfs/bind", "")]);
+        let drivers = sysfs.path();
+        assert_eq!(
+            driver_in(drivers, "usbfs").unwrap().0,
+            drivers.join("usbfs")
+        );
+        for name in ["nonexistent", "", "..", "usbfs/bind"] {
+            let err = driver_in(drivers, name).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput, "{name:?}");
+        }
+    }
+}
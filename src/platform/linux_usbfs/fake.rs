@@ -0,0 +1,394 @@
+//! In-process stand-in for a usbfs device, for testing the transfer and IO
+//! code without hardware.
+//!
+//! The device fd is an eventfd, so the event thread and timerfd work as
+//! usual. The usbfs ioctls used by transfers are intercepted in `usbfs.rs`
+//! and handled here, and the test completes the submitted URBs.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use rustix::{
+    event::{eventfd, EventfdFlags},
+    fd::{AsRawFd, BorrowedFd, OwnedFd, RawFd},
+    io::{self, Errno},
+};
+
+use super::{
+    usbfs::{Urb, USBDEVFS_URB_TYPE_CONTROL},
+    Device as LinuxDevice,
+};
+use crate::{transfer::SETUP_PACKET_SIZE, Device, Interface};
+
+/// Interface 0 with bulk endpoints 0x01 and 0x81 and interrupt endpoints 0x02
+/// and 0x82, all with a max packet size of 64.
+#[rustfmt::skip]
+const DESCRIPTORS: &[u8] = &[
+    0x12, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x40, 0x34, 0x12, 0x78, 0x56, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
+    0x09, 0x02, 0x2e, 0x00, 0x01, 0x01, 0x00, 0x80, 0x32,
+    0x09, 0x04, 0x00, 0x00, 0x04, 0xff, 0x00, 0x00, 0x00,
+    0x07, 0x05, 0x01, 0x02, 0x40, 0x00, 0x00,
+    0x07, 0x05, 0x81, 0x02, 0x40, 0x00, 0x00,
+    0x07, 0x05, 0x02, 0x03, 0x40, 0x00, 0x01,
+    0x07, 0x05, 0x82, 0x03, 0x40, 0x00, 0x01,
+];
+
+/// The eventfd is writable, and so reported to the event thread, unless its
+/// counter is at this value.
+const NOT_READY: u64 = u64::MAX - 1;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+static DEVICES: Mutex<BTreeMap<RawFd, Arc<FakeState>>> = Mutex::new(BTreeMap::new());
+
+struct UrbPtr(*mut Urb);
+
+// SAFETY: the URB is owned by the transfer until it is reaped, and only
+// accessed under the `FakeState` lock in the meantime.
+unsafe impl Send for UrbPtr {}
+
+#[derive(Default)]
+struct Urbs {
+    submitted: VecDeque<UrbPtr>,
+    reapable: VecDeque<UrbPtr>,
+    disconnected: bool,
+    suspend_allowed: bool,
+    suspended: bool,
+
+    /// Endpoints passed to the clear halt ioctl, in order.
+    halts_cleared: Vec<u8>,
+
+    /// Discarded URBs stay submitted, as if the host controller never
+    /// returned them.
+    hold_discards: bool,
+
+    /// The eventfd counter is 0 rather than `NOT_READY`.
+    ready: bool,
+}
+
+struct FakeState {
+    /// Duplicate of the device's eventfd, to report reapable URBs.
+    fd: OwnedFd,
+    urbs: Mutex<Urbs>,
+    changed: Condvar,
+}
+
+impl FakeState {
+    fn lock(&self) -> MutexGuard<'_, Urbs> {
+        self.urbs.lock().unwrap()
+    }
+
+    /// Report the device fd to the event thread while URBs can be reaped.
+    fn update_ready(&self, urbs: &mut Urbs) {
+        let ready = !urbs.reapable.is_empty() || urbs.disconnected;
+        if ready && !urbs.ready {
+            io::read(&self.fd, &mut [0; 8]).unwrap();
+        } else if !ready && urbs.ready {
+            io::write(&self.fd, &NOT_READY.to_ne_bytes()).unwrap();
+        }
+        urbs.ready = ready;
+    }
+
+    fn reap(&self, mut urbs: MutexGuard<Urbs>, urb: UrbPtr) {
+        urbs.reapable.push_back(urb);
+        self.update_ready(&mut urbs);
+        self.changed.notify_all();
+    }
+}
+
+fn state(fd: BorrowedFd) -> Option<Arc<FakeState>> {
+    DEVICES.lock().unwrap().get(&fd.as_raw_fd()).cloned()
+}
+
+pub(super) fn claim_or_release(fd: BorrowedFd) -> Option<io::Result<()>> {
+    state(fd).map(|_| Ok(()))
+}
+
+pub(super) fn submit_urb(fd: BorrowedFd, urb: *mut Urb) -> Option<io::Result<()>> {
+    let state = state(fd)?;
+    let mut urbs = state.lock();
+    if urbs.disconnected {
+        return Some(Err(Errno::NODEV));
+    }
+    urbs.submitted.push_back(UrbPtr(urb));
+    state.changed.notify_all();
+    Some(Ok(()))
+}
+
+pub(super) fn discard_urb(fd: BorrowedFd, urb: *mut Urb) -> Option<io::Result<()>> {
+    let state = state(fd)?;
+    let mut urbs = state.lock();
+    let Some(i) = urbs.submitted.iter().position(|u| u.0 == urb) else {
+        return Some(Err(Errno::INVAL));
+    };
+    if urbs.hold_discards {
+        return Some(Ok(()));
+    }
+    let urb = urbs.submitted.remove(i).unwrap();
+    unsafe {
+        (*urb.0).status = -Errno::NOENT.raw_os_error();
+        (*urb.0).actual_length = 0;
+    }
+    state.reap(urbs, urb);
+    Some(Ok(()))
+}
+
+pub(super) fn reap_urb_ndelay(fd: BorrowedFd) -> Option<io::Result<*mut Urb>> {
+    let state = state(fd)?;
+    let mut urbs = state.lock();
+    let res = match urbs.reapable.pop_front() {
+        Some(urb) => Ok(urb.0),
+        None if urbs.disconnected => Err(Errno::NODEV),
+        None => Err(Errno::AGAIN),
+    };
+    state.update_ready(&mut urbs);
+    Some(res)
+}
+
+pub(super) fn clear_halt(fd: BorrowedFd, endpoint: u8) -> Option<io::Result<()>> {
+    let state = state(fd)?;
+    let mut urbs = state.lock();
+    if urbs.disconnected {
+        return Some(Err(Errno::NODEV));
+    }
+    urbs.halts_cleared.push(endpoint);
+    Some(Ok(()))
+}
+
+pub(super) fn set_suspend(fd: BorrowedFd, allow: bool) -> Option<io::Result<()>> {
+    let state = state(fd)?;
+    let mut urbs = state.lock();
+    if urbs.disconnected {
+        return Some(Err(Errno::NODEV));
+    }
+    urbs.suspend_allowed = allow;
+    if !allow {
+        urbs.suspended = false;
+    }
+    Some(Ok(()))
+}
+
+pub(super) fn wait_for_resume(fd: BorrowedFd) -> Option<io::Result<()>> {
+    let state = state(fd)?;
+    let urbs = state.lock();
+    let mut urbs = state
+        .changed
+        .wait_while(urbs, |u| u.suspended && !u.disconnected)
+        .unwrap();
+    if urbs.disconnected {
+        return Some(Err(Errno::NODEV));
+    }
+    urbs.suspend_allowed = false;
+    Some(Ok(()))
+}
+
+/// Handle to control a fake device from a test.
+pub(crate) struct FakeDevice {
+    device: Device,
+    state: Arc<FakeState>,
+    raw_fd: RawFd,
+}
+
+impl FakeDevice {
+    pub(crate) fn new() -> FakeDevice {
+        let fd = eventfd(0, EventfdFlags::CLOEXEC | EventfdFlags::NONBLOCK).unwrap();
+        let raw_fd = fd.as_raw_fd();
+        let state = Arc::new(FakeState {
+            fd: fd.try_clone().unwrap(),
+            urbs: Mutex::new(Urbs::default()),
+            changed: Condvar::new(),
+        });
+        // The eventfd starts out writable
+        state.lock().ready = true;
+        state.update_ready(&mut state.lock());
+        DEVICES.lock().unwrap().insert(raw_fd, state.clone());
+
+        let device = LinuxDevice::from_parts(fd, DESCRIPTORS.to_vec(), None, 1).unwrap();
+        FakeDevice {
+            device: Device::wrap(device),
+            state,
+            raw_fd,
+        }
+    }
+
+    pub(crate) fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub(crate) fn interface(&self) -> Interface {
+        use crate::MaybeFuture;
+        self.device.claim_interface(0).wait().unwrap()
+    }
+
+    /// Number of URBs submitted on `endpoint` that have not been completed.
+    pub(crate) fn submitted(&self, endpoint: u8) -> usize {
+        let urbs = self.state.lock();
+        urbs.submitted
+            .iter()
+            .filter(|u| unsafe { (*u.0).endpoint } == endpoint)
+            .count()
+    }
+
+    /// Wait until at least `count` URBs are submitted on `endpoint`.
+    pub(crate) fn wait_submitted(&self, endpoint: u8, count: usize) {
+        let urbs = self.state.lock();
+        let (_urbs, timeout) = self
+            .state
+            .changed
+            .wait_timeout_while(urbs, TIMEOUT, |urbs| {
+                urbs.submitted
+                    .iter()
+                    .filter(|u| unsafe { (*u.0).endpoint } == endpoint)
+                    .count()
+                    < count
+            })
+            .unwrap();
+        assert!(
+            !timeout.timed_out(),
+            "timed out waiting for {count} transfers on endpoint {endpoint:02x}"
+        );
+    }
+
+    /// Complete the oldest URB submitted on `endpoint`, waiting for it to be
+    /// submitted if necessary.
+    ///
+    /// For IN, `data` is received, and for OUT, all data is sent. Returns the
+    /// data of an OUT transfer, including the SETUP packet of a control
+    /// transfer. With an error `status`, no data is transferred.
+    fn complete_with(&self, endpoint: u8, data: &[u8], status: Option<Errno>) -> Vec<u8> {
+        self.wait_submitted(endpoint, 1);
+        let mut urbs = self.state.lock();
+        let i = urbs
+            .submitted
+            .iter()
+            .position(|u| unsafe { (*u.0).endpoint } == endpoint)
+            .unwrap();
+        let urb = urbs.submitted.remove(i).unwrap();
+
+        let sent = unsafe {
+            let u = &mut *urb.0;
+            let setup = if u.ep_type == USBDEVFS_URB_TYPE_CONTROL {
+                SETUP_PACKET_SIZE
+            } else {
+                0
+            };
+            let capacity = u.buffer_length as usize - setup;
+            u.status = status.map_or(0, |e| -e.raw_os_error());
+            if status.is_some() {
+                u.actual_length = 0;
+                Vec::new()
+            } else if endpoint & 0x80 != 0 {
+                assert!(data.len() <= capacity, "IN data larger than transfer");
+                std::ptr::copy_nonoverlapping(data.as_ptr(), u.buffer.add(setup), data.len());
+                u.actual_length = data.len() as i32;
+                Vec::new()
+            } else {
+                u.actual_length = capacity as i32;
+                std::slice::from_raw_parts(u.buffer, setup + capacity).to_vec()
+            }
+        };
+
+        self.state.reap(urbs, urb);
+        sent
+    }
+
+    /// Complete the oldest IN transfer on `endpoint` with `data`.
+    pub(crate) fn complete_in(&self, endpoint: u8, data: &[u8]) {
+        self.complete_with(endpoint, data, None);
+    }
+
+    /// Complete the oldest OUT transfer on `endpoint`, returning the data sent.
+    pub(crate) fn complete_out(&self, endpoint: u8) -> Vec<u8> {
+        self.complete_with(endpoint, &[], None)
+    }
+
+    /// Fail the oldest transfer on `endpoint` with `errno`.
+    pub(crate) fn fail(&self, endpoint: u8, errno: Errno) {
+        self.complete_with(endpoint, &[], Some(errno));
+    }
+
+    /// Number of times the halt on `endpoint` was cleared.
+    pub(crate) fn halts_cleared(&self, endpoint: u8) -> usize {
+        let urbs = self.state.lock();
+        urbs.halts_cleared
+            .iter()
+            .filter(|&&e| e == endpoint)
+            .count()
+    }
+
+    /// Set whether cancelled transfers are left pending rather than
+    /// returned.
+    pub(crate) fn hold_discards(&self, hold: bool) {
+        self.state.lock().hold_discards = hold;
+    }
+
+    /// Whether the device may be suspended, as set by the usbfs suspend
+    /// ioctls.
+    pub(crate) fn suspend_allowed(&self) -> bool {
+        self.state.lock().suspend_allowed
+    }
+
+    /// Suspend the device, which must have allowed it.
+    pub(crate) fn suspend(&self) {
+        let mut urbs = self.state.lock();
+        assert!(urbs.suspend_allowed, "suspend not allowed");
+        urbs.suspended = true;
+    }
+
+    /// Resume the device, as if by remote wakeup.
+    pub(crate) fn resume(&self) {
+        self.state.lock().suspended = false;
+        self.state.changed.notify_all();
+    }
+
+    /// Fail all submitted and future transfers as if the device was
+    /// unplugged.
+    pub(crate) fn disconnect(&self) {
+        let mut urbs = self.state.lock();
+        urbs.disconnected = true;
+        while let Some(urb) = urbs.submitted.pop_front() {
+            unsafe {
+                (*urb.0).status = -Errno::NODEV.raw_os_error();
+                (*urb.0).actual_length = 0;
+            }
+            urbs.reapable.push_back(urb);
+        }
+        self.state.update_ready(&mut urbs);
+        self.state.changed.notify_all();
+    }
+}
+
+impl Drop for FakeDevice {
+    fn drop(&mut self) {
+        DEVICES.lock().unwrap().remove(&self.raw_fd);
+    }
+}
+
+#[test]
+fn bulk_round_trip() {
+    use crate::transfer::{Buffer, Bulk, In, Out};
+
+    let fake = FakeDevice::new();
+    let interface = fake.interface();
+    let timeout = Duration::from_secs(1);
+
+    let mut ep_out = interface.endpoint::<Bulk, Out>(0x01).unwrap();
+    ep_out.submit(Buffer::from(vec![1, 2, 3]));
+    assert_eq!(fake.complete_out(0x01), [1, 2, 3]);
+    let c = ep_out.wait_next_complete(timeout).unwrap();
+    assert_eq!((c.status, c.actual_len), (Ok(()), 3));
+
+    let mut ep_in = interface.endpoint::<Bulk, In>(0x81).unwrap();
+    ep_in.submit(ep_in.allocate(64));
+    ep_in.submit(ep_in.allocate(64));
+    fake.wait_submitted(0x81, 2);
+    fake.complete_in(0x81, &[4, 5]);
+    fake.complete_in(0x81, &[6]);
+    let c = ep_in.wait_next_complete(timeout).unwrap();
+    assert_eq!(&c.buffer[..], &[4, 5]);
+    let c = ep_in.wait_next_complete(timeout).unwrap();
+    assert_eq!(&c.buffer[..], &[6]);
+}
@@ -0,0 +1,80 @@
+//! Receiving usbfs file descriptors with an error status over a Unix socket.
+//!
+//! Each message is a single status byte. On success, it is sent by
+//! `Device::send_fd` as 0, accompanied by the fd in an `SCM_RIGHTS` control
+//! message. Otherwise, it encodes the kind of error.
+
+use std::{io::IoSliceMut, mem::MaybeUninit};
+
+use rustix::{
+    fd::{BorrowedFd, OwnedFd},
+    net::{recvmsg, send, RecvAncillaryBuffer, RecvAncillaryMessage, RecvFlags, SendFlags},
+};
+
+use crate::{Error, ErrorKind};
+
+const STATUS_OK: u8 = 0;
+const STATUS_PERMISSION_DENIED: u8 = 1;
+const STATUS_NOT_FOUND: u8 = 2;
+const STATUS_BUSY: u8 = 3;
+const STATUS_DISCONNECTED: u8 = 4;
+const STATUS_OTHER: u8 = 5;
+
+/// Send an error in place of an fd.
+pub(crate) fn send_error(socket: BorrowedFd, kind: ErrorKind) -> Result<(), Error> {
+    let status = match kind {
+        ErrorKind::PermissionDenied => STATUS_PERMISSION_DENIED,
+        ErrorKind::NotFound => STATUS_NOT_FOUND,
+        ErrorKind::Busy => STATUS_BUSY,
+        ErrorKind::Disconnected => STATUS_DISCONNECTED,
+        _ => STATUS_OTHER,
+    };
+    send(socket, &[status], SendFlags::NOSIGNAL)
+        .map_err(|e| Error::new_os(ErrorKind::Other, "failed to send broker error", e))?;
+    Ok(())
+}
+
+/// Receive a usbfs fd sent with `Device::send_fd`, or the error sent with
+/// `send_error`.
+pub(crate) fn recv_fd(socket: BorrowedFd) -> Result<OwnedFd, Error> {
+    let mut space = [MaybeUninit::uninit(); rustix::cmsg_space!(ScmRights(1))];
+    let mut control = RecvAncillaryBuffer::new(&mut space);
+    let mut status = [0u8; 1];
+    let msg = recvmsg(
+        socket,
+        &mut [IoSliceMut::new(&mut status)],
+        &mut control,
+        RecvFlags::CMSG_CLOEXEC,
+    )
+    .map_err(|e| Error::new_os(ErrorKind::Other, "failed to receive device fd", e))?;
+
+    let fd = control.drain().find_map(|msg| match msg {
+        RecvAncillaryMessage::ScmRights(mut fds) => fds.next(),
+        _ => None,
+    });
+
+    if msg.bytes == 0 {
+        return Err(Error::new(
+            ErrorKind::Other,
+            "socket closed before device fd was received",
+        ));
+    }
+
+    match (status[0], fd) {
+        (STATUS_OK, Some(fd)) => Ok(fd),
+        (STATUS_PERMISSION_DENIED, _) => Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "access to device denied by sender",
+        )),
+        (STATUS_NOT_FOUND, _) => Err(Error::new(
+            ErrorKind::NotFound,
+            "device not found by sender",
+        )),
+        (STATUS_BUSY, _) => Err(Error::new(ErrorKind::Busy, "device is busy")),
+        (STATUS_DISCONNECTED, _) => Err(Error::new(ErrorKind::Disconnected, "device disconnected")),
+        _ => Err(Error::new(
+            ErrorKind::Other,
+            "sender failed to provide device fd",
+        )),
+    }
+}
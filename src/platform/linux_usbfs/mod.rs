@@ -6,11 +6,14 @@ use rustix::io::Errno;
 pub(crate) use transfer::TransferData;
 mod usbfs;
 
+#[cfg(test)]
+pub(crate) mod fake;
+
 #[cfg(not(target_os = "android"))]
 mod enumeration;
 
 #[cfg(not(target_os = "android"))]
-pub use enumeration::{list_buses, list_devices, SysfsPath};
+pub use enumeration::{list_buses, list_devices, sysfs_usb_driver, SysfsPath};
 
 #[cfg(not(target_os = "android"))]
 mod hotplug;
@@ -20,7 +23,11 @@ pub(crate) use hotplug::LinuxHotplugWatch as HotplugWatch;
 
 mod events;
 
+pub(crate) mod fd_passing;
+
 mod device;
+pub(crate) use device::LinuxControlQueue as ControlQueue;
+pub(crate) use device::LinuxDelay as Delay;
 pub(crate) use device::LinuxDevice as Device;
 pub(crate) use device::LinuxEndpoint as Endpoint;
 pub(crate) use device::LinuxInterface as Interface;
@@ -38,7 +45,8 @@ fn errno_to_transfer_error(e: Errno) -> TransferError {
     match e {
         Errno::NODEV | Errno::SHUTDOWN => TransferError::Disconnected,
         Errno::PIPE => TransferError::Stall,
-        Errno::NOENT | Errno::CONNRESET | Errno::TIMEDOUT => TransferError::Cancelled,
+        Errno::NOENT | Errno::CONNRESET => TransferError::Cancelled,
+        Errno::TIMEDOUT => TransferError::TimedOut,
         Errno::PROTO | Errno::ILSEQ | Errno::OVERFLOW | Errno::COMM | Errno::TIME => {
             TransferError::Fault
         }
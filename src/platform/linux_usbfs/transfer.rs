@@ -2,6 +2,7 @@ use std::{
     mem::{self, ManuallyDrop},
     ptr::{addr_of_mut, null_mut},
     slice,
+    sync::Arc,
     time::Instant,
 };
 
@@ -10,8 +11,8 @@ use rustix::io::Errno;
 use crate::{
     descriptors::TransferType,
     transfer::{
-        internal::Pending, Allocator, Buffer, Completion, ControlIn, ControlOut, Direction,
-        TransferError, SETUP_PACKET_SIZE,
+        internal::Pending, pool::PoolShared, Allocator, Buffer, Completion, ControlIn, ControlOut,
+        Direction, TransferError, SETUP_PACKET_SIZE,
     },
 };
 
@@ -33,7 +34,10 @@ pub struct TransferData {
     urb: *mut Urb,
     capacity: u32,
     allocator: Allocator,
+    pool: Option<Arc<PoolShared>>,
     pub(crate) deadline: Option<Instant>,
+    pub(crate) submitted: Option<Instant>,
+    pub(crate) completed: Option<Instant>,
 }
 
 unsafe impl Send for TransferData {}
@@ -67,7 +71,10 @@ impl TransferData {
             })),
             capacity: 0,
             allocator: Allocator::Default,
+            pool: None,
             deadline: None,
+            submitted: None,
+            completed: None,
         }
     }
 
@@ -88,8 +95,9 @@ impl TransferData {
         t
     }
 
-    pub fn set_buffer(&mut self, buf: Buffer) {
+    pub fn set_buffer(&mut self, mut buf: Buffer) {
         debug_assert!(self.capacity == 0);
+        self.pool = buf.pool.take();
         let buf = ManuallyDrop::new(buf);
         self.capacity = buf.capacity;
         self.urb_mut().buffer = buf.ptr;
@@ -117,17 +125,19 @@ impl TransferData {
         self.urb_mut().actual_length = 0;
         let allocator = mem::replace(&mut self.allocator, Allocator::Default);
 
-        Completion {
-            status,
-            actual_len,
-            buffer: Buffer {
-                ptr,
-                len,
-                requested_len,
-                capacity,
-                allocator,
-            },
-        }
+        let buffer = Buffer {
+            ptr,
+            len,
+            requested_len,
+            capacity,
+            allocator,
+            pool: self.pool.take(),
+        };
+
+        let mut completion = Completion::new(buffer, actual_len, status);
+        completion.submitted = self.submitted.take();
+        completion.completed = self.completed.take();
+        completion
     }
 
     #[inline]
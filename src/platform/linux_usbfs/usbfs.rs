@@ -7,10 +7,12 @@
 use std::ffi::{c_int, c_uchar, c_uint, c_void};
 
 use linux_raw_sys::ioctl::{
-    USBDEVFS_CLAIMINTERFACE, USBDEVFS_CLEAR_HALT, USBDEVFS_CONNECT, USBDEVFS_CONTROL,
-    USBDEVFS_DISCARDURB, USBDEVFS_DISCONNECT, USBDEVFS_DISCONNECT_CLAIM, USBDEVFS_GET_SPEED,
-    USBDEVFS_IOCTL, USBDEVFS_REAPURBNDELAY, USBDEVFS_RELEASEINTERFACE, USBDEVFS_RESET,
-    USBDEVFS_SETCONFIGURATION, USBDEVFS_SETINTERFACE, USBDEVFS_SUBMITURB,
+    USBDEVFS_ALLOW_SUSPEND, USBDEVFS_CLAIMINTERFACE, USBDEVFS_CLEAR_HALT, USBDEVFS_CONNECT,
+    USBDEVFS_CONTROL, USBDEVFS_DISCARDURB, USBDEVFS_DISCONNECT, USBDEVFS_DISCONNECT_CLAIM,
+    USBDEVFS_DROP_PRIVILEGES, USBDEVFS_FORBID_SUSPEND, USBDEVFS_GET_CAPABILITIES,
+    USBDEVFS_GET_SPEED, USBDEVFS_IOCTL, USBDEVFS_REAPURBNDELAY, USBDEVFS_RELEASEINTERFACE,
+    USBDEVFS_RESET, USBDEVFS_SETCONFIGURATION, USBDEVFS_SETINTERFACE, USBDEVFS_SUBMITURB,
+    USBDEVFS_WAIT_FOR_RESUME,
 };
 use rustix::{
     fd::AsFd,
@@ -27,6 +29,11 @@ pub fn set_configuration<Fd: AsFd>(fd: Fd, configuration: u8) -> io::Result<()>
 }
 
 pub fn claim_interface<Fd: AsFd>(fd: Fd, interface: u8) -> io::Result<()> {
+    #[cfg(test)]
+    if let Some(r) = super::fake::claim_or_release(fd.as_fd()) {
+        return r;
+    }
+
     unsafe {
         let ctl = ioctl::Setter::<{ USBDEVFS_CLAIMINTERFACE as _ }, c_uint>::new(interface.into());
         ioctl::ioctl(fd, ctl)
@@ -34,6 +41,11 @@ pub fn claim_interface<Fd: AsFd>(fd: Fd, interface: u8) -> io::Result<()> {
 }
 
 pub fn release_interface<Fd: AsFd>(fd: Fd, interface: u8) -> io::Result<()> {
+    #[cfg(test)]
+    if let Some(r) = super::fake::claim_or_release(fd.as_fd()) {
+        return r;
+    }
+
     unsafe {
         let ctl =
             ioctl::Setter::<{ USBDEVFS_RELEASEINTERFACE as _ }, c_uint>::new(interface.into());
@@ -151,6 +163,11 @@ unsafe impl<const OPCODE: Opcode, Input> Ioctl for PassPtr<OPCODE, Input> {
 }
 
 pub unsafe fn submit_urb<Fd: AsFd>(fd: Fd, urb: *mut Urb) -> io::Result<()> {
+    #[cfg(test)]
+    if let Some(r) = super::fake::submit_urb(fd.as_fd(), urb) {
+        return r;
+    }
+
     unsafe {
         let ctl = PassPtr::<{ USBDEVFS_SUBMITURB as _ }, Urb>::new(urb);
         ioctl::ioctl(fd, ctl)
@@ -158,6 +175,11 @@ pub unsafe fn submit_urb<Fd: AsFd>(fd: Fd, urb: *mut Urb) -> io::Result<()> {
 }
 
 pub fn reap_urb_ndelay<Fd: AsFd>(fd: Fd) -> io::Result<*mut Urb> {
+    #[cfg(test)]
+    if let Some(r) = super::fake::reap_urb_ndelay(fd.as_fd()) {
+        return r;
+    }
+
     unsafe {
         let ctl = ioctl::Getter::<{ USBDEVFS_REAPURBNDELAY as _ }, *mut Urb>::new();
         ioctl::ioctl(fd, ctl)
@@ -165,6 +187,11 @@ pub fn reap_urb_ndelay<Fd: AsFd>(fd: Fd) -> io::Result<*mut Urb> {
 }
 
 pub unsafe fn discard_urb<Fd: AsFd>(fd: Fd, urb: *mut Urb) -> io::Result<()> {
+    #[cfg(test)]
+    if let Some(r) = super::fake::discard_urb(fd.as_fd(), urb) {
+        return r;
+    }
+
     unsafe {
         let ctl = PassPtr::<{ USBDEVFS_DISCARDURB as _ }, Urb>::new(urb);
         ioctl::ioctl(fd, ctl)
@@ -178,6 +205,42 @@ pub fn reset<Fd: AsFd>(fd: Fd) -> io::Result<()> {
     }
 }
 
+pub fn forbid_suspend<Fd: AsFd>(fd: Fd) -> io::Result<()> {
+    #[cfg(test)]
+    if let Some(r) = super::fake::set_suspend(fd.as_fd(), false) {
+        return r;
+    }
+
+    unsafe {
+        let ctl = ioctl::NoArg::<{ USBDEVFS_FORBID_SUSPEND as _ }>::new();
+        ioctl::ioctl(fd, ctl)
+    }
+}
+
+pub fn allow_suspend<Fd: AsFd>(fd: Fd) -> io::Result<()> {
+    #[cfg(test)]
+    if let Some(r) = super::fake::set_suspend(fd.as_fd(), true) {
+        return r;
+    }
+
+    unsafe {
+        let ctl = ioctl::NoArg::<{ USBDEVFS_ALLOW_SUSPEND as _ }>::new();
+        ioctl::ioctl(fd, ctl)
+    }
+}
+
+pub fn wait_for_resume<Fd: AsFd>(fd: Fd) -> io::Result<()> {
+    #[cfg(test)]
+    if let Some(r) = super::fake::wait_for_resume(fd.as_fd()) {
+        return r;
+    }
+
+    unsafe {
+        let ctl = ioctl::NoArg::<{ USBDEVFS_WAIT_FOR_RESUME as _ }>::new();
+        ioctl::ioctl(fd, ctl)
+    }
+}
+
 const USBDEVFS_URB_SHORT_NOT_OK: c_uint = 0x01;
 const USBDEVFS_URB_ISO_ASAP: c_uint = 0x02;
 const USBDEVFS_URB_BULK_CONTINUATION: c_uint = 0x04;
@@ -256,12 +319,31 @@ pub fn control<Fd: AsFd>(fd: Fd, transfer: CtrlTransfer) -> io::Result<usize> {
 }
 
 pub fn clear_halt<Fd: AsFd>(fd: Fd, endpoint: u8) -> io::Result<()> {
+    #[cfg(test)]
+    if let Some(r) = super::fake::clear_halt(fd.as_fd(), endpoint) {
+        return r;
+    }
+
     unsafe {
         let ctl = ioctl::Setter::<{ USBDEVFS_CLEAR_HALT as _ }, c_uint>::new(endpoint.into());
         ioctl::ioctl(fd, ctl)
     }
 }
 
+pub fn drop_privileges<Fd: AsFd>(fd: Fd, interface_mask: u32) -> io::Result<()> {
+    unsafe {
+        let ctl = ioctl::Setter::<{ USBDEVFS_DROP_PRIVILEGES as _ }, u32>::new(interface_mask);
+        ioctl::ioctl(fd, ctl)
+    }
+}
+
+pub fn get_capabilities<Fd: AsFd>(fd: Fd) -> io::Result<u32> {
+    unsafe {
+        let ctl = ioctl::Getter::<{ USBDEVFS_GET_CAPABILITIES as _ }, u32>::new();
+        ioctl::ioctl(fd, ctl)
+    }
+}
+
 pub fn get_speed<Fd: AsFd>(fd: Fd) -> io::Result<usize> {
     unsafe {
         let ctl = Transfer::<{ USBDEVFS_GET_SPEED as _ }, ()>::new(());
@@ -510,6 +510,10 @@ impl MacEndpoint {
         self.inner.address
     }
 
+    pub(crate) fn interface(&self) -> &Arc<MacInterface> {
+        &self.inner.interface
+    }
+
     pub(crate) fn pending(&self) -> usize {
         self.pending.len()
     }
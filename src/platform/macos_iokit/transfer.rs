@@ -1,8 +1,11 @@
-use std::mem::{self, ManuallyDrop};
+use std::{
+    mem::{self, ManuallyDrop},
+    sync::Arc,
+};
 
 use io_kit_sys::ret::{kIOReturnSuccess, IOReturn};
 
-use crate::transfer::{Allocator, Buffer, Completion, Direction};
+use crate::transfer::{pool::PoolShared, Allocator, Buffer, Completion, Direction};
 
 pub struct TransferData {
     pub(super) buf: *mut u8,
@@ -10,11 +13,20 @@ pub struct TransferData {
     pub(super) requested_len: u32,
     pub(super) actual_len: u32,
     pub(super) status: IOReturn,
+    pool: Option<Arc<PoolShared>>,
 }
 
 impl Drop for TransferData {
     fn drop(&mut self) {
-        unsafe { drop(Vec::from_raw_parts(self.buf, 0, self.capacity as usize)) }
+        // Return the memory to its pool, if any
+        drop(Buffer {
+            ptr: self.buf,
+            len: 0,
+            requested_len: 0,
+            capacity: self.capacity,
+            allocator: Allocator::Default,
+            pool: self.pool.take(),
+        });
     }
 }
 
@@ -27,12 +39,14 @@ impl TransferData {
             requested_len: 0,
             actual_len: 0,
             status: kIOReturnSuccess,
+            pool: None,
         }
     }
 
-    pub fn put_buffer(&mut self, buffer: Buffer, direction: Direction) {
+    pub fn put_buffer(&mut self, mut buffer: Buffer, direction: Direction) {
         // Assumes that there is no previous buffer; this would leak it
         debug_assert!(self.capacity == 0);
+        self.pool = buffer.pool.take();
         let buffer = ManuallyDrop::new(buffer);
         self.buf = buffer.ptr;
         self.capacity = buffer.capacity;
@@ -64,13 +78,10 @@ impl TransferData {
             requested_len,
             capacity,
             allocator: Allocator::Default,
+            pool: self.pool.take(),
         };
 
-        Completion {
-            status,
-            actual_len,
-            buffer,
-        }
+        Completion::new(buffer, actual_len, status)
     }
 }
 
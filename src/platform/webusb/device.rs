@@ -434,6 +434,10 @@ impl WebusbEndpoint {
         self.inner.address
     }
 
+    pub(crate) fn interface(&self) -> &Arc<WebusbInterface> {
+        &self.inner.interface
+    }
+
     pub(crate) fn pending(&self) -> usize {
         self.pending.len()
     }
@@ -495,11 +499,7 @@ impl WebusbEndpoint {
                 let Some(Pending::Failed { buffer, error }) = self.pending.pop_front() else {
                     unreachable!()
                 };
-                Poll::Ready(Completion {
-                    buffer,
-                    actual_len: 0,
-                    status: Err(error),
-                })
+                Poll::Ready(Completion::new(buffer, 0, Err(error)))
             }
             Pending::InFlight { future, .. } => {
                 let result = ready!(Pin::new(future).poll(cx));
@@ -540,11 +540,7 @@ fn complete_transfer(
     let result = match result {
         Ok(r) => r,
         Err(e) => {
-            return Completion {
-                buffer,
-                actual_len: 0,
-                status: Err(js_value_to_transfer_error(e)),
-            };
+            return Completion::new(buffer, 0, Err(js_value_to_transfer_error(e)));
         }
     };
 
@@ -553,11 +549,11 @@ fn complete_transfer(
             let result: UsbOutTransferResult = JsCast::unchecked_from_js(result);
             // `buffer.len` is the user-supplied payload length (unchanged from
             // submit). `actual_len` is what the device acknowledged.
-            Completion {
-                actual_len: result.bytes_written() as usize,
-                status: webusb_status_to_nusb_transfer_error(result.status()),
+            Completion::new(
                 buffer,
-            }
+                result.bytes_written() as usize,
+                webusb_status_to_nusb_transfer_error(result.status()),
+            )
         }
         Direction::In => {
             let result: UsbInTransferResult = JsCast::unchecked_from_js(result);
@@ -582,11 +578,11 @@ fn complete_transfer(
             } else {
                 0
             };
-            Completion {
-                actual_len,
-                status: webusb_status_to_nusb_transfer_error(result.status()),
+            Completion::new(
                 buffer,
-            }
+                actual_len,
+                webusb_status_to_nusb_transfer_error(result.status()),
+            )
         }
     }
 }
@@ -831,6 +831,10 @@ impl WindowsEndpoint {
         self.inner.address
     }
 
+    pub(crate) fn interface(&self) -> &Arc<WindowsInterface> {
+        &self.inner.interface
+    }
+
     pub(crate) fn pending(&self) -> usize {
         self.pending.len()
     }
@@ -13,7 +13,7 @@ use windows_sys::Win32::{
 };
 
 use super::{threadpool::Timer, Interface};
-use crate::transfer::{Buffer, Completion, Direction, TransferError};
+use crate::transfer::{pool::PoolShared, Allocator, Buffer, Completion, Direction, TransferError};
 
 #[repr(C)]
 pub struct TransferData {
@@ -25,6 +25,7 @@ pub struct TransferData {
     pub(crate) buf: *mut u8,
     pub(crate) capacity: u32,
     pub(crate) request_len: u32,
+    pool: Option<Arc<PoolShared>>,
 
     pub(crate) intf: Arc<Interface>,
     pub(crate) endpoint: u8,
@@ -45,6 +46,7 @@ impl TransferData {
             buf: empty.as_mut_ptr(),
             capacity: 0,
             request_len: 0,
+            pool: None,
             intf,
             endpoint,
             timeout: None,
@@ -52,8 +54,9 @@ impl TransferData {
         }
     }
 
-    pub fn set_buffer(&mut self, buf: Buffer) {
+    pub fn set_buffer(&mut self, mut buf: Buffer) {
         debug_assert!(self.capacity == 0);
+        self.pool = buf.pool.take();
         let buf = ManuallyDrop::new(buf);
         self.capacity = buf.capacity;
         self.buf = buf.ptr;
@@ -73,10 +76,8 @@ impl TransferData {
             match unsafe { GetLastError() } {
                 ERROR_SUCCESS => Ok(()),
                 ERROR_GEN_FAILURE => Err(TransferError::Stall),
-                ERROR_REQUEST_ABORTED
-                | ERROR_TIMEOUT
-                | ERROR_SEM_TIMEOUT
-                | ERROR_OPERATION_ABORTED => Err(TransferError::Cancelled),
+                ERROR_REQUEST_ABORTED | ERROR_OPERATION_ABORTED => Err(TransferError::Cancelled),
+                ERROR_TIMEOUT | ERROR_SEM_TIMEOUT => Err(TransferError::TimedOut),
                 ERROR_FILE_NOT_FOUND | ERROR_DEVICE_NOT_CONNECTED | ERROR_NO_SUCH_DEVICE => {
                     Err(TransferError::Disconnected)
                 }
@@ -94,24 +95,29 @@ impl TransferData {
         let requested_len = mem::replace(&mut self.request_len, 0);
         self.overlapped.InternalHigh = 0;
 
-        Completion {
-            status,
-            actual_len: actual_len as usize,
-            buffer: Buffer {
-                ptr,
-                len,
-                requested_len,
-                capacity,
-                allocator: crate::transfer::Allocator::Default,
-            },
-        }
+        let buffer = Buffer {
+            ptr,
+            len,
+            requested_len,
+            capacity,
+            allocator: Allocator::Default,
+            pool: self.pool.take(),
+        };
+
+        Completion::new(buffer, actual_len as usize, status)
     }
 }
 
 impl Drop for TransferData {
     fn drop(&mut self) {
-        unsafe {
-            drop(Vec::from_raw_parts(self.buf, 0, self.capacity as usize));
-        }
+        // Return the memory to its pool, if any
+        drop(Buffer {
+            ptr: self.buf,
+            len: 0,
+            requested_len: 0,
+            capacity: self.capacity,
+            allocator: Allocator::Default,
+            pool: self.pool.take(),
+        });
     }
 }
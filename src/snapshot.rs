@@ -0,0 +1,560 @@
+//! Serializable records of devices, buses, and descriptors.
+//!
+//! With the `serde` feature enabled, [`DeviceInfo`], [`InterfaceInfo`],
+//! [`BusInfo`][crate::BusInfo], [`Speed`], [`UsbControllerType`][crate::UsbControllerType], and the
+//! parsed descriptor types implement [`Serialize`].
+//!
+//! `DeviceInfo` and `BusInfo` serialize as a [`DeviceRecord`] and
+//! [`BusRecord`], which have the same form on every platform. The
+//! platform-specific location fields are stored in a [`PlatformLocation`]
+//! tagged with the platform they came from, so a record written on one OS can
+//! be read on any other.
+//!
+//! Deserializing gives back a `DeviceRecord` rather than a `DeviceInfo`,
+//! since a record can't be used to open a device. Use
+//! [`DeviceRecord::matches`] to find the device in a later enumeration.
+//!
+//! ### Example
+//!
+//! ```no_run
+//! use nusb::{MaybeFuture, snapshot::DeviceRecord};
+//!
+//! let devices: Vec<DeviceRecord> = nusb::list_devices()
+//!     .wait()
+//!     .unwrap()
+//!     .map(|d| DeviceRecord::from(&d))
+//!     .collect();
+//! ```
+
+use std::num::NonZeroU8;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    descriptors::{
+        ConfigurationDescriptor, DeviceDescriptor, EndpointDescriptor, InterfaceDescriptor,
+        TransferType,
+    },
+    transfer::Direction,
+    DeviceInfo, InterfaceInfo, Speed,
+};
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+use crate::{BusInfo, UsbControllerType};
+
+/// Platform-neutral record of a [`DeviceInfo`].
+///
+/// Fields that are not available on the platform where the record was
+/// created are `None`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct DeviceRecord {
+    /// Where the device was connected, if known on the platform.
+    pub location: Option<Location>,
+
+    /// See [`DeviceInfo::vendor_id`].
+    pub vendor_id: u16,
+
+    /// See [`DeviceInfo::product_id`].
+    pub product_id: u16,
+
+    /// See [`DeviceInfo::device_version`].
+    pub device_version: Option<u16>,
+
+    /// See [`DeviceInfo::usb_version`].
+    pub usb_version: u16,
+
+    /// See [`DeviceInfo::class`].
+    pub class: u8,
+
+    /// See [`DeviceInfo::subclass`].
+    pub subclass: u8,
+
+    /// See [`DeviceInfo::protocol`].
+    pub protocol: u8,
+
+    /// See [`DeviceInfo::speed`].
+    pub speed: Option<Speed>,
+
+    /// See [`DeviceInfo::manufacturer_string`].
+    pub manufacturer_string: Option<String>,
+
+    /// See [`DeviceInfo::product_string`].
+    pub product_string: Option<String>,
+
+    /// See [`DeviceInfo::serial_number`].
+    pub serial_number: Option<String>,
+
+    /// See [`DeviceInfo::interfaces`].
+    pub interfaces: Vec<InterfaceInfo>,
+}
+
+/// Location of a device on the system's USB topology.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Location {
+    /// See [`DeviceInfo::bus_id`].
+    pub bus_id: String,
+
+    /// See [`DeviceInfo::device_address`].
+    pub device_address: u8,
+
+    /// See [`DeviceInfo::port_chain`].
+    pub port_chain: Vec<u8>,
+
+    /// Platform-specific identifiers.
+    pub platform: Option<PlatformLocation>,
+}
+
+/// Platform-specific identifiers of a device or bus.
+///
+/// These serialize with a `platform` tag of `linux`, `windows`, or `macos`.
+/// Strings that are `OsString` on the originating platform are converted
+/// lossily.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "platform", rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum PlatformLocation {
+    /// Linux sysfs location.
+    Linux {
+        /// Sysfs path of the device or bus.
+        sysfs_path: String,
+
+        /// Bus number.
+        busnum: u8,
+    },
+
+    /// Windows device instance location.
+    Windows {
+        /// Instance ID of the device or bus.
+        instance_id: String,
+
+        /// Instance ID of the parent device.
+        parent_instance_id: String,
+
+        /// Location paths property.
+        location_paths: Vec<String>,
+
+        /// Port number on the parent hub, for devices.
+        port_number: Option<u32>,
+
+        /// Driver associated with the device as a whole, for devices.
+        driver: Option<String>,
+    },
+
+    /// macOS IOKit location.
+    Macos {
+        /// IOKit registry entry ID.
+        registry_entry_id: u64,
+
+        /// IOKit location ID.
+        location_id: u32,
+    },
+}
+
+impl DeviceRecord {
+    /// Check whether `info` describes the device this record was created
+    /// from.
+    ///
+    /// The vendor and product IDs must be equal. If the record has a serial
+    /// number, the serial numbers must be equal; otherwise, the device must be
+    /// connected to the same port, as identified by the bus ID and port chain.
+    /// Records without either never match.
+    pub fn matches(&self, info: &DeviceInfo) -> bool {
+        if self.vendor_id != info.vendor_id() || self.product_id != info.product_id() {
+            return false;
+        }
+
+        if let Some(serial) = &self.serial_number {
+            return info.serial_number() == Some(serial.as_str());
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        if let Some(location) = &self.location {
+            return location.bus_id == info.bus_id() && location.port_chain == info.port_chain();
+        }
+
+        false
+    }
+}
+
+impl From<&DeviceInfo> for DeviceRecord {
+    fn from(info: &DeviceInfo) -> Self {
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        let location = Some(Location {
+            bus_id: info.bus_id.clone(),
+            device_address: info.device_address,
+            port_chain: info.port_chain.clone(),
+            platform: Some(device_platform_location(info)),
+        });
+
+        #[cfg(target_os = "android")]
+        let location = Some(Location {
+            bus_id: format!("{:03}", info.busnum),
+            device_address: info.device_address,
+            port_chain: Vec::new(),
+            platform: None,
+        });
+
+        #[cfg(target_arch = "wasm32")]
+        let location = None;
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "windows",
+            target_arch = "wasm32"
+        ))]
+        let device_version = Some(info.device_version);
+
+        #[cfg(target_os = "android")]
+        let device_version = None;
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "windows",
+            target_os = "android",
+        ))]
+        let speed = info.speed;
+
+        #[cfg(target_arch = "wasm32")]
+        let speed = None;
+
+        DeviceRecord {
+            location,
+            vendor_id: info.vendor_id,
+            product_id: info.product_id,
+            device_version,
+            usb_version: info.usb_version,
+            class: info.class,
+            subclass: info.subclass,
+            protocol: info.protocol,
+            speed,
+            manufacturer_string: info.manufacturer_string.clone(),
+            product_string: info.product_string.clone(),
+            serial_number: info.serial_number.clone(),
+            interfaces: info.interfaces.clone(),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+fn device_platform_location(info: &DeviceInfo) -> PlatformLocation {
+    #[cfg(target_os = "linux")]
+    {
+        PlatformLocation::Linux {
+            sysfs_path: info.path.0.to_string_lossy().into_owned(),
+            busnum: info.busnum,
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        PlatformLocation::Windows {
+            instance_id: info.instance_id.to_string_lossy().into_owned(),
+            parent_instance_id: info.parent_instance_id.to_string_lossy().into_owned(),
+            location_paths: info
+                .location_paths
+                .iter()
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect(),
+            port_number: Some(info.port_number),
+            driver: info.driver.clone(),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        PlatformLocation::Macos {
+            registry_entry_id: info.registry_id,
+            location_id: info.location_id,
+        }
+    }
+}
+
+impl Serialize for DeviceInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DeviceRecord::from(self).serialize(serializer)
+    }
+}
+
+/// Platform-neutral record of a [`BusInfo`].
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct BusRecord {
+    /// See [`BusInfo::bus_id`].
+    pub bus_id: String,
+
+    /// See [`BusInfo::system_name`].
+    pub system_name: Option<String>,
+
+    /// See [`BusInfo::driver`].
+    pub driver: Option<String>,
+
+    /// See [`BusInfo::controller_type`].
+    pub controller_type: Option<UsbControllerType>,
+
+    /// Platform-specific identifiers.
+    pub platform: Option<PlatformLocation>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+impl From<&BusInfo> for BusRecord {
+    fn from(info: &BusInfo) -> Self {
+        #[cfg(target_os = "linux")]
+        let platform = PlatformLocation::Linux {
+            sysfs_path: info.path.0.to_string_lossy().into_owned(),
+            busnum: info.busnum,
+        };
+
+        #[cfg(target_os = "windows")]
+        let platform = PlatformLocation::Windows {
+            instance_id: info.instance_id.to_string_lossy().into_owned(),
+            parent_instance_id: info.parent_instance_id.to_string_lossy().into_owned(),
+            location_paths: info
+                .location_paths
+                .iter()
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect(),
+            port_number: None,
+            driver: None,
+        };
+
+        #[cfg(target_os = "macos")]
+        let platform = PlatformLocation::Macos {
+            registry_entry_id: info.registry_id,
+            location_id: info.location_id,
+        };
+
+        BusRecord {
+            bus_id: info.bus_id.clone(),
+            system_name: info.system_name().map(str::to_owned),
+            driver: info.driver.clone(),
+            controller_type: info.controller_type,
+            platform: Some(platform),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+impl Serialize for BusInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BusRecord::from(self).serialize(serializer)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "DeviceDescriptor")]
+struct DeviceDescriptorFields {
+    usb_version: u16,
+    class: u8,
+    subclass: u8,
+    protocol: u8,
+    max_packet_size_0: u8,
+    vendor_id: u16,
+    product_id: u16,
+    device_version: u16,
+    manufacturer_string_index: Option<NonZeroU8>,
+    product_string_index: Option<NonZeroU8>,
+    serial_number_string_index: Option<NonZeroU8>,
+    num_configurations: u8,
+}
+
+/// Serializes as a map of the descriptor fields.
+impl Serialize for DeviceDescriptor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DeviceDescriptorFields {
+            usb_version: self.usb_version(),
+            class: self.class(),
+            subclass: self.subclass(),
+            protocol: self.protocol(),
+            max_packet_size_0: self.max_packet_size_0(),
+            vendor_id: self.vendor_id(),
+            product_id: self.product_id(),
+            device_version: self.device_version(),
+            manufacturer_string_index: self.manufacturer_string_index(),
+            product_string_index: self.product_string_index(),
+            serial_number_string_index: self.serial_number_string_index(),
+            num_configurations: self.num_configurations(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DeviceDescriptor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let f = DeviceDescriptorFields::deserialize(deserializer)?;
+        Ok(DeviceDescriptor::from_fields(
+            f.usb_version,
+            f.class,
+            f.subclass,
+            f.protocol,
+            f.max_packet_size_0,
+            f.vendor_id,
+            f.product_id,
+            f.device_version,
+            f.manufacturer_string_index.map_or(0, NonZeroU8::get),
+            f.product_string_index.map_or(0, NonZeroU8::get),
+            f.serial_number_string_index.map_or(0, NonZeroU8::get),
+            f.num_configurations,
+        ))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "ConfigurationDescriptor")]
+struct ConfigurationDescriptorFields<'a> {
+    configuration_value: u8,
+    num_interfaces: u8,
+    attributes: u8,
+    max_power: u8,
+    string_index: Option<NonZeroU8>,
+    interface_alt_settings: Vec<InterfaceDescriptor<'a>>,
+}
+
+/// Serializes as a map of the descriptor fields, with nested interface
+/// and endpoint descriptors.
+///
+/// Class-specific and other unrecognized descriptors are not included. Use
+/// [`ConfigurationDescriptor::as_bytes`] to record the complete descriptor.
+impl Serialize for ConfigurationDescriptor<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ConfigurationDescriptorFields {
+            configuration_value: self.configuration_value(),
+            num_interfaces: self.num_interfaces(),
+            attributes: self.attributes(),
+            max_power: self.max_power(),
+            string_index: self.string_index(),
+            interface_alt_settings: self.interface_alt_settings().collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "InterfaceDescriptor")]
+struct InterfaceDescriptorFields<'a> {
+    interface_number: u8,
+    alternate_setting: u8,
+    class: u8,
+    subclass: u8,
+    protocol: u8,
+    string_index: Option<NonZeroU8>,
+    endpoints: Vec<EndpointDescriptor<'a>>,
+}
+
+/// Serializes as a map of the descriptor fields, with nested endpoint
+/// descriptors.
+impl Serialize for InterfaceDescriptor<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        InterfaceDescriptorFields {
+            interface_number: self.interface_number(),
+            alternate_setting: self.alternate_setting(),
+            class: self.class(),
+            subclass: self.subclass(),
+            protocol: self.protocol(),
+            string_index: self.string_index(),
+            endpoints: self.endpoints().collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "EndpointDescriptor")]
+struct EndpointDescriptorFields {
+    address: u8,
+    direction: Direction,
+    transfer_type: TransferType,
+    attributes: u8,
+    max_packet_size: usize,
+    packets_per_microframe: u8,
+    interval: u8,
+}
+
+/// Serializes as a map of the descriptor fields.
+impl Serialize for EndpointDescriptor<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        EndpointDescriptorFields {
+            address: self.address(),
+            direction: self.direction(),
+            transfer_type: self.transfer_type(),
+            attributes: self.attributes(),
+            max_packet_size: self.max_packet_size(),
+            packets_per_microframe: self.packets_per_microframe(),
+            interval: self.interval(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[test]
+fn test_device_record_roundtrip() {
+    let record = DeviceRecord {
+        location: Some(Location {
+            bus_id: "003".into(),
+            device_address: 7,
+            port_chain: vec![2, 1],
+            platform: Some(PlatformLocation::Linux {
+                sysfs_path: "/sys/devices/pci0000:00/0000:00:14.0/usb3/3-2/3-2.1".into(),
+                busnum: 3,
+            }),
+        }),
+        vendor_id: 0x59e3,
+        product_id: 0x0a23,
+        device_version: Some(0x0100),
+        usb_version: 0x0200,
+        class: 0xef,
+        subclass: 0x02,
+        protocol: 0x01,
+        speed: Some(Speed::High),
+        manufacturer_string: Some("Nonolith Labs".into()),
+        product_string: Some("CEE".into()),
+        serial_number: None,
+        interfaces: vec![InterfaceInfo {
+            interface_number: 0,
+            class: 0xff,
+            subclass: 0,
+            protocol: 0,
+            interface_string: None,
+            #[cfg(target_os = "linux")]
+            alternate_setting: 0,
+            #[cfg(target_os = "linux")]
+            driver: None,
+        }],
+    };
+
+    let json = serde_json::to_value(&record).unwrap();
+    assert_eq!(json["location"]["platform"]["platform"], "linux");
+    let interface = json["interfaces"][0].as_object().unwrap();
+    assert_eq!(
+        interface.keys().collect::<Vec<_>>(),
+        [
+            "class",
+            "interface_number",
+            "interface_string",
+            "protocol",
+            "subclass"
+        ]
+    );
+    assert_eq!(json["speed"], "High");
+    assert_eq!(
+        serde_json::from_value::<DeviceRecord>(json).unwrap(),
+        record
+    );
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_device_descriptor_roundtrip() {
+    let desc = DeviceDescriptor::new(&[
+        0x12, 0x01, 0x00, 0x02, 0xef, 0x02, 0x01, 0x40, 0xe3,
+        0x59, 0x23, 0x0a, 0x00, 0x01, 0x01, 0x02, 0x00, 0x01
+    ]).unwrap();
+
+    let json = serde_json::to_string(&desc).unwrap();
+    let parsed: DeviceDescriptor = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.as_bytes(), desc.as_bytes());
+}
@@ -0,0 +1,143 @@
+//! Runtime-independent timer for async timeouts.
+//!
+//! On Linux, deadlines are handled by the device's timerfd on the event
+//! thread. Elsewhere, they are kept in a map ordered by deadline and served
+//! by a single background thread, which is started the first time a
+//! [`Delay`] has to wait.
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) use crate::platform::Delay;
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) use thread::Delay;
+
+#[cfg(any(test, not(any(target_os = "linux", target_os = "android"))))]
+mod thread {
+    use std::{
+        collections::BTreeMap,
+        future::Future,
+        pin::Pin,
+        sync::{Condvar, Mutex, Once},
+        task::{Context, Poll, Waker},
+        time::{Duration, Instant},
+    };
+
+    struct Timers {
+        next_id: u64,
+
+        /// Registered delays by deadline, with the id making each key unique.
+        wakers: BTreeMap<(Instant, u64), Waker>,
+    }
+
+    static TIMERS: Mutex<Timers> = Mutex::new(Timers {
+        next_id: 0,
+        wakers: BTreeMap::new(),
+    });
+    static CHANGED: Condvar = Condvar::new();
+    static START: Once = Once::new();
+
+    /// Future that completes once a timeout has elapsed.
+    pub(crate) struct Delay {
+        /// `None` if the timeout is too long to represent, and never elapses.
+        deadline: Option<Instant>,
+        id: Option<u64>,
+    }
+
+    impl Delay {
+        pub(crate) fn new(timeout: Duration) -> Self {
+            Delay {
+                deadline: Instant::now().checked_add(timeout),
+                id: None,
+            }
+        }
+    }
+
+    impl Future for Delay {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let this = Pin::into_inner(self);
+            let Some(deadline) = this.deadline else {
+                return Poll::Pending;
+            };
+            if Instant::now() >= deadline {
+                return Poll::Ready(());
+            }
+
+            let mut timers = TIMERS.lock().unwrap();
+            let id = match this.id {
+                Some(id) => id,
+                None => {
+                    let id = timers.next_id;
+                    timers.next_id += 1;
+                    this.id = Some(id);
+                    CHANGED.notify_one();
+                    id
+                }
+            };
+            timers.wakers.insert((deadline, id), cx.waker().clone());
+            drop(timers);
+
+            START.call_once(|| {
+                std::thread::Builder::new()
+                    .name("nusb-timer".into())
+                    .spawn(run)
+                    .expect("failed to start timer thread");
+            });
+            Poll::Pending
+        }
+    }
+
+    impl Drop for Delay {
+        fn drop(&mut self) {
+            if let (Some(deadline), Some(id)) = (self.deadline, self.id) {
+                TIMERS.lock().unwrap().wakers.remove(&(deadline, id));
+            }
+        }
+    }
+
+    fn run() {
+        let mut timers = TIMERS.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let mut expired = Vec::new();
+            while let Some(entry) = timers.wakers.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                expired.push(entry.remove());
+            }
+
+            if !expired.is_empty() {
+                drop(timers);
+                expired.into_iter().for_each(Waker::wake);
+                timers = TIMERS.lock().unwrap();
+                continue;
+            }
+
+            timers = match timers.wakers.keys().next() {
+                Some(&(deadline, _)) => CHANGED.wait_timeout(timers, deadline - now).unwrap().0,
+                None => CHANGED.wait(timers).unwrap(),
+            };
+        }
+    }
+
+    #[test]
+    fn test_thread_delay() {
+        // A delay that is dropped after registering is removed, and doesn't
+        // hold up the others
+        let mut long = Delay::new(Duration::from_secs(60));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(Pin::new(&mut long).poll(&mut cx).is_pending());
+        let key = (long.deadline.unwrap(), long.id.unwrap());
+        assert!(TIMERS.lock().unwrap().wakers.contains_key(&key));
+        drop(long);
+        assert!(!TIMERS.lock().unwrap().wakers.contains_key(&key));
+
+        let start = Instant::now();
+        futures_lite::future::block_on(Delay::new(Duration::from_millis(20)));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(20), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+    }
+}
@@ -2,8 +2,11 @@ use std::{
     fmt::Debug,
     mem::{ManuallyDrop, MaybeUninit},
     ops::{Deref, DerefMut},
+    sync::Arc,
 };
 
+use super::pool::PoolShared;
+
 #[derive(Copy, Clone)]
 pub(crate) enum Allocator {
     Default,
@@ -42,6 +45,9 @@ pub struct Buffer {
 
     /// Whether the system allocator or a special allocator was used
     pub(crate) allocator: Allocator,
+
+    /// Pool to return the memory to when dropped
+    pub(crate) pool: Option<Arc<PoolShared>>,
 }
 
 impl Buffer {
@@ -66,6 +72,7 @@ impl Buffer {
             requested_len: len_u32,
             capacity: vec.capacity().try_into().expect("capacity overflow"),
             allocator: Allocator::Default,
+            pool: None,
         }
     }
 
@@ -93,6 +100,7 @@ impl Buffer {
             requested_len: len_u32,
             capacity: len_u32,
             allocator: Allocator::Mmap,
+            pool: None,
         })
     }
 
@@ -188,10 +196,12 @@ impl Buffer {
     ///
     /// This is zero-cost if the buffer was allocated with the default allocator
     /// (if [`is_zero_copy()`][Self::is_zero_copy] returns false), otherwise it will copy the data
-    /// into a new `Vec<u8>`.
+    /// into a new `Vec<u8>`. Buffers from a
+    /// [`BufferPool`][crate::transfer::BufferPool] are always copied, and
+    /// returned to the pool.
     pub fn into_vec(self) -> Vec<u8> {
         match self.allocator {
-            Allocator::Default => {
+            Allocator::Default if self.pool.is_none() => {
                 let buf = ManuallyDrop::new(self);
                 unsafe { Vec::from_raw_parts(buf.ptr, buf.len as usize, buf.capacity as usize) }
             }
@@ -216,6 +226,7 @@ impl From<Vec<u8>> for Buffer {
             requested_len: vec.len().try_into().expect("len overflow"),
             capacity: vec.capacity().try_into().expect("capacity overflow"),
             allocator: Allocator::Default,
+            pool: None,
         }
     }
 }
@@ -248,6 +259,7 @@ impl From<Vec<MaybeUninit<u8>>> for Buffer {
             requested_len: vec.len().try_into().expect("len overflow"),
             capacity: vec.capacity().try_into().expect("capacity overflow"),
             allocator: Allocator::Default,
+            pool: None,
         }
     }
 }
@@ -278,6 +290,18 @@ impl Debug for Buffer {
 
 impl Drop for Buffer {
     fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.put(Buffer {
+                ptr: self.ptr,
+                len: 0,
+                requested_len: self.capacity,
+                capacity: self.capacity,
+                allocator: self.allocator,
+                pool: None,
+            });
+            return;
+        }
+
         match self.allocator {
             Allocator::Default => unsafe {
                 drop(Vec::from_raw_parts(
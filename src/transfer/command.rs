@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{future::Future, time::Instant};
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::TransferError;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{maybe_future::Mode, MaybeFuture};
+
+/// Options for polling a status request until the device reports that a
+/// command has completed, for `control_command` on a
+/// [`Device`](crate::Device::control_command) or
+/// [`Interface`](crate::Interface::control_command).
+///
+/// ```
+/// use std::time::Duration;
+/// use nusb::transfer::StatusPoll;
+///
+/// let poll = StatusPoll::new(Duration::from_millis(10), Duration::from_secs(2))
+///     .transfer_timeout(Duration::from_millis(100))
+///     .stall_retries(5);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusPoll {
+    interval: Duration,
+    timeout: Duration,
+    transfer_timeout: Duration,
+    stall_retries: u32,
+}
+
+impl StatusPoll {
+    /// Poll the status every `interval`, and give up with
+    /// [`TransferError::TimedOut`](super::TransferError::TimedOut) if the
+    /// whole sequence has not completed within `timeout`.
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        StatusPoll {
+            interval,
+            timeout,
+            transfer_timeout: Duration::MAX,
+            stall_retries: 0,
+        }
+    }
+
+    /// Limit the time for each individual control transfer.
+    ///
+    /// By default, each transfer may use the remaining time of the overall
+    /// timeout.
+    pub fn transfer_timeout(mut self, timeout: Duration) -> Self {
+        self.transfer_timeout = timeout;
+        self
+    }
+
+    /// Retry after up to `max_retries` stalls in total.
+    ///
+    /// A stall on the control endpoint only rejects the current request, so
+    /// no halt needs to be cleared. If the command stalls, it is sent again
+    /// after the polling interval, and if the status request stalls, polling
+    /// continues. Once the retries are exhausted, the stall is returned to the
+    /// caller. By default, the first stall is returned.
+    pub fn stall_retries(mut self, max_retries: u32) -> Self {
+        self.stall_retries = max_retries;
+        self
+    }
+
+    /// Send the command with `control_out`, then read the status with
+    /// `control_in` until `ready` accepts it.
+    ///
+    /// Between polls, blocking mode sleeps the thread, and async mode awaits
+    /// a timer from `delay`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn run<O, I, D>(
+        &self,
+        mode: Mode,
+        mut control_out: impl FnMut(Duration) -> O,
+        mut control_in: impl FnMut(Duration) -> I,
+        mut ready: impl FnMut(&[u8]) -> bool,
+        delay: impl Fn(Duration) -> D,
+    ) -> Result<Vec<u8>, TransferError>
+    where
+        O: MaybeFuture<Output = Result<(), TransferError>>,
+        I: MaybeFuture<Output = Result<Vec<u8>, TransferError>>,
+        D: Future<Output = ()>,
+    {
+        let deadline = Instant::now().checked_add(self.timeout);
+        let remaining = || deadline.map_or(Duration::MAX, |d| d - Instant::now().min(d));
+
+        let mut sent = false;
+        let mut stalls = 0;
+        loop {
+            let timeout = remaining().min(self.transfer_timeout);
+            if timeout.is_zero() {
+                return Err(TransferError::TimedOut);
+            }
+
+            let res = if sent {
+                mode.run(control_in(timeout)).await.map(Some)
+            } else {
+                mode.run(control_out(timeout)).await.map(|()| None)
+            };
+
+            match res {
+                Ok(None) => {
+                    sent = true;
+                    continue;
+                }
+                Ok(Some(status)) if ready(&status) => return Ok(status),
+                Ok(Some(_)) => {}
+                Err(TransferError::Stall) if stalls < self.stall_retries => {
+                    stalls += 1;
+                    log::debug!(
+                        "Stall on {} request, retry {stalls} of {}",
+                        if sent { "status" } else { "command" },
+                        self.stall_retries
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+
+            let wait = self.interval.min(remaining());
+            match mode {
+                Mode::Blocking => std::thread::sleep(wait),
+                Mode::Async => delay(wait).await,
+            }
+        }
+    }
+}
+
+#[test]
+fn test_status_poll() {
+    use crate::maybe_future::{Ready, Sequence};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Blocking mode must finish in a single poll, without awaiting `delay`
+    fn run<F: Future<Output = Result<Vec<u8>, TransferError>> + Send>(
+        mode: Mode,
+        fut: F,
+    ) -> F::Output {
+        match mode {
+            Mode::Blocking => Sequence::new(|_| fut).wait(),
+            Mode::Async => futures_lite::future::block_on(fut),
+        }
+    }
+
+    for mode in [Mode::Blocking, Mode::Async] {
+        let delays = AtomicUsize::new(0);
+        let delay = |d| {
+            delays.fetch_add(1, Ordering::Relaxed);
+            std::thread::sleep(d);
+            std::future::ready(())
+        };
+
+        let poll = StatusPoll::new(Duration::ZERO, Duration::from_secs(10)).stall_retries(2);
+        let mut commands = [Err(TransferError::Stall), Ok(())].into_iter();
+        let mut statuses = [Err(TransferError::Stall), Ok(vec![0]), Ok(vec![1])].into_iter();
+        let res = run(
+            mode,
+            poll.run(
+                mode,
+                |_| Ready(commands.next().unwrap()),
+                |_| Ready(statuses.next().unwrap()),
+                |status| status[0] == 1,
+                delay,
+            ),
+        );
+        assert_eq!(res, Ok(vec![1]));
+        assert_eq!(commands.next(), None);
+
+        let res = run(
+            mode,
+            poll.run(
+                mode,
+                |_| Ready(Err(TransferError::Stall)),
+                |_| -> Ready<Result<Vec<u8>, TransferError>> { unreachable!() },
+                |_| true,
+                delay,
+            ),
+        );
+        assert_eq!(res, Err(TransferError::Stall));
+
+        let poll = StatusPoll::new(Duration::from_millis(1), Duration::from_millis(20));
+        let res = run(
+            mode,
+            poll.run(
+                mode,
+                |_| Ready(Ok(())),
+                |_| Ready(Ok(vec![0])),
+                |_| false,
+                delay,
+            ),
+        );
+        assert_eq!(res, Err(TransferError::TimedOut));
+
+        let delays = delays.load(Ordering::Relaxed);
+        match mode {
+            Mode::Blocking => assert_eq!(delays, 0),
+            Mode::Async => assert!(delays > 0),
+        }
+    }
+}
@@ -3,27 +3,50 @@
 //! Use the methods on an [`Interface`][`super::Interface`] and
 //! [`Endpoint`][`super::Endpoint`] to perform transfers.
 
-use std::{fmt::Display, io};
+use std::{
+    fmt::Display,
+    io,
+    time::{Duration, Instant},
+};
 
 mod control;
 #[allow(unused)]
 pub(crate) use control::{request_type, SETUP_PACKET_SIZE};
 pub use control::{ControlIn, ControlOut, ControlType, Direction, Recipient};
 
+mod command;
+pub use command::StatusPoll;
+
+pub(crate) mod standard_request;
+pub use standard_request::{
+    DeviceFeature, DeviceStatus, EndpointStatus, InterfaceFeature, InterfaceStatus, PtmStatus,
+    SystemExitLatency, TestMode,
+};
+
 mod buffer;
 pub(crate) use buffer::Allocator;
 pub use buffer::Buffer;
 
+pub(crate) mod pool;
+pub use pool::{BufferPool, BufferPoolStats};
+
+mod stats;
+pub use stats::{EndpointStats, LatencyHistogram, TransferErrorCounts};
+
 pub(crate) mod internal;
 
 use crate::{descriptors::TransferType, platform};
 
 /// Transfer error.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum TransferError {
-    /// Transfer was cancelled or timed out.
+    /// Transfer was cancelled.
     Cancelled,
 
+    /// Transfer was cancelled because its timeout elapsed.
+    TimedOut,
+
     /// Endpoint in a STALL condition.
     ///
     /// This is used by the device to signal that an error occurred. For bulk
@@ -37,6 +60,10 @@ pub enum TransferError {
     Disconnected,
 
     /// Hardware issue or protocol violation.
+    ///
+    /// This is also returned by the standard requests such as
+    /// [`Device::get_status`][crate::Device::get_status] when the device
+    /// responds with fewer bytes than the USB specification requires.
     Fault,
 
     /// The request has an invalid argument or is not supported by this OS.
@@ -55,6 +82,7 @@ impl Display for TransferError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TransferError::Cancelled => write!(f, "transfer was cancelled"),
+            TransferError::TimedOut => write!(f, "transfer timed out"),
             TransferError::Stall => write!(f, "endpoint stalled"),
             TransferError::Disconnected => write!(f, "device disconnected"),
             TransferError::Fault => write!(f, "hardware fault or protocol violation"),
@@ -74,6 +102,7 @@ impl From<TransferError> for io::Error {
     fn from(value: TransferError) -> Self {
         match value {
             TransferError::Cancelled => io::Error::new(io::ErrorKind::Interrupted, value),
+            TransferError::TimedOut => io::Error::new(io::ErrorKind::TimedOut, value),
             TransferError::Stall => io::Error::new(io::ErrorKind::ConnectionReset, value),
             TransferError::Disconnected => io::Error::new(io::ErrorKind::ConnectionAborted, value),
             TransferError::Fault => io::Error::other(value),
@@ -132,12 +161,38 @@ impl EndpointType for Interrupt {
 }
 impl BulkOrInterrupt for Interrupt {}
 
+/// Identifier for a transfer submitted on an [`Endpoint`][`crate::Endpoint`].
+///
+/// Returned from [`Endpoint::submit`][`crate::Endpoint::submit`] and carried
+/// by the transfer's [`Completion`], so that completions can be correlated
+/// with submissions. IDs are unique per endpoint.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct TransferId(pub(crate) u64);
+
+/// How a transfer ended, from [`Completion::outcome`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransferOutcome {
+    /// The transfer completed successfully.
+    Completed,
+
+    /// The transfer was cancelled before it completed.
+    Cancelled,
+
+    /// The transfer was cancelled because its timeout elapsed.
+    TimedOut,
+
+    /// The transfer failed with another error.
+    Failed,
+}
+
 /// A completed transfer returned from [`Endpoint::next_complete`][`crate::Endpoint::next_complete`].
 ///
 /// A transfer can partially complete even in the case of failure or
 /// cancellation, thus the [`actual_len`][`Self::actual_len`] may be nonzero
 /// even if the [`status`][`Self::status`] is an error.
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct Completion {
     /// The transfer buffer.
     pub buffer: Buffer,
@@ -147,9 +202,62 @@ pub struct Completion {
 
     /// Status of the transfer.
     pub status: Result<(), TransferError>,
+
+    /// The ID returned when the transfer was submitted.
+    pub id: TransferId,
+
+    /// The tag passed to
+    /// [`Endpoint::submit_tagged`][`crate::Endpoint::submit_tagged`], or 0.
+    pub tag: u64,
+
+    /// When the transfer was submitted to the OS.
+    ///
+    /// Only recorded on Linux and Android.
+    pub submitted: Option<Instant>,
+
+    /// When the OS returned the transfer, recorded on the event thread
+    /// before the transfer was queued for [`Endpoint`][crate::Endpoint].
+    ///
+    /// Only recorded on Linux and Android.
+    pub completed: Option<Instant>,
 }
 
 impl Completion {
+    /// Create a completion for the platform backends. The ID and tag are
+    /// filled in by the `Endpoint`.
+    pub(crate) fn new(
+        buffer: Buffer,
+        actual_len: usize,
+        status: Result<(), TransferError>,
+    ) -> Self {
+        Completion {
+            buffer,
+            actual_len,
+            status,
+            id: TransferId::default(),
+            tag: 0,
+            submitted: None,
+            completed: None,
+        }
+    }
+
+    /// Get the time from submission to completion, if the platform recorded
+    /// the [timestamps](Self::submitted).
+    pub fn latency(&self) -> Option<Duration> {
+        Some(self.completed?.saturating_duration_since(self.submitted?))
+    }
+
+    /// Get whether the transfer completed, was cancelled, timed out, or
+    /// failed.
+    pub fn outcome(&self) -> TransferOutcome {
+        match self.status {
+            Ok(()) => TransferOutcome::Completed,
+            Err(TransferError::Cancelled) => TransferOutcome::Cancelled,
+            Err(TransferError::TimedOut) => TransferOutcome::TimedOut,
+            Err(_) => TransferOutcome::Failed,
+        }
+    }
+
     /// Ignore any partial completion, turning `self` into a `Result` containing
     /// either the completed buffer for a successful transfer or a
     /// `TransferError`.
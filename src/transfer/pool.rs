@@ -0,0 +1,206 @@
+use std::sync::{Arc, Mutex};
+
+use super::Buffer;
+
+/// A fixed set of pre-allocated transfer buffers shared between endpoints.
+///
+/// Created with [`Device::buffer_pool`][crate::Device::buffer_pool], which
+/// allocates the buffers for zero-copy transfers when the platform supports
+/// it. Cloning a `BufferPool` returns another handle to the same pool.
+///
+/// Buffers obtained from [`get`](Self::get) return to the pool automatically
+/// when dropped, including when they are dropped after being submitted to and
+/// returned from an [`Endpoint`][crate::Endpoint]. If every buffer is in use,
+/// `get` falls back to [`Buffer::new`] and the miss is counted in
+/// [`stats`](Self::stats).
+///
+/// The pool is not tied to a device, and its buffers can be submitted to any
+/// endpoint. Only the device that created the pool uses them zero-copy,
+/// though; other devices copy the data as with [`Buffer::new`].
+///
+/// A pool can be passed to
+/// [`EndpointRead::set_buffer_pool`][crate::io::EndpointRead::set_buffer_pool]
+/// and
+/// [`EndpointWrite::set_buffer_pool`][crate::io::EndpointWrite::set_buffer_pool]
+/// to allocate their transfers from it.
+#[derive(Clone)]
+pub struct BufferPool {
+    shared: Arc<PoolShared>,
+}
+
+pub(crate) struct PoolShared {
+    buffer_size: usize,
+    state: Mutex<PoolState>,
+}
+
+struct PoolState {
+    free: Vec<Buffer>,
+    stats: BufferPoolStats,
+}
+
+/// Usage statistics for a [`BufferPool`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BufferPoolStats {
+    /// Number of buffers owned by the pool.
+    pub buffers: usize,
+
+    /// Number of the pool's buffers that support zero-copy transfers.
+    pub zero_copy: usize,
+
+    /// Number of buffers currently available in the pool.
+    pub available: usize,
+
+    /// Number of requests served with a buffer from the pool.
+    pub hits: u64,
+
+    /// Number of requests made while the pool was exhausted, which were
+    /// served with a newly allocated buffer instead.
+    pub exhausted: u64,
+}
+
+impl BufferPool {
+    pub(crate) fn new(buffer_size: usize, count: usize, alloc: impl Fn(usize) -> Buffer) -> Self {
+        let free: Vec<Buffer> = (0..count).map(|_| alloc(buffer_size)).collect();
+        let stats = BufferPoolStats {
+            buffers: count,
+            zero_copy: free.iter().filter(|b| b.is_zero_copy()).count(),
+            available: count,
+            ..Default::default()
+        };
+
+        BufferPool {
+            shared: Arc::new(PoolShared {
+                buffer_size,
+                state: Mutex::new(PoolState { free, stats }),
+            }),
+        }
+    }
+
+    /// Get the size of each buffer in the pool.
+    pub fn buffer_size(&self) -> usize {
+        self.shared.buffer_size
+    }
+
+    /// Get an empty buffer from the pool.
+    ///
+    /// The buffer has a capacity and `requested_len` of
+    /// [`buffer_size`](Self::buffer_size). If no buffer is available, a new
+    /// buffer is allocated with [`Buffer::new`] and will not be added to the
+    /// pool when dropped.
+    pub fn get(&self) -> Buffer {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.free.pop() {
+            Some(mut buf) => {
+                state.stats.hits += 1;
+                state.stats.available = state.free.len();
+                drop(state);
+                buf.len = 0;
+                buf.requested_len = buf.capacity;
+                buf.pool = Some(self.shared.clone());
+                buf
+            }
+            None => {
+                state.stats.exhausted += 1;
+                drop(state);
+                Buffer::new(self.shared.buffer_size)
+            }
+        }
+    }
+
+    /// Get the pool's usage statistics.
+    pub fn stats(&self) -> BufferPoolStats {
+        self.shared.state.lock().unwrap().stats
+    }
+}
+
+impl std::fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("buffer_size", &self.shared.buffer_size)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl PoolShared {
+    /// Return a buffer's memory to the pool. Called from `Buffer::drop`.
+    pub(crate) fn put(&self, buf: Buffer) {
+        let mut state = self.state.lock().unwrap();
+        state.free.push(buf);
+        state.stats.available = state.free.len();
+    }
+}
+
+#[test]
+fn test_pool_reuse() {
+    let pool = BufferPool::new(64, 2, Buffer::new);
+
+    let a = pool.get();
+    let b = pool.get();
+    assert_eq!(a.requested_len(), 64);
+    assert_eq!(pool.stats().available, 0);
+
+    let c = pool.get();
+    assert!(c.pool.is_none());
+    assert_eq!(pool.stats().exhausted, 1);
+
+    drop(a);
+    drop(c);
+    assert_eq!(pool.stats().available, 1);
+
+    let mut d = pool.get();
+    d.extend_from_slice(&[1, 2, 3]);
+    assert_eq!(d.into_vec(), vec![1, 2, 3]);
+    drop(b);
+
+    let stats = pool.stats();
+    assert_eq!(stats.buffers, 2);
+    assert_eq!(stats.available, 2);
+    assert_eq!(stats.hits, 3);
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::{
+        platform::fake::FakeDevice,
+        transfer::{Bulk, In},
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn pool_buffers_return_from_transfers() {
+        let fake = FakeDevice::new();
+        let pool = fake.device().buffer_pool(64, 2);
+        let interface = fake.interface();
+
+        // Each buffer keeps its own pool through the transfer
+        let mut ep = interface.endpoint::<Bulk, In>(0x81).unwrap();
+        ep.submit(Buffer::new(64));
+        ep.submit(pool.get());
+        fake.complete_in(0x81, &[1]);
+        fake.complete_in(0x81, &[2]);
+        let unpooled = ep.wait_next_complete(TIMEOUT).unwrap();
+        assert!(unpooled.buffer.pool.is_none());
+        let pooled = ep.wait_next_complete(TIMEOUT).unwrap();
+        assert_eq!(pool.stats().available, 1);
+        drop(pooled);
+        assert_eq!(pool.stats().available, 2);
+
+        // Dropping the endpoint with pending transfers
+        ep.submit(pool.get());
+        ep.submit(pool.get());
+        fake.wait_submitted(0x81, 2);
+        assert_eq!(pool.stats().available, 0);
+        drop(ep);
+        let deadline = Instant::now() + TIMEOUT;
+        while pool.stats().available < 2 {
+            assert!(Instant::now() < deadline, "buffers not returned to pool");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use super::{ControlIn, ControlOut, ControlType, Recipient, TransferError};
+
+const REQUEST_GET_STATUS: u8 = 0x00;
+const REQUEST_CLEAR_FEATURE: u8 = 0x01;
+const REQUEST_SET_FEATURE: u8 = 0x03;
+const REQUEST_SYNCH_FRAME: u8 = 0x0C;
+const REQUEST_SET_SEL: u8 = 0x30;
+const REQUEST_SET_ISOCH_DELAY: u8 = 0x31;
+
+const STATUS_TYPE_STANDARD: u16 = 0x00;
+const STATUS_TYPE_PTM: u16 = 0x01;
+
+const FEATURE_ENDPOINT_HALT: u16 = 0;
+const FEATURE_FUNCTION_SUSPEND: u16 = 0;
+const FEATURE_DEVICE_REMOTE_WAKEUP: u16 = 1;
+const FEATURE_TEST_MODE: u16 = 2;
+const FEATURE_U1_ENABLE: u16 = 48;
+const FEATURE_U2_ENABLE: u16 = 49;
+const FEATURE_LTM_ENABLE: u16 = 50;
+
+/// Device status returned by a `GET_STATUS` request.
+///
+/// See USB 3.2 section 9.4.5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceStatus(u16);
+
+impl DeviceStatus {
+    /// Get the raw status bits.
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    /// The device is currently self-powered.
+    pub fn self_powered(&self) -> bool {
+        self.0 & (1 << 0) != 0
+    }
+
+    /// The device is enabled to signal remote wakeup.
+    pub fn remote_wakeup(&self) -> bool {
+        self.0 & (1 << 1) != 0
+    }
+
+    /// *(SuperSpeed)* The device is enabled to initiate U1 entry.
+    pub fn u1_enabled(&self) -> bool {
+        self.0 & (1 << 2) != 0
+    }
+
+    /// *(SuperSpeed)* The device is enabled to initiate U2 entry.
+    pub fn u2_enabled(&self) -> bool {
+        self.0 & (1 << 3) != 0
+    }
+
+    /// *(SuperSpeed)* The device is enabled to send Latency Tolerance
+    /// Messages.
+    pub fn ltm_enabled(&self) -> bool {
+        self.0 & (1 << 4) != 0
+    }
+}
+
+/// Precision Time Measurement status returned by a `GET_STATUS` request with
+/// the PTM status type.
+///
+/// See USB 3.2 section 9.4.5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtmStatus(u32);
+
+impl PtmStatus {
+    /// Get the raw status bits.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Link Delay Measurement is enabled.
+    pub fn ldm_enabled(&self) -> bool {
+        self.0 & (1 << 0) != 0
+    }
+
+    /// [`link_delay`][Self::link_delay] holds a valid measurement.
+    pub fn ldm_valid(&self) -> bool {
+        self.0 & (1 << 1) != 0
+    }
+
+    /// Measured link delay, in units of tIsochTimestampGranularity.
+    pub fn link_delay(&self) -> u16 {
+        (self.0 >> 16) as u16
+    }
+}
+
+/// Interface status returned by a `GET_STATUS` request.
+///
+/// See USB 3.2 section 9.4.5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterfaceStatus(u16);
+
+impl InterfaceStatus {
+    /// Get the raw status bits.
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    /// *(SuperSpeed)* The function supports remote wakeup.
+    pub fn remote_wake_capable(&self) -> bool {
+        self.0 & (1 << 0) != 0
+    }
+
+    /// *(SuperSpeed)* The function is enabled to signal remote wakeup.
+    pub fn remote_wakeup(&self) -> bool {
+        self.0 & (1 << 1) != 0
+    }
+}
+
+/// Endpoint status returned by a `GET_STATUS` request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndpointStatus(u16);
+
+impl EndpointStatus {
+    /// Get the raw status bits.
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    /// The endpoint is halted.
+    pub fn halted(&self) -> bool {
+        self.0 & (1 << 0) != 0
+    }
+}
+
+/// Device feature for `SET_FEATURE` and `CLEAR_FEATURE` requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeviceFeature {
+    /// `DEVICE_REMOTE_WAKEUP`: allow the device to signal remote wakeup.
+    RemoteWakeup,
+
+    /// `TEST_MODE`: enter a USB 2.0 electrical test mode.
+    ///
+    /// The device must be power cycled to exit test mode; it can't be
+    /// cleared.
+    TestMode(TestMode),
+
+    /// `U1_ENABLE`: allow the device to initiate U1 entry.
+    U1Enable,
+
+    /// `U2_ENABLE`: allow the device to initiate U2 entry.
+    U2Enable,
+
+    /// `LTM_ENABLE`: allow the device to send Latency Tolerance Messages.
+    LtmEnable,
+}
+
+impl DeviceFeature {
+    fn selector_and_index(self) -> (u16, u16) {
+        match self {
+            DeviceFeature::RemoteWakeup => (FEATURE_DEVICE_REMOTE_WAKEUP, 0),
+            DeviceFeature::TestMode(mode) => (FEATURE_TEST_MODE, (mode as u16) << 8),
+            DeviceFeature::U1Enable => (FEATURE_U1_ENABLE, 0),
+            DeviceFeature::U2Enable => (FEATURE_U2_ENABLE, 0),
+            DeviceFeature::LtmEnable => (FEATURE_LTM_ENABLE, 0),
+        }
+    }
+}
+
+/// Test mode selector for [`DeviceFeature::TestMode`].
+///
+/// See USB 2.0 section 7.1.20.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TestMode {
+    /// `Test_J`
+    J = 0x01,
+
+    /// `Test_K`
+    K = 0x02,
+
+    /// `Test_SE0_NAK`
+    Se0Nak = 0x03,
+
+    /// `Test_Packet`
+    Packet = 0x04,
+
+    /// `Test_Force_Enable`
+    ForceEnable = 0x05,
+}
+
+/// Interface feature for `SET_FEATURE` and `CLEAR_FEATURE` requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InterfaceFeature {
+    /// `FUNCTION_SUSPEND`: set the function's suspend state and remote wake
+    /// enable.
+    FunctionSuspend {
+        /// Place the function in its low power suspend state.
+        low_power: bool,
+
+        /// Allow the function to signal remote wakeup.
+        remote_wake: bool,
+    },
+}
+
+impl InterfaceFeature {
+    fn selector_and_options(self) -> (u16, u8) {
+        match self {
+            InterfaceFeature::FunctionSuspend {
+                low_power,
+                remote_wake,
+            } => (
+                FEATURE_FUNCTION_SUSPEND,
+                (low_power as u8) | ((remote_wake as u8) << 1),
+            ),
+        }
+    }
+}
+
+/// System exit latency values sent by a `SET_SEL` request.
+///
+/// See USB 3.2 section 9.4.12.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemExitLatency {
+    /// U1 System Exit Latency, in microseconds.
+    pub u1_sel: u8,
+
+    /// U1 Device to Host Exit Latency, in microseconds.
+    pub u1_pel: u8,
+
+    /// U2 System Exit Latency, in microseconds.
+    pub u2_sel: u16,
+
+    /// U2 Device to Host Exit Latency, in microseconds.
+    pub u2_pel: u16,
+}
+
+impl SystemExitLatency {
+    pub(crate) fn to_bytes(self) -> [u8; 6] {
+        let [u2_sel_lo, u2_sel_hi] = self.u2_sel.to_le_bytes();
+        let [u2_pel_lo, u2_pel_hi] = self.u2_pel.to_le_bytes();
+        [
+            self.u1_sel,
+            self.u1_pel,
+            u2_sel_lo,
+            u2_sel_hi,
+            u2_pel_lo,
+            u2_pel_hi,
+        ]
+    }
+}
+
+fn get_status(recipient: Recipient, index: u16, status_type: u16, length: u16) -> ControlIn {
+    ControlIn {
+        control_type: ControlType::Standard,
+        recipient,
+        request: REQUEST_GET_STATUS,
+        value: status_type,
+        index,
+        length,
+    }
+}
+
+fn feature_request(
+    recipient: Recipient,
+    set: bool,
+    selector: u16,
+    index: u16,
+) -> ControlOut<'static> {
+    ControlOut {
+        control_type: ControlType::Standard,
+        recipient,
+        request: if set {
+            REQUEST_SET_FEATURE
+        } else {
+            REQUEST_CLEAR_FEATURE
+        },
+        value: selector,
+        index,
+        data: &[],
+    }
+}
+
+/// Take the fixed-size response of a standard request. A short response
+/// violates the protocol, so it is reported as [`TransferError::Fault`].
+fn response<const N: usize>(r: Result<Vec<u8>, TransferError>) -> Result<[u8; N], TransferError> {
+    r?.get(..N)
+        .and_then(|d| d.try_into().ok())
+        .ok_or(TransferError::Fault)
+}
+
+pub(crate) fn get_device_status() -> ControlIn {
+    get_status(Recipient::Device, 0, STATUS_TYPE_STANDARD, 2)
+}
+
+pub(crate) fn device_status(
+    r: Result<Vec<u8>, TransferError>,
+) -> Result<DeviceStatus, TransferError> {
+    response(r).map(|d| DeviceStatus(u16::from_le_bytes(d)))
+}
+
+pub(crate) fn get_ptm_status() -> ControlIn {
+    get_status(Recipient::Device, 0, STATUS_TYPE_PTM, 4)
+}
+
+pub(crate) fn ptm_status(r: Result<Vec<u8>, TransferError>) -> Result<PtmStatus, TransferError> {
+    response(r).map(|d| PtmStatus(u32::from_le_bytes(d)))
+}
+
+pub(crate) fn get_interface_status(interface: u8) -> ControlIn {
+    get_status(
+        Recipient::Interface,
+        interface as u16,
+        STATUS_TYPE_STANDARD,
+        2,
+    )
+}
+
+pub(crate) fn interface_status(
+    r: Result<Vec<u8>, TransferError>,
+) -> Result<InterfaceStatus, TransferError> {
+    response(r).map(|d| InterfaceStatus(u16::from_le_bytes(d)))
+}
+
+pub(crate) fn get_endpoint_status(endpoint: u8) -> ControlIn {
+    get_status(
+        Recipient::Endpoint,
+        endpoint as u16,
+        STATUS_TYPE_STANDARD,
+        2,
+    )
+}
+
+pub(crate) fn endpoint_status(
+    r: Result<Vec<u8>, TransferError>,
+) -> Result<EndpointStatus, TransferError> {
+    response(r).map(|d| EndpointStatus(u16::from_le_bytes(d)))
+}
+
+pub(crate) fn device_feature(feature: DeviceFeature, set: bool) -> ControlOut<'static> {
+    let (selector, index) = feature.selector_and_index();
+    feature_request(Recipient::Device, set, selector, index)
+}
+
+pub(crate) fn interface_feature(
+    interface: u8,
+    feature: InterfaceFeature,
+    set: bool,
+) -> ControlOut<'static> {
+    let (selector, options) = feature.selector_and_options();
+    let index = ((options as u16) << 8) | interface as u16;
+    feature_request(Recipient::Interface, set, selector, index)
+}
+
+pub(crate) fn endpoint_halt(endpoint: u8, set: bool) -> ControlOut<'static> {
+    feature_request(
+        Recipient::Endpoint,
+        set,
+        FEATURE_ENDPOINT_HALT,
+        endpoint as u16,
+    )
+}
+
+pub(crate) fn set_sel(data: &[u8; 6]) -> ControlOut<'_> {
+    ControlOut {
+        control_type: ControlType::Standard,
+        recipient: Recipient::Device,
+        request: REQUEST_SET_SEL,
+        value: 0,
+        index: 0,
+        data,
+    }
+}
+
+pub(crate) fn set_isoch_delay(delay_ns: u16) -> ControlOut<'static> {
+    ControlOut {
+        control_type: ControlType::Standard,
+        recipient: Recipient::Device,
+        request: REQUEST_SET_ISOCH_DELAY,
+        value: delay_ns,
+        index: 0,
+        data: &[],
+    }
+}
+
+pub(crate) fn synch_frame(endpoint: u8) -> ControlIn {
+    ControlIn {
+        control_type: ControlType::Standard,
+        recipient: Recipient::Endpoint,
+        request: REQUEST_SYNCH_FRAME,
+        value: 0,
+        index: endpoint as u16,
+        length: 2,
+    }
+}
+
+pub(crate) fn frame_number(r: Result<Vec<u8>, TransferError>) -> Result<u16, TransferError> {
+    response(r).map(u16::from_le_bytes)
+}
+
+#[test]
+fn test_standard_requests() {
+    let sel = SystemExitLatency {
+        u1_sel: 1,
+        u1_pel: 2,
+        u2_sel: 0x0304,
+        u2_pel: 0x0506,
+    };
+    assert_eq!(sel.to_bytes(), [1, 2, 4, 3, 6, 5]);
+
+    let setup = device_feature(DeviceFeature::TestMode(TestMode::Packet), true).setup_packet();
+    assert_eq!(setup, [0x00, 0x03, 0x02, 0x00, 0x00, 0x04, 0x00, 0x00]);
+
+    let suspend = InterfaceFeature::FunctionSuspend {
+        low_power: true,
+        remote_wake: true,
+    };
+    let setup = interface_feature(2, suspend, true).setup_packet();
+    assert_eq!(setup, [0x01, 0x03, 0x00, 0x00, 0x02, 0x03, 0x00, 0x00]);
+
+    assert_eq!(
+        get_endpoint_status(0x81).setup_packet(),
+        [0x82, 0x00, 0x00, 0x00, 0x81, 0x00, 0x02, 0x00]
+    );
+
+    let status = device_status(Ok(vec![0x1d, 0x00])).unwrap();
+    assert!(status.self_powered() && !status.remote_wakeup());
+    assert!(status.u1_enabled() && status.u2_enabled() && status.ltm_enabled());
+
+    let ptm = ptm_status(Ok(vec![0x03, 0x00, 0x34, 0x12])).unwrap();
+    assert!(ptm.ldm_enabled() && ptm.ldm_valid());
+    assert_eq!(ptm.link_delay(), 0x1234);
+
+    assert_eq!(endpoint_status(Ok(vec![0x01])), Err(TransferError::Fault));
+}
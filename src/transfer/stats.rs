@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use super::{Completion, TransferError};
+
+/// Counters for the transfers completed on an endpoint, from
+/// [`Endpoint::stats`][crate::Endpoint::stats].
+///
+/// Transfers are counted when they are returned from the endpoint, whether
+/// directly or through one of the adapters in [`nusb::io`][crate::io].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EndpointStats {
+    /// Number of transfers completed, including failed transfers.
+    pub transfers: u64,
+
+    /// Number of bytes transferred.
+    pub bytes: u64,
+
+    /// Number of failed transfers by error.
+    pub errors: TransferErrorCounts,
+
+    /// Time from submission to completion of each transfer, where the
+    /// platform records [timestamps](Completion::submitted).
+    pub latency: LatencyHistogram,
+}
+
+impl EndpointStats {
+    pub(crate) fn record(&mut self, c: &Completion) {
+        self.transfers += 1;
+        self.bytes += c.actual_len as u64;
+        if let Err(e) = c.status {
+            self.errors.record(e);
+        }
+        if let Some(latency) = c.latency() {
+            self.latency.record(latency);
+        }
+    }
+}
+
+/// Number of failed transfers for each [`TransferError`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TransferErrorCounts {
+    /// [`TransferError::Cancelled`]
+    pub cancelled: u64,
+
+    /// [`TransferError::TimedOut`]
+    pub timed_out: u64,
+
+    /// [`TransferError::Stall`]
+    pub stall: u64,
+
+    /// [`TransferError::Disconnected`]
+    pub disconnected: u64,
+
+    /// [`TransferError::Fault`]
+    pub fault: u64,
+
+    /// [`TransferError::InvalidArgument`]
+    pub invalid_argument: u64,
+
+    /// [`TransferError::Unknown`]
+    pub unknown: u64,
+}
+
+impl TransferErrorCounts {
+    /// Total number of failed transfers.
+    pub fn total(&self) -> u64 {
+        self.cancelled
+            + self.timed_out
+            + self.stall
+            + self.disconnected
+            + self.fault
+            + self.invalid_argument
+            + self.unknown
+    }
+
+    fn record(&mut self, e: TransferError) {
+        let count = match e {
+            TransferError::Cancelled => &mut self.cancelled,
+            TransferError::TimedOut => &mut self.timed_out,
+            TransferError::Stall => &mut self.stall,
+            TransferError::Disconnected => &mut self.disconnected,
+            TransferError::Fault => &mut self.fault,
+            TransferError::InvalidArgument => &mut self.invalid_argument,
+            TransferError::Unknown(_) => &mut self.unknown,
+        };
+        *count += 1;
+    }
+}
+
+const LATENCY_BUCKETS: usize = 32;
+
+/// Histogram of transfer latencies with power-of-two buckets.
+///
+/// Bucket `i` counts latencies below `2^i` microseconds that don't fit in an
+/// earlier bucket. The last bucket also counts all longer latencies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    buckets: [u64; LATENCY_BUCKETS],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl LatencyHistogram {
+    pub(crate) fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros();
+        let bucket = (u128::BITS - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(LATENCY_BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.total = self.total.saturating_add(latency);
+        self.max = self.max.max(latency);
+    }
+
+    /// Number of latencies recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Mean latency, or `None` if nothing was recorded.
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count).unwrap_or(u32::MAX);
+        (count > 0).then(|| self.total / count)
+    }
+
+    /// Longest latency recorded.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Iterate over the buckets as the bucket's exclusive upper bound and the
+    /// number of latencies in the bucket.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| (Duration::from_micros(1 << i), n))
+    }
+
+    /// Estimate the latency below which the fraction `q` of transfers
+    /// completed, as the upper bound of the bucket containing that quantile.
+    ///
+    /// Returns `None` if nothing was recorded.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let target = ((self.count as f64) * q.clamp(0.0, 1.0)).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        self.buckets().find_map(|(bound, n)| {
+            seen += n;
+            (seen >= target).then_some(bound.min(self.max))
+        })
+    }
+}
+
+#[test]
+fn test_latency_histogram() {
+    let mut h = LatencyHistogram::default();
+    assert_eq!(h.mean(), None);
+    assert_eq!(h.quantile(0.5), None);
+
+    h.record(Duration::from_nanos(500));
+    h.record(Duration::from_micros(3));
+    h.record(Duration::from_micros(100));
+    h.record(Duration::from_millis(5));
+
+    let buckets: Vec<u64> = h.buckets().map(|(_, n)| n).collect();
+    assert_eq!(buckets[0], 1);
+    assert_eq!(buckets[2], 1);
+    assert_eq!(buckets[7], 1);
+    assert_eq!(buckets[13], 1);
+    assert_eq!(h.count(), 4);
+    assert_eq!(h.max(), Duration::from_millis(5));
+    assert_eq!(h.quantile(0.5), Some(Duration::from_micros(4)));
+    assert_eq!(h.quantile(1.0), Some(Duration::from_millis(5)));
+}